@@ -5,7 +5,7 @@
 # Usage: ./run_prot1.sh <size>
 #
 # 1) Ensure a size argument is provided
-# 2) Run setup with `--size <size>`
+# 2) Run fde setup with `--size <size>`
 # 3) Record start-time, then launch client, smart_contract, server in the background,
 #    redirecting each of their stdout into its own temp file
 # 4) Wait for all three to exit
@@ -23,19 +23,19 @@ if [ "$#" -ne 1 ]; then
 fi
 SIZE="$1"
 
-# 2) Run setup with --size <size> (exit immediately if it fails)
-./target/release/setup --size "$SIZE"
+# 2) Run fde setup with --size <size> (exit immediately if it fails)
+./target/release/fde setup --size "$SIZE"
 
 # 3) Record start time (seconds since the epoch) and launch each program in the background
 START_TIME=$(date +%s)
 
-./target/release/client1         > client_out.txt       2>&1 &
+./target/release/fde client --protocol 1 > client_out.txt       2>&1 &
 PID_CLIENT=$!
 
-./target/release/smart_contract1 > sc_out.txt           2>&1 &
+./target/release/fde contract --protocol 1 > sc_out.txt           2>&1 &
 PID_SC=$!
 
-./target/release/server1        > server_out.txt       2>&1 &
+./target/release/fde server --protocol 1 > server_out.txt       2>&1 &
 PID_SERVER=$!
 
 # 4) Wait for all three to finish
@@ -0,0 +1,74 @@
+//! Benchmarks the homomorphic hash of the data alone, with the boolean, shortint or integer
+//! backend : it encrypts the padded data, hashes it homomorphically and checks the decrypted hash
+//! against the plaintext one.
+use std::error::Error;
+use std::time::{Duration, Instant};
+use fde_protocols::config::Config;
+use fde_protocols::homomorphic_functions::{
+    bools_to_hex, decrypt_bits_shortint, decrypt_bools, decrypt_bools_uint64, encrypt_bits_shortint, encrypt_bools,
+    encrypt_bools_uint64, gen_integer_keys, gen_shortint_keys, sponge_block_bootstraps, Sha3Backend,
+};
+use tfhe::boolean::prelude::gen_keys;
+use crate::setup::{read_source, Source};
+
+pub fn bench(source: &Source, backend: Sha3Backend, config: &Config) -> Result<(), Box<dyn Error>> {
+    // 1 : retrieve the data and pad it
+    let data = read_source(source)?;
+    let sponge = config.hash_function.sponge();
+    let padded_input = sponge.pad_bytes(&data);
+    let blocks = padded_input.len() / sponge.rate();
+
+    // 2 : generate the keys, encrypt and hash with the chosen backend
+    let (keygen, encrypt, hash, ct_size, bits) = match backend {
+        Sha3Backend::Boolean => {
+            let (keygen, (ck, sk)) = timed(gen_keys);
+            let (encrypt, ct) = timed(|| encrypt_bools(padded_input, &ck));
+            let ct_size = bincode::serialized_size(&ct)?;
+            let (hash, hash_ct) = timed(|| sponge.hash(&ct, &sk));
+            (keygen, encrypt, hash, ct_size, decrypt_bools(&hash_ct?, &ck))
+        }
+        Sha3Backend::Shortint => {
+            let (keygen, (ck, sk)) = timed(gen_shortint_keys);
+            let (encrypt, ct) = timed(|| encrypt_bits_shortint(&padded_input, &ck));
+            let ct_size = bincode::serialized_size(&ct)?;
+            let (hash, hash_ct) = timed(|| sponge.hash_shortint(&ct, &sk));
+            (keygen, encrypt, hash, ct_size, decrypt_bits_shortint(&hash_ct?, &ck))
+        }
+        Sha3Backend::Integer => {
+            let (keygen, (ck, sk)) = timed(gen_integer_keys);
+            let (encrypt, ct) = timed(|| encrypt_bools_uint64(&padded_input, &ck));
+            let ct_size = bincode::serialized_size(&ct)?;
+            let (hash, hash_ct) = timed(|| sponge.hash_integer(&ct, &sk));
+            // the last lane of the hash may hold bits after the output length
+            let mut bits = decrypt_bools_uint64(&hash_ct?, &ck);
+            bits.truncate(sponge.output_len());
+            (keygen, encrypt, hash, ct_size, bits)
+        }
+    };
+
+    // 3 : check the decrypted hash
+    let expected = config.hash_function.hex_digest(&data);
+    if bools_to_hex(&bits) != expected {
+        return Err(format!("the homomorphic hash does not match {}", expected).into());
+    }
+    println!("Bench ▶ hashed {} bytes ({} blocks) with the {:?} backend", data.len(), blocks, backend);
+
+    // 4 : print statistics about the run
+    println!(
+        "HASH COMPUTATION COST IS {:?} (keygen : {:?}, encrypt : {:?}, hash : {:?})",
+        hash, keygen, encrypt, hash
+    );
+    println!("CIPHERTEXT SIZE: {} bytes", ct_size);
+    match sponge_block_bootstraps(&sponge, backend) {
+        Some(bootstraps) => println!("BOOTSTRAPS PER BLOCK: {}", bootstraps),
+        None => println!("BOOTSTRAPS PER BLOCK: not counted for the {:?} backend", backend),
+    }
+    Ok(())
+}
+
+/// runs `f` and returns how long it took along with its result
+fn timed<T>(f: impl FnOnce() -> T) -> (Duration, T) {
+    let start = Instant::now();
+    let result = f();
+    (start.elapsed(), result)
+}
@@ -0,0 +1,159 @@
+//! This binary runs one role of the fair data exchange protocols (or setups the data to exchange).
+//! The protocol, the data and hash files and the endpoints come from an optional TOML config file,
+//! and the command-line options take precedence over it.
+mod bench;
+mod prot1;
+mod prot2;
+mod setup;
+
+use std::env;
+use std::error::Error;
+use std::process;
+use std::thread;
+use std::time::Instant;
+use fde_protocols::config::{Config, Role};
+use fde_protocols::error::FdeError;
+use fde_protocols::homomorphic_functions::Sha3Backend;
+use setup::Source;
+
+/// prints usage of the binary in case of incorrect usage
+fn print_usage_and_exit(program: &str) -> ! {
+    eprintln!(
+        "Usage:\n  {0} setup (--size <num_bytes> | --filename <name.txt>) [options]\n  \
+         {0} bench (--size <num_bytes> | --filename <name.txt>) [--sha3-backend boolean|shortint|integer] [options]\n  \
+         {0} (server | client | contract) [--protocol 1|2] [--listen <addr>] [--peer <addr>] [options]\n\
+         Options:\n  --config <file.toml>  read the configuration from a TOML file\n  \
+         --data <path>         data file (default data.txt)\n  \
+         --hash <path>         hash file (default hash.txt)\n  \
+         --connect-timeout <s> seconds spent retrying to connect to the peer (default 120)\n  \
+         --read-timeout <s>    abort if the peer stalls for this many seconds (default none)\n  \
+         --write-timeout <s>   abort if a write stalls for this many seconds (default none)\n  \
+         --reveal-deadline <s> seconds the server has to reveal before the contract refunds the client (default 600)\n  \
+         --price <amount>      price of the data, paid by the client in escrow (default 100)\n  \
+         --deposit <amount>    initial balance of the client on the contract's ledger (default 1000)\n  \
+         --key-opening <kind>  what the server reveals in Protocol I : client_key (default) or lwe_key\n  \
+         --hash-function <h>   hash of the data, the key and a : sha3_256 (default) or keccak256\n  \
+         --cipher <c>          stream cipher the server proposes in Protocol II : trivium (default), kreyvium or aes128_ctr\n  \
+         --accepted-ciphers <c,..> stream ciphers the client accepts in Protocol II (default all of them)\n  \
+         --chunk-size <bytes>  commit to the Merkle root of chunks of this size, and sell chunks in Protocol II (default none)\n  \
+         --chunks <i,..>       indices of the chunks the client buys in Protocol II (default 0)\n  \
+         --challenge-field <f> arithmetic of the client's challenge in Protocol II : mod2_256 (default), bn254 or secp256k1\n  \
+         --sha3-backend <b>    TFHE backend of the homomorphic hash in bench : boolean (default), shortint or integer",
+        program
+    );
+    process::exit(1);
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // 1 : collect command-line arguments
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || !args.len().is_multiple_of(2) {
+        print_usage_and_exit(&args[0]);
+    }
+    let role = match args[1].as_str() {
+        "server" => Some(Role::Server),
+        "client" => Some(Role::Client),
+        "contract" => Some(Role::Contract),
+        "setup" | "bench" => None,
+        _ => print_usage_and_exit(&args[0]),
+    };
+    let options: Vec<(&str, &str)> = args[2..].chunks(2).map(|o| (o[0].as_str(), o[1].as_str())).collect();
+
+    // 2 : read the config file first, so that the other options override it
+    let mut config = match options.iter().find(|(name, _)| *name == "--config") {
+        Some((_, path)) => Config::load(path).map_err(|e| format!("Failed to read `{}`: {}", path, e))?,
+        None => Config::default(),
+    };
+    let bench = args[1] == "bench";
+    let mut source = None;
+    let mut backend = Sha3Backend::default();
+    for (name, value) in options {
+        match (name, role) {
+            ("--config", _) => {}
+            ("--data", _) => config.data = value.to_string(),
+            ("--hash", _) => config.hash = value.to_string(),
+            ("--protocol", Some(_)) => {
+                let protocol = value.parse().map_err(|e| format!("Invalid protocol `{}`: {}", value, e))?;
+                config.set_protocol(protocol).map_err(|e| e.to_string())?;
+            }
+            ("--listen", Some(role)) => config.endpoint_mut(role).listen = value.to_string(),
+            ("--peer", Some(role)) => config.endpoint_mut(role).peer = value.to_string(),
+            ("--connect-timeout", Some(_)) => config.connect_timeout = parse_secs(name, value)?,
+            ("--read-timeout", Some(_)) => config.read_timeout = Some(parse_secs(name, value)?),
+            ("--write-timeout", Some(_)) => config.write_timeout = Some(parse_secs(name, value)?),
+            ("--reveal-deadline", Some(Role::Contract)) => config.reveal_deadline = parse_secs(name, value)?,
+            ("--price", Some(Role::Client | Role::Contract)) => config.price = parse_amount(name, value)?,
+            ("--deposit", Some(Role::Contract)) => config.client_deposit = parse_amount(name, value)?,
+            ("--key-opening", Some(Role::Server | Role::Contract)) => config.key_opening = value.parse().map_err(|e: FdeError| e.to_string())?,
+            ("--hash-function", None | Some(Role::Server | Role::Contract)) => {
+                config.hash_function = value.parse().map_err(|e: FdeError| e.to_string())?;
+            }
+            ("--cipher", Some(Role::Server)) => config.cipher = value.parse().map_err(|e: FdeError| e.to_string())?,
+            ("--accepted-ciphers", Some(Role::Client)) => {
+                config.accepted_ciphers = value.split(',').map(str::parse).collect::<Result<_, FdeError>>().map_err(|e| e.to_string())?;
+            }
+            ("--challenge-field", Some(Role::Client)) => {
+                config.challenge_field = value.parse().map_err(|e: FdeError| e.to_string())?;
+            }
+            ("--chunk-size", None | Some(Role::Server | Role::Client)) => {
+                let chunk_size = value.parse().map_err(|e| format!("Invalid number for --chunk-size (`{}`): {}", value, e))?;
+                config.set_chunk_size(Some(chunk_size)).map_err(|e| e.to_string())?;
+            }
+            ("--chunks", Some(Role::Client)) => {
+                config.chunks = value.split(',').map(str::parse).collect::<Result<_, _>>()
+                    .map_err(|e| format!("Invalid chunk indices `{}`: {}", value, e))?;
+            }
+            ("--size", None) => {
+                let n = value.parse().map_err(|e| format!("Invalid number for --size (`{}`): {}", value, e))?;
+                source = Some(Source::Size(n));
+            }
+            ("--filename", None) => source = Some(Source::File(value.to_string())),
+            ("--sha3-backend", None) if bench => backend = value.parse().map_err(|e: FdeError| e.to_string())?,
+            _ => print_usage_and_exit(&args[0]),
+        }
+    }
+
+    // 3 : run the role, a failure (e.g. a peer which timed out) aborts it
+    let result = match (role, config.protocol) {
+        (None, _) => match source {
+            Some(source) if bench => bench::bench(&source, backend, &config),
+            Some(source) => setup::setup(&source, &config),
+            None => print_usage_and_exit(&args[0]),
+        },
+        (Some(Role::Server), 1) => prot1::server(&config),
+        (Some(Role::Client), 1) => prot1::client(&config),
+        (Some(Role::Contract), 1) => prot1::contract(&config),
+        (Some(Role::Server), _) => prot2::server(&config),
+        (Some(Role::Client), _) => prot2::client(&config),
+        (Some(Role::Contract), _) => prot2::contract(&config),
+    };
+    if let Err(e) = result {
+        let name = match role {
+            Some(Role::Server) => "Server",
+            Some(Role::Client) => "Client",
+            Some(Role::Contract) => "Smart Contract",
+            None if bench => "Bench",
+            None => "Setup",
+        };
+        eprintln!("{} ▶ aborting : {}", name, e);
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// sleeps until `deadline`, if there is one
+fn wait_until(deadline: Option<Instant>) {
+    if let Some(deadline) = deadline {
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// parses a number of seconds given to the option `name`
+fn parse_secs(name: &str, value: &str) -> Result<u64, String> {
+    value.parse().map_err(|e| format!("Invalid number of seconds for {} (`{}`): {}", name, value, e))
+}
+
+/// parses an amount of money given to the option `name`
+fn parse_amount(name: &str, value: &str) -> Result<u64, String> {
+    value.parse().map_err(|e| format!("Invalid amount for {} (`{}`): {}", name, value, e))
+}
@@ -0,0 +1,297 @@
+//! The three roles of Protocol I, a protocol for fair data exchange using homomorphic encryption
+use std::error::Error;
+use std::fs;
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+use fde_protocols::config::Config;
+use fde_protocols::gas::{deadline_cost, prot1_submission_cost, prot1_reveal_cost};
+use fde_protocols::ledger::Ledger;
+use crate::wait_until;
+use fde_protocols::prot_utils::*;
+use fde_protocols::protocol1::{CiphertextBlock, CiphertextHeader, Client1, Contract1, Reveal, Server1, Settlement, Submission};
+use fde_protocols::transport::{recv_message, recv_message_before, send_message, FramedTransport, TcpTransport};
+
+/// Runs the server : it owns the data
+pub fn server(config: &Config) -> Result<(), Box<dyn Error>> {
+    // listen right away, so that the peers can be started in any order
+    let options = config.tcp_options();
+    let listener = TcpListener::bind(config.server.listen.as_str())
+        .map_err(|e| format!("Failed to listen on {}: {}", config.server.listen, e))?;
+
+    // 1 : retrieve the data
+    let data = fs::read(&config.data).map_err(|e| {
+        format!(
+            "Failed to read `{}`: {}",
+            config.data, e
+        )
+    })?;
+
+    // 2 : pad and encrypt the data homomorphically, and commit to the secret key
+    let mut time_recap: String = String::new();
+    let start = Instant::now();
+    let (mut server, bundle) = Server1::with_options(data.as_slice(), config.key_opening, config.hash_function)?;
+    let time = start.elapsed();
+    time_recap.push_str(&format!(" (pad and encrypt : {:?}, ", time));
+    let mut full_time = time;
+
+    // 3 : send the commitment and the public key to the client, then stream the encrypted data
+    // block by block
+    let session_id = new_session_id();
+    let mut client_conn =
+        FramedTransport::new(TcpTransport::connect(config.server.peer.as_str(), &options).map_err(|e| format!("Failed to connect to Client: {}", e))?, session_id);
+    let (header, blocks) = bundle.into_stream();
+    send_message(&mut client_conn, &header).map_err(|e| format!("Failed to write data to Client: {}", e))?;
+    for block in &blocks {
+        send_message(&mut client_conn, block).map_err(|e| format!("Failed to write data to Client: {}", e))?;
+    }
+    println!("Server ▶ sent (ct, pk, com) off-chain to Client");
+    client_conn.get_ref().shutdown().map_err(|e| format!("Failed to shutdown Client: {}", e))?;
+    println!("Server ▶ shutdown Client");
+
+    // 4:  Listen to the smart contract for Hct, H, com
+    let mut sc_conn =
+        FramedTransport::new(TcpTransport::accept(&listener, &options).map_err(|e| format!("Failed to accept connection from SmartContract: {}", e))?, session_id);
+    let (submission, _): (Submission, usize) = recv_message(&mut sc_conn)?;
+
+    // 5 : run the verify function
+    println!("Server ▶ Verifying client's inputs");
+    let start = Instant::now();
+    let reveal = server.on_submission(submission)?;
+    let time = start.elapsed();
+    time_recap.push_str(&format!(" verify : {:?})", time));
+    full_time += time;
+
+    // 6 : send the secret key to the smart contract
+    send_message(&mut sc_conn, &reveal).map_err(|e| format!("Failed to write data to SmartContract: {}", e))?;
+    println!("Server ▶ sent (status, opening) on‐chain to SmartContract");
+
+    // 7 : wait for the final success signal from smart contract
+    let (settlement, _): (Settlement, usize) = recv_message(&mut sc_conn)?;
+    server.on_settlement(&settlement)?;
+    println!("Server ▶ final outcome from SmartContract = {}", settlement.status);
+    println!("Server ▶ done.");
+
+    // 8 : print statistics about the run
+    let mut beginning_time_string = String::new();
+    beginning_time_string.push_str(&format!(
+        "SERVER COMPUTATION COST IS {:?}" , full_time
+    ));
+    beginning_time_string.push_str(time_recap.as_str());
+    println!("{}", beginning_time_string);
+    Ok(())
+}
+
+/// Runs the client : it buys the data whose hash is in the hash file
+pub fn client(config: &Config) -> Result<(), Box<dyn Error>> {
+    // listen right away, so that the peers can be started in any order
+    let options = config.tcp_options();
+    let listener = TcpListener::bind(config.client.listen.as_str())
+        .map_err(|e| format!("Failed to listen on {}: {}", config.client.listen, e))?;
+
+    // 1 : retrieve the hash of the data
+    let hash_data = fs::read_to_string(&config.hash).map_err(|e| {
+        format!(
+            "Failed to read `{}`: {}",
+            config.hash, e
+        )
+    })?;
+    let mut client = Client1::new(hash_data).with_payment(config.client_account.clone(), config.price);
+
+    let mut time_recap: String = String::new();
+
+    // 2 : wait for the server to send evk and com, and unserialize them
+    println!("Client ▶ listening on {} …", config.client.listen);
+    let mut server_conn =
+        FramedTransport::joining(TcpTransport::accept(&listener, &options).map_err(|e| format!("Failed to accept connection from Server: {}", e))?);
+    println!("Client ▶ accepted connection from Server");
+
+    let (header, mut len_comm): (CiphertextHeader, usize) = recv_message(&mut server_conn)?;
+    let pk_len = bincode::serialized_size(&header.pk)?;
+    let com_len = bincode::serialized_size(&header.com)?;
+    let nb_blocks = header.blocks;
+    let start = Instant::now();
+    client.on_header(header)?;
+    let mut time = start.elapsed();
+
+    // 3 : compute the hash of the data homomorphically, absorbing each block as soon as it is
+    // received
+    let mut ct_len = 0;
+    for _ in 0..nb_blocks {
+        let (block, block_len): (CiphertextBlock, usize) = recv_message(&mut server_conn)?;
+        len_comm += block_len;
+        ct_len += bincode::serialized_size(&block.ct)?;
+        let start = Instant::now();
+        client.on_block(block)?;
+        time += start.elapsed();
+    }
+    let start = Instant::now();
+    let submission = client.on_last_block()?;
+    time += start.elapsed();
+
+    println!(
+        "Client ▶ read {} bytes total from Server (JSON).",
+        len_comm
+    );
+
+    let com_off_chain = format!(
+        "OFF-CHAIN COMMUNICATION COST: {} bytes (ct is {} bytes, pk is {} bytes, com is {} bytes)\n",
+        len_comm,
+        ct_len,
+        pk_len,
+        com_len,
+    );
+
+    time_recap.push_str(&format!(" (homomorphic hash time is : {:?},", time));
+    let mut full_time = time;
+    println!("Client ▶ computed Hct = SHA3(ct)");
+
+    // 4 : send the hash and homomorphic hash to the smart contract,
+    println!("Client ▶ connecting to SmartContract at {} …", config.client.peer);
+    let session_id = server_conn.session_id().ok_or("No exchange was opened")?;
+    let mut sc_conn =
+        FramedTransport::new(TcpTransport::connect(config.client.peer.as_str(), &options).map_err(|e| format!("Failed to connect to SmartContract: {}", e))?, session_id);
+    send_message(&mut sc_conn, &submission).map_err(|e| format!("Failed to write data to SmartContract: {}", e))?;
+    println!("Client ▶ sent (H, Hct, Com) on‐chain to SmartContract");
+
+    // 5 : Wait for the secret key / status message (if the protocol suceeded or not),
+    // in a real scenario the secret key would be public at that point
+    // and the smart contract wouldn't have had to send it
+    let (settlement, _): (Settlement, usize) = recv_message(&mut sc_conn)?;
+
+    if settlement.status == ABORT {
+        let _ = client.on_settlement(&settlement);
+        println!("Client ▶ final outcome from SmartContract = ABORT");
+        println!("Client ▶ done.");
+    }else{
+        println!("Client ▶ final outcome from SmartContract = SUCCESS");
+        println!("Client ▶ decrypting the data....");
+
+        // 6 : decrypt the data with the secret key and check that it was the expected data
+        let start = Instant::now();
+        let data = client.on_settlement(&settlement);
+        let time = start.elapsed();
+        time_recap.push_str(&format!(" decryption time is : {:?})", time));
+        full_time += time;
+
+        match data {
+            Ok(_) => println!("Client RETRIEVED THE EXPECTED DATA"),
+            Err(e) => println!("Client DID NOT RETRIEVE THE EXPECTED DATA ({})", e),
+        }
+    }
+
+    // 7 : Print some statistics about the run
+    let mut beginning_time_string = String::new();
+    beginning_time_string.push_str(&format!(
+        "CLIENT COMPUTATION COST IS {:?}" , full_time
+    ));
+    beginning_time_string.push_str(time_recap.as_str());
+    println!("{}", beginning_time_string);
+    println!("{}", com_off_chain);
+    Ok(())
+}
+
+/// Runs the smart contract
+pub fn contract(config: &Config) -> Result<(), Box<dyn Error>> {
+    // listen right away, so that the peers can be started in any order
+    let options = config.tcp_options();
+    let listener = TcpListener::bind(config.contract.listen.as_str())
+        .map_err(|e| format!("Failed to listen on {}: {}", config.contract.listen, e))?;
+
+    // 1 : wait for the client to send Hct, H and com
+    println!("Smart Contract ▶ listening on {} …", config.contract.listen);
+    let mut client_conn =
+        FramedTransport::joining(TcpTransport::accept(&listener, &options).map_err(|e| format!("Failed to accept connection from Client: {}", e))?);
+    println!("Smart Contract ▶ accepted connection from client");
+    let mut ledger = Ledger::new();
+    ledger.deposit(&config.client_account, config.client_deposit);
+    let mut contract = Contract1::new()
+        .with_deadline(Duration::from_secs(config.reveal_deadline))
+        .with_ledger(ledger, config.terms())
+        .with_key_opening(config.key_opening);
+    let mut time_recap: String = String::new();
+    time_recap.push_str(&format!(
+        "SMART CONTRACT COMPUTATION COST {}",
+        ""
+    ));
+
+    // 1a : unserialize the messages
+    let (submission, len_comm): (Submission, usize) = recv_message(&mut client_conn)?;
+    println!(
+        "Smart Contract ▶ read {} bytes total from Client (JSON).",
+        len_comm
+    );
+    let hash_enc_len = bincode::serialized_size(&submission.hash_ct)?;
+    let hash_len = bincode::serialized_size(&submission.hash)?;
+    let com_len = bincode::serialized_size(&submission.com)?;
+
+    let submission_gas = prot1_submission_cost(&submission)?;
+
+    // 1b : lock the client's payment in escrow, the exchange stops here if the client cannot pay
+    if let Err(e) = contract.on_submission(submission.clone()) {
+        let _ = send_message(&mut client_conn, &Settlement { status: ABORT, secret_key: vec![] });
+        return Err(e.into());
+    }
+
+    // (bonus : send the data to the server, wouldn't be needed in real life where that data
+    // would have been now public on the blockchain)
+    let session_id = client_conn.session_id().ok_or("No exchange was opened")?;
+    let mut server_conn =
+        FramedTransport::new(TcpTransport::connect(config.contract.peer.as_str(), &options).map_err(|e| format!("Failed to connect to Server: {}", e))?, session_id);
+    send_message(&mut server_conn, &submission).map_err(|e| format!("Failed to write data to Server: {}", e))?;
+
+    // 2 : read the opening from the server, and the server's status, before the deadline
+    let (settlement, op_len, reveal_gas) = match recv_message_before::<Reveal>(&mut server_conn, contract.deadline()) {
+        Ok((reveal, op_len)) => {
+            let reveal_gas = prot1_reveal_cost(&submission, &reveal, contract.key_opening())?;
+            // 3 : run Verify function (the contract aborts directly if the server aborted)
+            let server_aborted = reveal.status == ABORT;
+            let start =  Instant::now();
+            let settlement = contract.on_reveal(reveal)?;
+            let time = start.elapsed();
+            if !server_aborted {
+                time_recap.push_str(&format!("SMART CONTRACT COMPUTATION COST IS {:?}" , time));
+                time_recap.push_str(&format!(" (verify : {:?})", time));
+            }
+            (settlement, op_len, reveal_gas)
+        }
+        Err(e) => {
+            // 3' : the server did not reveal, the contract aborts at the deadline and refunds the client
+            println!("Smart Contract ▶ no opening from the Server ({}), waiting for the deadline", e);
+            wait_until(contract.deadline());
+            (contract.on_deadline()?, 0, deadline_cost())
+        }
+    };
+
+    // 4 : send the final status to client and server, and secret key to client
+    send_message(&mut client_conn, &settlement).map_err(|e| format!("Failed to write data to Client: {}", e))?;
+    client_conn.get_ref().shutdown().map_err(|e| format!("Failed to shutdown Client: {}", e))?;
+    if let Err(e) = send_message(&mut server_conn, &settlement) {
+        println!("Smart Contract ▶ could not notify the Server: {}", e);
+    }
+    let _ = server_conn.get_ref().shutdown();
+
+    // 5 : print some statistics about the run
+    println!("SmartContract ▶ estimated gas of the submission : {}", submission_gas);
+    println!("SmartContract ▶ estimated gas of the reveal : {}", reveal_gas);
+    for event in contract.events() {
+        println!("SmartContract ▶ event {:?}", event);
+    }
+    for account in [&config.client_account, &config.server_account] {
+        println!("SmartContract ▶ balance of {} = {}", account, contract.ledger().balance(account));
+    }
+    println!("SmartContract ▶ final outcome from SmartContract = {}", settlement.status==SUCCESS);
+    println!("SmartContract ▶ done.");
+    println!("{}", time_recap);
+    println!(
+        "ON-CHAIN COMMUNICATION COST: {} bytes (Hct = {}, H = {}, com = {}, op = {}) . ESTIMATED GAS: {} (submission = {}, reveal = {}) .",
+        len_comm + op_len,
+        hash_enc_len,
+        hash_len,
+        com_len,
+        op_len,
+        submission_gas.total() + reveal_gas.total(),
+        submission_gas.total(),
+        reveal_gas.total(),
+    );
+    Ok(())
+}
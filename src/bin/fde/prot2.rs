@@ -0,0 +1,324 @@
+//! The three roles of Protocol II, a protocol for fair data exchange using hybrid homomorphic encryption.
+//! In the chunked variant, the client opens the exchange by sending the indices of the chunks it
+//! buys to the server, which sends them along with their Merkle proofs.
+use std::error::Error;
+use std::fs;
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+use fde_protocols::config::Config;
+use fde_protocols::gas::{deadline_cost, prot2_submission_cost, prot2_reveal_cost};
+use fde_protocols::ledger::Ledger;
+use crate::wait_until;
+use fde_protocols::prot_utils::*;
+use fde_protocols::protocol2::{Challenge, ChunkRequest, CiphertextBundle, Client2, Contract2, Reveal, Server2, Settlement, Submission};
+use fde_protocols::transport::{recv_message, recv_message_before, send_message, FramedTransport, TcpTransport};
+
+/// Runs the server : it owns the data
+pub fn server(config: &Config) -> Result<(), Box<dyn Error>> {
+    // listen right away, so that the peers can be started in any order
+    let options = config.tcp_options();
+    let listener = TcpListener::bind(config.server.listen.as_str())
+        .map_err(|e| format!("Failed to listen on {}: {}", config.server.listen, e))?;
+
+    // 1 : retrieve the data
+    println!("Server ▶ Starting...");
+    let data = fs::read(&config.data).map_err(|e| {
+        format!(
+            "Failed to read `{}`: {}",
+            config.data, e
+        )
+    })?;
+
+    // 1a : connect to the client, which opens the exchange with the chunks it buys in the
+    // chunked variant
+    let client_stream = TcpTransport::connect(config.server.peer.as_str(), &options).map_err(|e| format!("Failed to connect to Client: {}", e))?;
+    let (mut client_conn, request) = match config.chunk_size {
+        Some(_) => {
+            let mut client_conn = FramedTransport::joining(client_stream);
+            let (request, _): (ChunkRequest, usize) = recv_message(&mut client_conn)?;
+            println!("Server ▶ the Client requested the chunks {:?}", request.indices);
+            (client_conn, Some(request))
+        }
+        None => (FramedTransport::new(client_stream, new_session_id()), None),
+    };
+    let session_id = client_conn.session_id().ok_or("No exchange was opened")?;
+
+    // 2 : prepare the data to be sent to the client : pad it (or split it in chunks), encrypt it
+    // symmetrically, encrypt the symmetric key homomorphically and compute the hash of the
+    // (plaintext) symmetric key
+    let mut time_recap: String = String::new();
+    let start = Instant::now();
+    let (mut server, bundle) = match (config.chunk_size, &request) {
+        (Some(chunk_size), Some(request)) => Server2::with_chunks(data.as_slice(), chunk_size, request, config.hash_function, config.cipher)?,
+        _ => Server2::with_cipher(data.as_slice(), config.hash_function, config.cipher)?,
+    };
+    println!("Server ▶ Encrypted the data symmetrically with {} and the symmetric key homomophically", config.cipher);
+    let time = start.elapsed();
+    let mut full_time = time;
+    time_recap.push_str(&format!(" (pad, symmetric and homomorphic encryption: {:?}, ", time));
+
+    // 2a : Send sym_enc_data, hash_key, encrypted_key, iv,  homomorphic_public_key to the client
+    send_message(&mut client_conn, &bundle).map_err(|e| format!("Failed to write data to Client: {}", e))?;
+    println!("Server ▶ sent (ct, Hk, kct, pk) off-chain to Client");
+
+    // 3 : listen to smart contract for Ha and Hk
+    let mut sc_conn =
+        FramedTransport::new(TcpTransport::accept(&listener, &options).map_err(|e| format!("Failed to accept connection from SmartContract: {}", e))?, session_id);
+    let (submission, _): (Submission, usize) = recv_message(&mut sc_conn)?;
+    server.on_submission(submission)?;
+
+    // 4: listen for client chal and shut down connexion with client
+    let (challenge, _): (Challenge, usize) = recv_message(&mut client_conn)?;
+    println!("Server ▶ read (chal) from Client");
+    client_conn.get_ref().shutdown().map_err(|e| format!("Failed to shutdown Client: {}", e))?;
+    println!("Server ▶ shutdown Client");
+
+    // 5 : decrypt chal to â and run VerifyKA
+    println!("Server ▶ Verifying client's inputs");
+    let start = Instant::now();
+    let reveal = server.on_challenge(challenge)?;
+    let time = start.elapsed();
+    full_time += time;
+    time_recap.push_str(&format!(" decrypt chal and verify_ka: {:?}", time));
+
+    // 6 : send the symmetric key and â to the smart contract
+    send_message(&mut sc_conn, &reveal).map_err(|e| format!("Failed to write data to SmartContract: {}", e))?;
+    println!("Server ▶ sent (k, â) on‐chain to SmartContract");
+
+    // 7 : wait for success signal from smart contract
+    let (settlement, _): (Settlement, usize) = recv_message(&mut sc_conn)?;
+    server.on_settlement(&settlement)?;
+    println!("Server ▶ final outcome from SmartContract = {}", settlement.status);
+    println!("Server ▶ done.");
+
+    // 8 : print final statistics of the run
+    let mut beginning_time_string = String::new();
+    beginning_time_string.push_str(&format!(
+        "SERVER COMPUTATION COST IS {:?}" , full_time
+    ));
+
+    beginning_time_string.push_str(time_recap.as_str());
+    println!("{}", beginning_time_string);
+    Ok(())
+}
+
+/// Runs the client : it buys the data whose hash is in the hash file
+pub fn client(config: &Config) -> Result<(), Box<dyn Error>> {
+    // listen right away, so that the peers can be started in any order
+    let options = config.tcp_options();
+    let listener = TcpListener::bind(config.client.listen.as_str())
+        .map_err(|e| format!("Failed to listen on {}: {}", config.client.listen, e))?;
+
+    // 1 : retrieve the hash of the data
+    let hash_data = fs::read_to_string(&config.hash).map_err(|e| {
+        format!(
+            "Failed to read `{}`: {}",
+            config.hash, e
+        )
+    })?;
+    let client = match config.chunk_size {
+        Some(_) => Client2::new_chunks(hash_data, config.chunks.clone()),
+        None => Client2::new(hash_data),
+    };
+    let mut client = client
+        .with_payment(config.client_account.clone(), config.price)
+        .with_accepted_ciphers(config.accepted_ciphers.clone())
+        .with_challenge_field(config.challenge_field);
+    let mut time_recap: String = String::new();
+
+    // 2 : wait for the server to send Hk, k_ct, IV, ct, sk and unserialize them
+    println!("Client ▶ listening on {} …", config.client.listen);
+    let server_stream = TcpTransport::accept(&listener, &options).map_err(|e| format!("Failed to accept connection from Server: {}", e))?;
+    println!("Client ▶ accepted connection from Server");
+
+    // 2a : in the chunked variant, open the exchange with the chunks to buy
+    let mut server_conn = match client.chunk_request() {
+        Some(request) => {
+            let mut server_conn = FramedTransport::new(server_stream, new_session_id());
+            send_message(&mut server_conn, &request).map_err(|e| format!("Failed to write data to Server: {}", e))?;
+            println!("Client ▶ requested the chunks {:?} from Server", request.indices);
+            server_conn
+        }
+        None => FramedTransport::joining(server_stream),
+    };
+
+    let (bundle, len_comm): (CiphertextBundle, usize) = recv_message(&mut server_conn)?;
+    let sym_enc_data_len: u64 = bundle.files.iter().map(|file| bincode::serialized_size(&file.sym_ct)).sum::<Result<_, _>>()?;
+    let sym_key_hash_len = bincode::serialized_size(&bundle.hash_key)?;
+    let encrypted_sym_key_len = bincode::serialized_size(&bundle.key_ct)?;
+    let iv_len: u64 = bundle.files.iter().map(|file| bincode::serialized_size(&file.iv)).sum::<Result<_, _>>()?;
+    let public_key_len = bincode::serialized_size(&bundle.pk)?;
+
+    println!(
+        "Client ▶ read {} bytes total from Server (JSON).",
+        len_comm
+    );
+    println!("Client ▶ the Server proposed the {} cipher", bundle.cipher);
+
+    // 3 : run CreateChal : decrypt the data homomorphically, compute the hashes of the data and
+    // of the symmetric key homomorphically and compute the final challenge with them
+    println!("Client ▶ computing the challenge homomorphically...");
+    let start = Instant::now();
+    let (challenge, submission) = client.on_ciphertext(bundle)?;
+    let time = start.elapsed();
+    let mut full_time = time;
+    time_recap.push_str(&format!(" (createChal : {:?},", time));
+
+    // 4 : send chal to the server
+    let chal_len = send_message(&mut server_conn, &challenge)?;
+    println!("Client ▶ sent chal to the server");
+    let com_off_chain = format!(
+        "OFF-CHAIN COMMUNICATION COST: {} bytes (ct is {} bytes, H_k is {} bytes, k_ct is {} bytes, iv is {},  public_key is {} bytes, chal is {} bytes)\n",
+        len_comm + chal_len,
+        sym_enc_data_len,
+        sym_key_hash_len,
+        encrypted_sym_key_len,
+        iv_len,
+        public_key_len,
+        chal_len
+    );
+
+    // 5 : send the hash of a and of the hash key to the smart contract
+    println!("Client ▶ connecting to SmartContract at {} …", config.client.peer);
+    let session_id = server_conn.session_id().ok_or("No exchange was opened")?;
+    let mut sc_conn =
+        FramedTransport::new(TcpTransport::connect(config.client.peer.as_str(), &options).map_err(|e| format!("Failed to connect to SmartContract: {}", e))?, session_id);
+    send_message(&mut sc_conn, &submission).map_err(|e| format!("Failed to write data to SmartContract: {}", e))?;
+    println!("Client ▶ sent (Ha, Hk) on‐chain to SmartContract");
+
+    // 6 : wait for the secret symmetric key and status from smart contract (in real life those
+    // values would be public on the blockchain)
+    let (settlement, _): (Settlement, usize) = recv_message(&mut sc_conn)?;
+
+    if settlement.status == ABORT {
+        let _ = client.on_settlement(&settlement);
+        println!("Client ▶ final outcome from SmartContract = ABORT");
+        println!("Client ▶ done.");
+    }else{
+        // 7 : decrypt the data symmetrically with the symmetric key and check that it has the
+        // expected hash
+        println!("Client ▶ final outcome from SmartContract = SUCCESS");
+        println!("Client ▶ decrypting the data....");
+        let start = Instant::now();
+        let data = client.on_settlement(&settlement);
+        let time = start.elapsed();
+        time_recap.push_str(&format!(" decryption time is : {:?})", time));
+        full_time += time;
+
+        match data {
+            Ok(_) => println!("Client RETRIEVED THE EXPECTED DATA"),
+            Err(e) => println!("Client DID NOT RETRIEVE THE EXPECTED DATA ({})", e),
+        }
+    }
+
+    // 8 : Print some statistics about the run
+    let mut beginning_time_string = String::new();
+    beginning_time_string.push_str(&format!(
+        "CLIENT COMPUTATION COST IS {:?}" , full_time
+    ));
+
+    beginning_time_string.push_str(time_recap.as_str());
+    println!("{}", beginning_time_string);
+    println!("{}", com_off_chain);
+    Ok(())
+}
+
+/// Runs the smart contract
+pub fn contract(config: &Config) -> Result<(), Box<dyn Error>> {
+    // listen right away, so that the peers can be started in any order
+    let options = config.tcp_options();
+    let listener = TcpListener::bind(config.contract.listen.as_str())
+        .map_err(|e| format!("Failed to listen on {}: {}", config.contract.listen, e))?;
+
+    // 1 : wait for the client to send Ha, Hk, and unserialize them
+    println!("Smart Contract ▶ listening on {} …", config.contract.listen);
+    let mut client_conn =
+        FramedTransport::joining(TcpTransport::accept(&listener, &options).map_err(|e| format!("Failed to accept connection from Client: {}", e))?);
+    println!("Smart Contract ▶ accepted connection from client");
+    let mut ledger = Ledger::new();
+    ledger.deposit(&config.client_account, config.client_deposit);
+    let mut contract = Contract2::new()
+        .with_deadline(Duration::from_secs(config.reveal_deadline))
+        .with_ledger(ledger, config.terms())
+        .with_hash_function(config.hash_function);
+    let mut time_recap: String = String::new();
+    time_recap.push_str(&format!(
+        "SMART CONTRACT COMPUTATION COST {}",
+        ""
+    ));
+    let (submission, len_comm): (Submission, usize) = recv_message(&mut client_conn)?;
+    println!("Smart Contract ▶ read {} bytes total from Client (JSON).", len_comm);
+    let hash_a_len = bincode::serialized_size(&submission.hash_a)?;
+    let hash_k_len = bincode::serialized_size(&submission.hash_k)?;
+
+    let submission_gas = prot2_submission_cost(&submission)?;
+
+    // 1b : lock the client's payment in escrow, the exchange stops here if the client cannot pay
+    if let Err(e) = contract.on_submission(submission.clone()) {
+        let _ = send_message(&mut client_conn, &Settlement { status: ABORT, key: vec![] });
+        return Err(e.into());
+    }
+
+    // 2 : wait for the server to send a, k and the status from the server
+    let session_id = client_conn.session_id().ok_or("No exchange was opened")?;
+    let mut server_conn =
+        FramedTransport::new(TcpTransport::connect(config.contract.peer.as_str(), &options).map_err(|e| format!("Failed to connect to Server: {}", e))?, session_id);
+    send_message(&mut server_conn, &submission).map_err(|e| format!("Failed to write data to Server: {}", e))?;
+    let (settlement, op_len, a_len, k_len, reveal_gas) = match recv_message_before::<Reveal>(&mut server_conn, contract.deadline()) {
+        Ok((reveal, op_len)) => {
+            let a_len = bincode::serialized_size(&reveal.a)?;
+            let k_len = bincode::serialized_size(&reveal.key)?;
+            let reveal_gas = prot2_reveal_cost(&submission, &reveal)?;
+
+            // 3 : run VerifyKA function (the contract aborts directly if the server aborted)
+            let start = Instant::now();
+            let server_aborted = reveal.status == ABORT;
+            let settlement = contract.on_reveal(reveal)?;
+            let time = start.elapsed();
+            if !server_aborted {
+                time_recap.push_str(&format!("SMART CONTRACT COMPUTATION COST IS {:?}" , time));
+                time_recap.push_str(&format!(" (verify : {:?})", time));
+            }
+            (settlement, op_len, a_len, k_len, reveal_gas)
+        }
+        Err(e) => {
+            // 3' : the server did not reveal, the contract aborts at the deadline and refunds the client
+            println!("Smart Contract ▶ no (k, â) from the Server ({}), waiting for the deadline", e);
+            wait_until(contract.deadline());
+            (contract.on_deadline()?, 0, 0, 0, deadline_cost())
+        }
+    };
+
+    // 4 : send the final status to client and server, and symmetric secret key to client
+    send_message(&mut client_conn, &settlement).map_err(|e| format!("Failed to write data to Client: {}", e))?;
+    client_conn.get_ref().shutdown().map_err(|e| format!("Failed to shutdown Client: {}", e))?;
+    if let Err(e) = send_message(&mut server_conn, &settlement) {
+        println!("Smart Contract ▶ could not notify the Server: {}", e);
+    }
+    let _ = server_conn.get_ref().shutdown();
+
+    // 5 : print some statistics about the run
+    println!("SmartContract ▶ estimated gas of the submission : {}", submission_gas);
+    println!("SmartContract ▶ estimated gas of the reveal : {}", reveal_gas);
+    for event in contract.events() {
+        println!("SmartContract ▶ event {:?}", event);
+    }
+    for account in [&config.client_account, &config.server_account] {
+        println!("SmartContract ▶ balance of {} = {}", account, contract.ledger().balance(account));
+    }
+    println!("SmartContract ▶ final outcome from SmartContract = {}", settlement.status==SUCCESS);
+    println!("SmartContract ▶ done.");
+    println!("{}", time_recap);
+    println!(
+        "ON-CHAIN COMMUNICATION COST: {} bytes (Ha = {}, Hk = {}, a = {}, k = {}) . ESTIMATED GAS: {} (submission = {}, reveal = {}) .",
+        len_comm + op_len,
+        hash_a_len,
+        hash_k_len,
+        a_len,
+        k_len,
+        submission_gas.total() + reveal_gas.total(),
+        submission_gas.total(),
+        reveal_gas.total(),
+    );
+    Ok(())
+}
@@ -0,0 +1,60 @@
+//! Setups the needed files to run the protocols : it writes the data in the data file and its hash
+//! in the hash file. These files will be used by the client and server in a run of the protocols.
+//! With a chunk size, the hash file holds the root of the Merkle tree over the chunks instead.
+use std::error::Error;
+use std::fs;
+use rand::Rng;
+use fde_protocols::config::Config;
+use fde_protocols::merkle::MerkleTree;
+
+/// Where the data of the exchange comes from
+pub enum Source {
+    /// Random data of the given size in bytes
+    Size(usize),
+    /// The content of a file
+    File(String),
+}
+
+/// Returns either the file contents or `n` random bytes
+pub fn read_source(source: &Source) -> Result<Vec<u8>, Box<dyn Error>> {
+    match source {
+        Source::File(input_filename) => {
+            // Read entire file into `Vec<u8>` if the user provided a filename
+            Ok(fs::read(input_filename).map_err(|e| {
+                format!(
+                    "Failed to read `{}`: {}",
+                    input_filename,
+                    e
+                )
+            })?)
+        }
+        Source::Size(n) => {
+            // generate `n` random bytes if the user provided a size
+            let mut buf = vec![0u8; *n];
+            rand::thread_rng().fill(&mut buf[..]);
+            Ok(buf)
+        }
+    }
+}
+
+pub fn setup(source: &Source, config: &Config) -> Result<(), Box<dyn Error>> {
+    // 1 : `data` will hold either the file contents or the generated random bytes.
+    let data: Vec<u8> = read_source(source)?;
+
+    // 2 : write the raw bytes into the data file
+    fs::write(&config.data, &data)
+        .map_err(|e| format!("Could not write {}: {}", config.data, e))?;
+
+    // 3 : compute the hash of data (SHA3-256 unless configured otherwise), or the Merkle root of
+    // its chunks
+    let hash = match config.chunk_size {
+        Some(chunk_size) => MerkleTree::new(&data, chunk_size, config.hash_function).hex_root(),
+        None => config.hash_function.hex_digest(&data),
+    };
+
+    // 4 : write that hex digest into the hash file
+    fs::write(&config.hash, hash)
+        .map_err(|e| format!("Could not write {}: {}", config.hash, e))?;
+
+    Ok(())
+}
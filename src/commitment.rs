@@ -1,8 +1,10 @@
-/// This files contains a commitment scheme based of hash functions
+//! This files contains a commitment scheme based of hash functions
 
 use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
 use crate::homomorphic_functions::hex_sha3;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Opening {
     pub nonce: [u8; 32],   // base64‐encoded ciphertext
     pub data: Vec<u8>,  // base64‐encoded evaluation key
@@ -23,7 +25,7 @@ pub fn commit(data: &[u8]) -> (String, Opening) {
     let hash = hex_sha3(concatanation.as_slice());
 
     // create the opening, consisting of the data and nonce
-    let opening = Opening{nonce: nonce, data : data.to_vec()};
+    let opening = Opening{nonce, data : data.to_vec()};
 
     (hash, opening)
 }
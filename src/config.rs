@@ -0,0 +1,290 @@
+//! This module contains the configuration of a run of the protocols : which protocol to run, the
+//! paths of the data and hash files, and the endpoints of each role. It can be read from a TOML
+//! file, and the command-line options of the `fde` binary take precedence over it. Each role
+//! listens on one address and connects to one peer : the server connects to the client, the
+//! client to the smart contract, and the smart contract to the server. The connections are
+//! retried until `connect_timeout`, so that the roles can be started in any order.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+use serde::Deserialize;
+use crate::error::FdeError;
+use crate::homomorphic_functions::{ChallengeField, HashFunction, SymmetricCipher};
+use crate::ledger::Terms;
+use crate::protocol1::KeyOpening;
+use crate::prot_utils::{CLIENT_PORT, DATA_FILE, HASH_FILE, SC_PORT, SERVER_PORT};
+use crate::transport::TcpOptions;
+
+/// The three roles of an exchange
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Server,
+    Client,
+    Contract,
+}
+
+/// The address a role listens on, and the address of the peer it connects to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    pub listen: String,
+    pub peer: String,
+}
+
+/// The configuration of a run of the protocols
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub protocol: u8,
+    pub data: String,
+    pub hash: String,
+    pub server: Endpoint,
+    pub client: Endpoint,
+    pub contract: Endpoint,
+    /// Seconds spent retrying to connect to a peer
+    pub connect_timeout: u64,
+    /// Seconds a role waits for a message of a stalled peer before aborting (forever if `None`)
+    pub read_timeout: Option<u64>,
+    /// Seconds a role waits to send a message to a stalled peer before aborting (forever if `None`)
+    pub write_timeout: Option<u64>,
+    /// Seconds the server has to reveal after the client's submission, before the smart contract
+    /// aborts and refunds the client
+    pub reveal_deadline: u64,
+    /// Price of the data, paid by the client and held in escrow by the smart contract
+    pub price: u64,
+    /// Account of the client on the ledger of the smart contract
+    pub client_account: String,
+    /// Account of the server on the ledger of the smart contract
+    pub server_account: String,
+    /// Initial balance of the client on the ledger of the smart contract
+    pub client_deposit: u64,
+    /// What the server reveals in Protocol I : the whole `ClientKey`, or only the LWE secret key
+    pub key_opening: KeyOpening,
+    /// Hash function of the data (and in Protocol II of the symmetric key and of a)
+    pub hash_function: HashFunction,
+    /// Stream cipher the server proposes in Protocol II
+    pub cipher: SymmetricCipher,
+    /// Stream ciphers the client accepts in Protocol II
+    pub accepted_ciphers: Vec<SymmetricCipher>,
+    /// Arithmetic of the challenge the client computes in Protocol II
+    pub challenge_field: ChallengeField,
+    /// Size in bytes of the chunks of the data : if set, the hash file holds the root of their
+    /// Merkle tree, and the client of Protocol II buys a subset of the chunks
+    pub chunk_size: Option<usize>,
+    /// Indices of the chunks the client buys in the chunked variant of Protocol II
+    pub chunks: Vec<usize>,
+}
+
+// The content of a configuration file, where every field is optional
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    protocol: Option<u8>,
+    data: Option<String>,
+    hash: Option<String>,
+    server: Option<EndpointFile>,
+    client: Option<EndpointFile>,
+    contract: Option<EndpointFile>,
+    connect_timeout: Option<u64>,
+    read_timeout: Option<u64>,
+    write_timeout: Option<u64>,
+    reveal_deadline: Option<u64>,
+    price: Option<u64>,
+    client_account: Option<String>,
+    server_account: Option<String>,
+    client_deposit: Option<u64>,
+    key_opening: Option<KeyOpening>,
+    hash_function: Option<HashFunction>,
+    cipher: Option<SymmetricCipher>,
+    accepted_ciphers: Option<Vec<SymmetricCipher>>,
+    challenge_field: Option<ChallengeField>,
+    chunk_size: Option<usize>,
+    chunks: Option<Vec<usize>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EndpointFile {
+    listen: Option<String>,
+    peer: Option<String>,
+}
+
+fn local(port: u16) -> String {
+    format!("127.0.0.1:{}", port)
+}
+
+impl Default for Config {
+    /// The configuration of the original binaries : Protocol I, with all roles on localhost
+    fn default() -> Self {
+        Config {
+            protocol: 1,
+            data: String::from(DATA_FILE),
+            hash: String::from(HASH_FILE),
+            server: Endpoint { listen: local(SERVER_PORT), peer: local(CLIENT_PORT) },
+            client: Endpoint { listen: local(CLIENT_PORT), peer: local(SC_PORT) },
+            contract: Endpoint { listen: local(SC_PORT), peer: local(SERVER_PORT) },
+            connect_timeout: TcpOptions::default().connect_timeout.as_secs(),
+            read_timeout: None,
+            write_timeout: None,
+            reveal_deadline: 600,
+            price: 100,
+            client_account: String::from("client"),
+            server_account: String::from("server"),
+            client_deposit: 1000,
+            key_opening: KeyOpening::ClientKey,
+            hash_function: HashFunction::Sha3_256,
+            cipher: SymmetricCipher::Trivium,
+            accepted_ciphers: SymmetricCipher::ALL.to_vec(),
+            challenge_field: ChallengeField::Mod2_256,
+            chunk_size: None,
+            chunks: vec![0],
+        }
+    }
+}
+
+impl Endpoint {
+    fn apply(&mut self, file: Option<EndpointFile>) {
+        if let Some(file) = file {
+            if let Some(listen) = file.listen { self.listen = listen }
+            if let Some(peer) = file.peer { self.peer = peer }
+        }
+    }
+}
+
+impl Config {
+    /// Parses a TOML configuration, the missing fields keep their default value
+    pub fn from_toml(content: &str) -> Result<Self, FdeError> {
+        let file: ConfigFile = toml::from_str(content).map_err(|e| FdeError::Deserialize(e.to_string()))?;
+        let mut config = Config::default();
+        if let Some(protocol) = file.protocol {
+            config.set_protocol(protocol)?;
+        }
+        config.data = file.data.unwrap_or(config.data);
+        config.hash = file.hash.unwrap_or(config.hash);
+        config.server.apply(file.server);
+        config.client.apply(file.client);
+        config.contract.apply(file.contract);
+        config.connect_timeout = file.connect_timeout.unwrap_or(config.connect_timeout);
+        config.read_timeout = file.read_timeout.or(config.read_timeout);
+        config.write_timeout = file.write_timeout.or(config.write_timeout);
+        config.reveal_deadline = file.reveal_deadline.unwrap_or(config.reveal_deadline);
+        config.price = file.price.unwrap_or(config.price);
+        config.client_account = file.client_account.unwrap_or(config.client_account);
+        config.server_account = file.server_account.unwrap_or(config.server_account);
+        config.client_deposit = file.client_deposit.unwrap_or(config.client_deposit);
+        config.key_opening = file.key_opening.unwrap_or(config.key_opening);
+        config.hash_function = file.hash_function.unwrap_or(config.hash_function);
+        config.cipher = file.cipher.unwrap_or(config.cipher);
+        config.accepted_ciphers = file.accepted_ciphers.unwrap_or(config.accepted_ciphers);
+        config.challenge_field = file.challenge_field.unwrap_or(config.challenge_field);
+        config.set_chunk_size(file.chunk_size.or(config.chunk_size))?;
+        config.chunks = file.chunks.unwrap_or(config.chunks);
+        Ok(config)
+    }
+
+    /// Reads a TOML configuration file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, FdeError> {
+        Config::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Selects the protocol to run, which must be 1 or 2
+    pub fn set_protocol(&mut self, protocol: u8) -> Result<(), FdeError> {
+        if protocol != 1 && protocol != 2 {
+            return Err(FdeError::Deserialize(format!("unknown protocol {} (expected 1 or 2)", protocol)));
+        }
+        self.protocol = protocol;
+        Ok(())
+    }
+
+    /// Selects the size of the chunks of the data, which must not be 0 (the whole data if `None`)
+    pub fn set_chunk_size(&mut self, chunk_size: Option<usize>) -> Result<(), FdeError> {
+        if chunk_size == Some(0) {
+            return Err(FdeError::Deserialize(String::from("the chunk size must not be 0")));
+        }
+        self.chunk_size = chunk_size;
+        Ok(())
+    }
+
+    /// Returns the options of the TCP connections
+    pub fn tcp_options(&self) -> TcpOptions {
+        TcpOptions {
+            connect_timeout: Duration::from_secs(self.connect_timeout),
+            read_timeout: self.read_timeout.map(Duration::from_secs),
+            write_timeout: self.write_timeout.map(Duration::from_secs),
+        }
+    }
+
+    /// Returns the terms of the sale enforced by the smart contract
+    pub fn terms(&self) -> Terms {
+        Terms { seller: self.server_account.clone(), price: self.price }
+    }
+
+    /// Returns the endpoint of `role`
+    pub fn endpoint(&self, role: Role) -> &Endpoint {
+        match role {
+            Role::Server => &self.server,
+            Role::Client => &self.client,
+            Role::Contract => &self.contract,
+        }
+    }
+
+    pub fn endpoint_mut(&mut self, role: Role) -> &mut Endpoint {
+        match role {
+            Role::Server => &mut self.server,
+            Role::Client => &mut self.client,
+            Role::Contract => &mut self.contract,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_from_toml() {
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+
+        let config = Config::from_toml(
+            "protocol = 2\nhash = \"run2/hash.txt\"\n\n[client]\nlisten = \"0.0.0.0:9102\"\n",
+        ).unwrap();
+        assert_eq!(config.protocol, 2);
+        assert_eq!(config.data, DATA_FILE);
+        assert_eq!(config.hash, "run2/hash.txt");
+        assert_eq!(config.endpoint(Role::Client).listen, "0.0.0.0:9102");
+        assert_eq!(config.endpoint(Role::Client).peer, Config::default().client.peer);
+        assert_eq!(config.contract, Config::default().contract);
+        assert_eq!(config.tcp_options(), TcpOptions::default());
+
+        let config = Config::from_toml("connect_timeout = 5\nread_timeout = 600").unwrap();
+        assert_eq!(config.tcp_options().connect_timeout, Duration::from_secs(5));
+        assert_eq!(config.tcp_options().read_timeout, Some(Duration::from_secs(600)));
+        assert_eq!(config.tcp_options().write_timeout, None);
+        assert_eq!(config.reveal_deadline, Config::default().reveal_deadline);
+
+        let config = Config::from_toml("price = 42\nserver_account = \"alice\"").unwrap();
+        assert_eq!(config.terms(), Terms { seller: String::from("alice"), price: 42 });
+        assert_eq!(config.client_account, "client");
+        assert_eq!(config.key_opening, KeyOpening::ClientKey);
+        assert_eq!(Config::from_toml("key_opening = \"lwe_key\"").unwrap().key_opening, KeyOpening::LweKey);
+        assert_eq!(Config::from_toml("hash_function = \"keccak256\"").unwrap().hash_function, HashFunction::Keccak256);
+        let config = Config::from_toml("cipher = \"kreyvium\"\naccepted_ciphers = [\"kreyvium\"]").unwrap();
+        assert_eq!(config.cipher, SymmetricCipher::Kreyvium);
+        assert_eq!(config.accepted_ciphers, vec![SymmetricCipher::Kreyvium]);
+        assert_eq!(Config::default().accepted_ciphers, SymmetricCipher::ALL.to_vec());
+        assert_eq!(Config::from_toml("challenge_field = \"bn254\"").unwrap().challenge_field, ChallengeField::Bn254);
+        let config = Config::from_toml("chunk_size = 1024\nchunks = [3, 1]").unwrap();
+        assert_eq!((config.chunk_size, config.chunks), (Some(1024), vec![3, 1]));
+        assert_eq!((Config::default().chunk_size, Config::default().chunks), (None, vec![0]));
+    }
+
+    #[test]
+    fn test_config_invalid() {
+        assert!(matches!(Config::from_toml("protocol = 3"), Err(FdeError::Deserialize(_))));
+        assert!(matches!(Config::from_toml("port = 9001"), Err(FdeError::Deserialize(_))));
+        assert!(matches!(Config::from_toml("key_opening = \"lwe\""), Err(FdeError::Deserialize(_))));
+        assert!(matches!(Config::from_toml("cipher = \"aes\""), Err(FdeError::Deserialize(_))));
+        assert!(matches!(Config::from_toml("chunk_size = 0"), Err(FdeError::Deserialize(_))));
+        assert!(matches!(Config::from_toml("[server]\nhost = \"a\""), Err(FdeError::Deserialize(_))));
+        assert!(matches!(Config::load("does/not/exist.toml"), Err(FdeError::Io(_))));
+    }
+}
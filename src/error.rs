@@ -0,0 +1,110 @@
+//! This module contains the error type returned by the library functions and the message handlers
+//! of the protocols, so that a malformed message from a dishonest or buggy counterparty is
+//! reported to the caller instead of crashing the node.
+
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum FdeError {
+    /// The underlying connection failed
+    Io(io::Error),
+    /// A message could not be (de)serialized
+    Deserialize(String),
+    /// A value does not have the expected length
+    BadLength { what: &'static str, expected: usize, got: usize },
+    /// Decrypted data does not end with a valid SHA3 padding
+    BadPadding,
+    /// An opening does not match its commitment
+    CommitmentMismatch,
+    /// A hash does not match the expected one
+    HashMismatch,
+    /// The counterparty (or the smart contract) aborted the exchange
+    PeerAborted,
+    /// The counterparty did not answer in time
+    Timeout,
+    /// A message arrived while the role was not expecting it
+    UnexpectedMessage(String),
+    /// A frame has a wrong header (magic bytes, version or checksum)
+    BadFrame(String),
+    /// An account cannot pay the requested amount
+    InsufficientFunds { needed: u64, available: u64 },
+    /// There is no payment in escrow with this id
+    UnknownEscrow(u64),
+    /// The counterparty proposed a cipher which is not accepted
+    UnsupportedCipher(String),
+    /// A value is not a canonical element of the field of the challenge
+    NotInField(String),
+    /// A chunk which is not in the data was requested
+    UnknownChunk { index: usize, chunks: usize },
+}
+
+impl fmt::Display for FdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FdeError::Io(e) => write!(f, "I/O error: {}", e),
+            FdeError::Deserialize(e) => write!(f, "could not deserialize message: {}", e),
+            FdeError::BadLength { what, expected, got } => {
+                write!(f, "bad length for {}: expected {}, got {}", what, expected, got)
+            }
+            FdeError::BadPadding => write!(f, "invalid padding: did not find 0x86 or 0x80 at end"),
+            FdeError::CommitmentMismatch => write!(f, "the opening does not match the commitment"),
+            FdeError::HashMismatch => write!(f, "the hash does not match the expected one"),
+            FdeError::PeerAborted => write!(f, "the exchange was aborted"),
+            FdeError::Timeout => write!(f, "the counterparty did not answer in time"),
+            FdeError::UnexpectedMessage(e) => write!(f, "unexpected message: {}", e),
+            FdeError::BadFrame(e) => write!(f, "invalid frame: {}", e),
+            FdeError::InsufficientFunds { needed, available } => {
+                write!(f, "insufficient funds: needed {}, available {}", needed, available)
+            }
+            FdeError::UnknownEscrow(id) => write!(f, "no payment in escrow with id {}", id),
+            FdeError::UnsupportedCipher(cipher) => write!(f, "the cipher {} is not accepted", cipher),
+            FdeError::NotInField(field) => write!(f, "the value is not an element of the field {}", field),
+            FdeError::UnknownChunk { index, chunks } => write!(f, "there is no chunk {} (the data has {} chunks)", index, chunks),
+        }
+    }
+}
+
+impl std::error::Error for FdeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FdeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FdeError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => FdeError::Timeout,
+            _ => FdeError::Io(e),
+        }
+    }
+}
+
+impl From<bincode::Error> for FdeError {
+    fn from(e: bincode::Error) -> Self {
+        FdeError::Deserialize(e.to_string())
+    }
+}
+
+impl From<hex::FromHexError> for FdeError {
+    fn from(e: hex::FromHexError) -> Self {
+        FdeError::Deserialize(e.to_string())
+    }
+}
+
+/// Checks that `got` is the `expected` length of `what`
+pub fn check_len(what: &'static str, expected: usize, got: usize) -> Result<(), FdeError> {
+    if expected == got { Ok(()) } else { Err(FdeError::BadLength { what, expected, got }) }
+}
+
+/// Checks that a role in `state` is in the `expected` state to handle the message `msg`
+pub fn check_state<S: PartialEq + fmt::Debug>(state: S, expected: S, msg: &str) -> Result<(), FdeError> {
+    if state == expected {
+        Ok(())
+    } else {
+        Err(FdeError::UnexpectedMessage(format!("{} while in state {:?}", msg, state)))
+    }
+}
@@ -0,0 +1,273 @@
+//! This module contains a cost model estimating the gas of the on-chain steps of both protocols,
+//! if the smart contract ran on the EVM. For each transaction sent to the smart contract, it
+//! estimates the calldata gas (zero and non-zero bytes are priced differently), the storage
+//! reads and writes, and the hashing and decryption work done by `verify` and `verify_ka`.
+//!
+//! The prices follow the Ethereum yellow paper after EIP-2028 (calldata) and EIP-2929 (cold
+//! storage accesses). SHA3-256 is priced as the KECCAK256 opcode, and the decryption of an LWE
+//! ciphertext as the inner product of its mask with the secret key.
+
+use std::fmt;
+use std::ops::Add;
+use serde::Serialize;
+use tfhe::boolean::prelude::*;
+use crate::error::FdeError;
+use crate::prot_utils::SUCCESS;
+use crate::protocol1::{self, KeyOpening};
+use crate::protocol2;
+
+/// Base gas of a transaction
+pub const TX_BASE_GAS: u64 = 21_000;
+/// Gas of a zero byte of calldata
+pub const CALLDATA_ZERO_BYTE_GAS: u64 = 4;
+/// Gas of a non-zero byte of calldata
+pub const CALLDATA_NONZERO_BYTE_GAS: u64 = 16;
+/// Gas of writing a 32-byte word to a fresh storage slot (cold access included)
+pub const SSTORE_SET_GAS: u64 = 22_100;
+/// Gas of overwriting a non-zero 32-byte word in storage (cold access included)
+pub const SSTORE_RESET_GAS: u64 = 5_000;
+/// Gas of reading a 32-byte word from storage (cold access)
+pub const SLOAD_GAS: u64 = 2_100;
+/// Base gas of the KECCAK256 opcode
+pub const KECCAK_BASE_GAS: u64 = 30;
+/// Gas of the KECCAK256 opcode per 32-byte word hashed
+pub const KECCAK_WORD_GAS: u64 = 6;
+/// Gas of one coefficient of an LWE decryption : two MLOAD, one MUL and one ADD
+pub const LWE_COEF_GAS: u64 = 3 + 3 + 5 + 3;
+/// Gas of one coefficient of an LWE decryption with a packed binary key : one MLOAD, a bit test
+/// and one SUB
+pub const LWE_SUBSET_SUM_COEF_GAS: u64 = 3 + 3 + 3;
+
+/// Words written when the client's payment is locked in escrow (payer, payee and amount), on
+/// top of the update of the client's balance
+const ESCROW_WORDS: u64 = 3;
+/// Words overwritten when the exchange settles : the state of the contract and the balance
+/// credited with the escrowed payment
+const SETTLEMENT_WORDS: u64 = 2;
+
+/// The gas of one transaction sent to the smart contract, split by kind of work
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GasCost {
+    pub calldata: u64,
+    pub storage: u64,
+    pub hashing: u64,
+    pub decryption: u64,
+}
+
+impl GasCost {
+    /// Returns the total gas of the transaction, base cost included
+    pub fn total(&self) -> u64 {
+        TX_BASE_GAS + self.calldata + self.storage + self.hashing + self.decryption
+    }
+}
+
+impl Add for GasCost {
+    type Output = GasCost;
+
+    /// Adds the work of two steps (the base cost is counted once per step by `total`)
+    fn add(self, other: GasCost) -> GasCost {
+        GasCost {
+            calldata: self.calldata + other.calldata,
+            storage: self.storage + other.storage,
+            hashing: self.hashing + other.hashing,
+            decryption: self.decryption + other.decryption,
+        }
+    }
+}
+
+impl fmt::Display for GasCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} gas (calldata = {}, storage = {}, hashing = {}, decryption = {})",
+            self.total(), self.calldata, self.storage, self.hashing, self.decryption
+        )
+    }
+}
+
+// -------------------------------------------- MODEL ----------------------------------------------
+
+/// Returns the number of 32-byte words needed to hold `len` bytes
+pub fn words(len: usize) -> u64 {
+    len.div_ceil(32) as u64
+}
+
+/// Returns the gas of sending `data` as calldata
+pub fn calldata_gas(data: &[u8]) -> u64 {
+    data.iter()
+        .map(|&b| if b == 0 { CALLDATA_ZERO_BYTE_GAS } else { CALLDATA_NONZERO_BYTE_GAS })
+        .sum()
+}
+
+/// Returns the gas of the calldata of a message serialized with bincode
+pub fn message_calldata_gas<M: Serialize>(msg: &M) -> Result<u64, FdeError> {
+    Ok(calldata_gas(&bincode::serialize(msg)?))
+}
+
+/// Returns the gas of storing `len` bytes in fresh storage slots
+pub fn store_gas(len: usize) -> u64 {
+    words(len) * SSTORE_SET_GAS
+}
+
+/// Returns the gas of reading `len` bytes back from storage
+pub fn load_gas(len: usize) -> u64 {
+    words(len) * SLOAD_GAS
+}
+
+/// Returns the gas of hashing `len` bytes
+pub fn hash_gas(len: usize) -> u64 {
+    KECCAK_BASE_GAS + words(len) * KECCAK_WORD_GAS
+}
+
+/// Returns the gas of decrypting `ct` with the secret key revealed as `key_opening` (nothing for
+/// a trivial ciphertext)
+pub fn decryption_gas(ct: &Ciphertext, key_opening: KeyOpening) -> u64 {
+    let coef_gas = match key_opening {
+        KeyOpening::ClientKey => LWE_COEF_GAS,
+        KeyOpening::LweKey => LWE_SUBSET_SUM_COEF_GAS,
+    };
+    match ct {
+        Ciphertext::Encrypted(lwe) => lwe.lwe_size().to_lwe_dimension().0 as u64 * coef_gas,
+        Ciphertext::Trivial(_) => 0,
+    }
+}
+
+// Gas of locking the client's payment in escrow
+fn escrow_gas() -> u64 {
+    ESCROW_WORDS * SSTORE_SET_GAS + SSTORE_RESET_GAS
+}
+
+// Gas of releasing or refunding the escrowed payment
+fn settlement_gas() -> u64 {
+    SETTLEMENT_WORDS * SSTORE_RESET_GAS
+}
+
+// ----------------------------------------- PROTOCOL I --------------------------------------------
+
+/// Estimates the submission of (Hct, H, com) by the client : the contract stores them until the
+/// reveal, and locks the payment in escrow
+pub fn prot1_submission_cost(msg: &protocol1::Submission) -> Result<GasCost, FdeError> {
+    let stored = bincode::serialized_size(&(&msg.hash_ct, &msg.hash, &msg.com))? as usize;
+    Ok(GasCost {
+        calldata: message_calldata_gas(msg)?,
+        storage: store_gas(stored) + escrow_gas(),
+        ..GasCost::default()
+    })
+}
+
+/// Estimates the reveal of the opening by the server : if the server did not abort, `verify`
+/// (or `verify_lwe`) loads (Hct, H, com), hashes the opening and decrypts Hct with the revealed
+/// secret key
+pub fn prot1_reveal_cost(
+    submission: &protocol1::Submission,
+    msg: &protocol1::Reveal,
+    key_opening: KeyOpening,
+) -> Result<GasCost, FdeError> {
+    let calldata = message_calldata_gas(msg)?;
+    if msg.status != SUCCESS {
+        return Ok(GasCost { calldata, storage: settlement_gas(), ..GasCost::default() });
+    }
+    let stored = bincode::serialized_size(&(&submission.hash_ct, &submission.hash, &submission.com))? as usize;
+    Ok(GasCost {
+        calldata,
+        storage: load_gas(stored) + settlement_gas(),
+        hashing: hash_gas(msg.opening.nonce.len() + msg.opening.data.len()),
+        decryption: submission.hash_ct.iter().map(|ct| decryption_gas(ct, key_opening)).sum(),
+    })
+}
+
+// ----------------------------------------- PROTOCOL II -------------------------------------------
+
+/// Estimates the submission of (Ha, Hk) by the client : the contract stores them until the reveal,
+/// and locks the payment in escrow
+pub fn prot2_submission_cost(msg: &protocol2::Submission) -> Result<GasCost, FdeError> {
+    let stored = bincode::serialized_size(&(&msg.hash_a, &msg.hash_k, &msg.field))? as usize;
+    Ok(GasCost {
+        calldata: message_calldata_gas(msg)?,
+        storage: store_gas(stored) + escrow_gas(),
+        ..GasCost::default()
+    })
+}
+
+/// Estimates the reveal of (k, â) by the server : if the server did not abort, `verify_ka` loads
+/// (Ha, Hk) and the field of the challenge, and hashes â and k (checking that â is below the
+/// modulus only costs a comparison)
+pub fn prot2_reveal_cost(submission: &protocol2::Submission, msg: &protocol2::Reveal) -> Result<GasCost, FdeError> {
+    let calldata = message_calldata_gas(msg)?;
+    if msg.status != SUCCESS {
+        return Ok(GasCost { calldata, storage: settlement_gas(), ..GasCost::default() });
+    }
+    let stored = bincode::serialized_size(&(&submission.hash_a, &submission.hash_k, &submission.field))? as usize;
+    Ok(GasCost {
+        calldata,
+        storage: load_gas(stored) + settlement_gas(),
+        hashing: hash_gas(msg.a.len().div_ceil(8)) + hash_gas(msg.key.len().div_ceil(8)),
+        decryption: 0,
+    })
+}
+
+/// Estimates the transaction triggering the refund once the deadline passed without a reveal,
+/// in both protocols
+pub fn deadline_cost() -> GasCost {
+    GasCost { storage: settlement_gas(), ..GasCost::default() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commitment::Opening;
+    use crate::homomorphic_functions::ChallengeField;
+
+    #[test]
+    fn test_calldata_gas() {
+        assert_eq!(calldata_gas(&[]), 0);
+        assert_eq!(calldata_gas(&[0, 0, 1, 255]), 2 * CALLDATA_ZERO_BYTE_GAS + 2 * CALLDATA_NONZERO_BYTE_GAS);
+        assert_eq!(words(0), 0);
+        assert_eq!(words(64), 2);
+        assert_eq!(words(65), 3);
+        assert_eq!(hash_gas(32), KECCAK_BASE_GAS + KECCAK_WORD_GAS);
+    }
+
+    #[test]
+    fn test_prot1_cost() {
+        let submission = protocol1::Submission {
+            hash_ct: vec![Ciphertext::Trivial(true); 256],
+            hash: String::from("00"),
+            com: String::from("00"),
+            payer: String::from("client"),
+            amount: 100,
+        };
+        let cost = prot1_submission_cost(&submission).unwrap();
+        assert!(cost.calldata > 0 && cost.storage > escrow_gas());
+        assert_eq!(cost.hashing + cost.decryption, 0);
+
+        // the server aborts : there is nothing to verify
+        let opening = Opening { nonce: [1u8; 32], data: vec![1u8; 100] };
+        let reveal = protocol1::Reveal { status: SUCCESS, opening };
+        let aborted = protocol1::Reveal { status: crate::prot_utils::ABORT, ..reveal.clone() };
+        let cost = prot1_reveal_cost(&submission, &aborted, KeyOpening::ClientKey).unwrap();
+        assert_eq!((cost.storage, cost.hashing), (settlement_gas(), 0));
+        let cost = prot1_reveal_cost(&submission, &reveal, KeyOpening::ClientKey).unwrap();
+        assert_eq!(cost.hashing, hash_gas(132));
+        assert!(cost.storage > settlement_gas());
+    }
+
+    #[test]
+    fn test_prot2_cost() {
+        let submission = protocol2::Submission {
+            hash_a: "ab".repeat(32),
+            hash_k: "cd".repeat(32),
+            field: ChallengeField::Bn254,
+            payer: String::from("client"),
+            amount: 100,
+        };
+        let reveal = protocol2::Reveal { status: SUCCESS, key: vec![true; 80], a: vec![false; 256] };
+        let cost = prot2_reveal_cost(&submission, &reveal).unwrap();
+        assert_eq!(cost.hashing, hash_gas(32) + hash_gas(10));
+        assert_eq!(cost.decryption, 0);
+        assert_eq!(cost.storage, load_gas(2 * (8 + 64) + 4) + settlement_gas());
+        let total = prot2_submission_cost(&submission).unwrap() + cost;
+        assert_eq!(total.hashing, cost.hashing);
+        assert_eq!(deadline_cost().total(), TX_BASE_GAS + settlement_gas());
+    }
+}
@@ -0,0 +1,398 @@
+//! This module implements AES-128 in counter mode as a boolean circuit, generic over the
+//! representation of the bits like `TriviumStream`, so that data already encrypted with AES-CTR
+//! can be decrypted homomorphically. The S-box is the circuit of Boyar and Peralta ("A depth-16
+//! circuit for the AES S-box", 2012) with 32 AND, 83 XOR and 4 NOT gates, and the key schedule is
+//! computed once on the (encrypted) key. The counter blocks are public, so the first AddRoundKey
+//! only XORs plaintext bits into the key.
+//! Bytes are represented by their bits lsb first, and the counter is the iv read as a big-endian
+//! 128-bit integer, incremented for each block (NIST SP 800-38A).
+
+use rayon::prelude::*;
+use crate::homomorphic_functions::{Bit, StreamCipher};
+
+/// A byte as 8 bits, lsb first
+pub type Byte<T> = [T; 8];
+
+/// Number of rounds of AES-128
+const ROUNDS: usize = 10;
+/// Round constants of the key schedule
+const RCON: [u8; ROUNDS] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36];
+
+/// AesCtrStream: a struct implementing AES-128 in counter mode, using T for the internal
+/// representation of bits (bool, Ciphertext or FheBool), with the key of T to compute the FHE
+/// operations.
+pub struct AesCtrStream<T: Bit> {
+    // the 11 round keys of 16 bytes
+    round_keys: Vec<Vec<Byte<T>>>,
+    counter: u128,
+    // the bits of the last block which were not output yet
+    buffer: Vec<T>,
+    fhe_key: T::Key,
+}
+
+impl<T: Bit> AesCtrStream<T> {
+    /// Constructor for `AesCtrStream<T>`: arguments are the (encrypted) 128-bit secret key, the
+    /// 128-bit iv (the first counter block), and the key of T. The key schedule is computed
+    /// before returning
+    pub fn new(key: [T; 128], iv: [bool; 128], fhe_key: &T::Key) -> AesCtrStream<T> {
+        let key_bytes: Vec<Byte<T>> = key.chunks(8).map(|byte| std::array::from_fn(|i| byte[i].clone())).collect();
+        let iv_bytes: Vec<u8> = iv.chunks(8).map(|byte| byte.iter().rev().fold(0u8, |acc, &b| acc << 1 | b as u8)).collect();
+        AesCtrStream {
+            round_keys: key_schedule(key_bytes, fhe_key),
+            counter: u128::from_be_bytes(iv_bytes.try_into().unwrap()),
+            buffer: vec![],
+            fhe_key: fhe_key.clone(),
+        }
+    }
+
+    /// Skips the keystream of the next `n` blocks of 128 bits, from the end of the last
+    /// computed block
+    pub fn skip_blocks(&mut self, n: u128) {
+        self.counter = self.counter.wrapping_add(n);
+    }
+
+    /// Encrypts the counter block `counter`
+    fn encrypt_block(&self, counter: u128) -> Vec<T> {
+        let key = &self.fhe_key;
+        let block = counter.to_be_bytes();
+
+        // the counter is public, so the first AddRoundKey is a XOR with plaintext bits
+        let mut state: Vec<Byte<T>> = self.round_keys[0].iter()
+            .zip(block)
+            .map(|(key_byte, byte)| std::array::from_fn(|i| key_byte[i].xor_plain((byte >> i) & 1 == 1, key)))
+            .collect();
+        for round in 1..=ROUNDS {
+            state = state.par_iter().map(|byte| sbox(byte, key)).collect();
+            state = shift_rows(state);
+            if round < ROUNDS {
+                state = mix_columns(&state, key);
+            }
+            state = xor_bytes(&state, &self.round_keys[round], key);
+        }
+        state.into_iter().flatten().collect()
+    }
+}
+
+impl<T: Bit> StreamCipher<T> for AesCtrStream<T> {
+    fn next_n(&mut self, n: usize) -> Vec<T> {
+        // the missing blocks are independent, so they are encrypted in parallel
+        let blocks = (n.saturating_sub(self.buffer.len())).div_ceil(128) as u128;
+        let counter = self.counter;
+        let keystream: Vec<Vec<T>> = (0..blocks).into_par_iter()
+            .map(|i| self.encrypt_block(counter.wrapping_add(i)))
+            .collect();
+        self.counter = self.counter.wrapping_add(blocks);
+        self.buffer.extend(keystream.into_iter().flatten());
+        let rest = self.buffer.split_off(n);
+        std::mem::replace(&mut self.buffer, rest)
+    }
+}
+
+/// Computes the AES S-box on a byte with the circuit of Boyar and Peralta
+pub fn sbox<T: Bit>(byte: &Byte<T>, key: &T::Key) -> Byte<T> {
+    let xor = |a: &T, b: &T| a.xor(b, key);
+    let and = |a: &T, b: &T| a.and(b, key);
+    let xnor = |a: &T, b: &T| a.xor(b, key).xor_plain(true, key);
+    // x0 is the msb
+    let [x7, x6, x5, x4, x3, x2, x1, x0] = byte;
+
+    // top linear transformation
+    let y14 = xor(x3, x5);
+    let y13 = xor(x0, x6);
+    let y9 = xor(x0, x3);
+    let y8 = xor(x0, x5);
+    let t0 = xor(x1, x2);
+    let y1 = xor(&t0, x7);
+    let y4 = xor(&y1, x3);
+    let y12 = xor(&y13, &y14);
+    let y2 = xor(&y1, x0);
+    let y5 = xor(&y1, x6);
+    let y3 = xor(&y5, &y8);
+    let t1 = xor(x4, &y12);
+    let y15 = xor(&t1, x5);
+    let y20 = xor(&t1, x1);
+    let y6 = xor(&y15, x7);
+    let y10 = xor(&y15, &t0);
+    let y11 = xor(&y20, &y9);
+    let y7 = xor(x7, &y11);
+    let y17 = xor(&y10, &y11);
+    let y19 = xor(&y10, &y8);
+    let y16 = xor(&t0, &y11);
+    let y21 = xor(&y13, &y16);
+    let y18 = xor(x0, &y16);
+
+    // non-linear section
+    let t2 = and(&y12, &y15);
+    let t3 = and(&y3, &y6);
+    let t4 = xor(&t3, &t2);
+    let t5 = and(&y4, x7);
+    let t6 = xor(&t5, &t2);
+    let t7 = and(&y13, &y16);
+    let t8 = and(&y5, &y1);
+    let t9 = xor(&t8, &t7);
+    let t10 = and(&y2, &y7);
+    let t11 = xor(&t10, &t7);
+    let t12 = and(&y9, &y11);
+    let t13 = and(&y14, &y17);
+    let t14 = xor(&t13, &t12);
+    let t15 = and(&y8, &y10);
+    let t16 = xor(&t15, &t12);
+    let t17 = xor(&t4, &t14);
+    let t18 = xor(&t6, &t16);
+    let t19 = xor(&t9, &t14);
+    let t20 = xor(&t11, &t16);
+    let t21 = xor(&t17, &y20);
+    let t22 = xor(&t18, &y19);
+    let t23 = xor(&t19, &y21);
+    let t24 = xor(&t20, &y18);
+
+    let t25 = xor(&t21, &t22);
+    let t26 = and(&t21, &t23);
+    let t27 = xor(&t24, &t26);
+    let t28 = and(&t25, &t27);
+    let t29 = xor(&t28, &t22);
+    let t30 = xor(&t23, &t24);
+    let t31 = xor(&t22, &t26);
+    let t32 = and(&t31, &t30);
+    let t33 = xor(&t32, &t24);
+    let t34 = xor(&t23, &t33);
+    let t35 = xor(&t27, &t33);
+    let t36 = and(&t24, &t35);
+    let t37 = xor(&t36, &t34);
+    let t38 = xor(&t27, &t36);
+    let t39 = and(&t29, &t38);
+    let t40 = xor(&t25, &t39);
+
+    let t41 = xor(&t40, &t37);
+    let t42 = xor(&t29, &t33);
+    let t43 = xor(&t29, &t40);
+    let t44 = xor(&t33, &t37);
+    let t45 = xor(&t42, &t41);
+    let z0 = and(&t44, &y15);
+    let z1 = and(&t37, &y6);
+    let z2 = and(&t33, x7);
+    let z3 = and(&t43, &y16);
+    let z4 = and(&t40, &y1);
+    let z5 = and(&t29, &y7);
+    let z6 = and(&t42, &y11);
+    let z7 = and(&t45, &y17);
+    let z8 = and(&t41, &y10);
+    let z9 = and(&t44, &y12);
+    let z10 = and(&t37, &y3);
+    let z11 = and(&t33, &y4);
+    let z12 = and(&t43, &y13);
+    let z13 = and(&t40, &y5);
+    let z14 = and(&t29, &y2);
+    let z15 = and(&t42, &y9);
+    let z16 = and(&t45, &y14);
+    let z17 = and(&t41, &y8);
+
+    // bottom linear transformation
+    let t46 = xor(&z15, &z16);
+    let t47 = xor(&z10, &z11);
+    let t48 = xor(&z5, &z13);
+    let t49 = xor(&z9, &z10);
+    let t50 = xor(&z2, &z12);
+    let t51 = xor(&z2, &z5);
+    let t52 = xor(&z7, &z8);
+    let t53 = xor(&z0, &z3);
+    let t54 = xor(&z6, &z7);
+    let t55 = xor(&z16, &z17);
+    let t56 = xor(&z12, &t48);
+    let t57 = xor(&t50, &t53);
+    let t58 = xor(&z4, &t46);
+    let t59 = xor(&z3, &t54);
+    let t60 = xor(&t46, &t57);
+    let t61 = xor(&z14, &t57);
+    let t62 = xor(&t52, &t58);
+    let t63 = xor(&t49, &t58);
+    let t64 = xor(&z4, &t59);
+    let t65 = xor(&t61, &t62);
+    let t66 = xor(&z1, &t63);
+    let s0 = xor(&t59, &t63);
+    let s6 = xnor(&t56, &t62);
+    let s7 = xnor(&t48, &t60);
+    let t67 = xor(&t64, &t65);
+    let s3 = xor(&t53, &t66);
+    let s4 = xor(&t51, &t66);
+    let s5 = xor(&t47, &t65);
+    let s1 = xnor(&t64, &s3);
+    let s2 = xnor(&t55, &t67);
+
+    [s7, s6, s5, s4, s3, s2, s1, s0]
+}
+
+// -------------------------- HELPER FUNCTIONS ---------------------------------------
+
+// Computes the 11 round keys from the 16 bytes of the key
+fn key_schedule<T: Bit>(key_bytes: Vec<Byte<T>>, key: &T::Key) -> Vec<Vec<Byte<T>>> {
+    let mut words: Vec<Vec<Byte<T>>> = key_bytes.chunks(4).map(|word| word.to_vec()).collect();
+    for i in 4..4 * (ROUNDS + 1) {
+        let mut temp = words[i - 1].clone();
+        if i % 4 == 0 {
+            // RotWord, SubWord and the round constant
+            temp.rotate_left(1);
+            temp = temp.par_iter().map(|byte| sbox(byte, key)).collect();
+            let rcon = RCON[i / 4 - 1];
+            temp[0] = std::array::from_fn(|j| temp[0][j].xor_plain((rcon >> j) & 1 == 1, key));
+        }
+        words.push(xor_bytes(&words[i - 4], &temp, key));
+    }
+    words.chunks(4).map(|round_key| round_key.concat()).collect()
+}
+
+// XORs two encrypted bytes
+fn xor_byte<T: Bit>(a: &Byte<T>, b: &Byte<T>, key: &T::Key) -> Byte<T> {
+    std::array::from_fn(|i| a[i].xor(&b[i], key))
+}
+
+// XORs two sequences of encrypted bytes
+fn xor_bytes<T: Bit>(a: &[Byte<T>], b: &[Byte<T>], key: &T::Key) -> Vec<Byte<T>> {
+    a.par_iter()
+        .zip(b.par_iter())
+        .map(|(x, y)| xor_byte(x, y, key))
+        .collect()
+}
+
+// Shifts the row r of the state (the bytes r, r+4, r+8 and r+12) by r to the left
+fn shift_rows<T: Bit>(state: Vec<Byte<T>>) -> Vec<Byte<T>> {
+    (0..16).map(|i| state[(i + 4 * (i % 4)) % 16].clone()).collect()
+}
+
+// Mixes each column a of the state : b[i] = a[i] ^ t ^ 2 * (a[i] ^ a[i+1]), with t the XOR of the
+// four bytes of the column
+fn mix_columns<T: Bit>(state: &[Byte<T>], key: &T::Key) -> Vec<Byte<T>> {
+    state.par_chunks(4)
+        .flat_map_iter(|a| {
+            let t = xor_byte(&xor_byte(&a[0], &a[1], key), &xor_byte(&a[2], &a[3], key), key);
+            (0..4)
+                .map(|i| xor_byte(&xor_byte(&a[i], &t, key), &xtime(&xor_byte(&a[i], &a[(i + 1) % 4], key), key), key))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// Multiplies a byte by x in GF(2^8) : a shift, and a reduction by 0x1b if the msb was set
+fn xtime<T: Bit>(a: &Byte<T>, key: &T::Key) -> Byte<T> {
+    [
+        a[7].clone(),
+        a[0].xor(&a[7], key),
+        a[1].clone(),
+        a[2].xor(&a[7], key),
+        a[3].xor(&a[7], key),
+        a[4].clone(),
+        a[5].clone(),
+        a[6].clone(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tfhe::boolean::prelude::*;
+    use crate::homomorphic_functions::{keystream_gate_count, GateCount, SymmetricCipher};
+
+    fn hex_to_bits<const N: usize>(hex: &str) -> [bool; N] {
+        let bytes = hex::decode(hex).unwrap();
+        std::array::from_fn(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+    }
+
+    fn bits_to_hex(bits: &[bool]) -> String {
+        bits.chunks(8)
+            .map(|byte| format!("{:02x}", byte.iter().rev().fold(0u8, |acc, &b| acc << 1 | b as u8)))
+            .collect()
+    }
+
+    // The S-box from its definition : the inverse in GF(2^8), followed by the affine map
+    fn reference_sbox(x: u8) -> u8 {
+        let mul = |mut a: u8, mut b: u8| {
+            let mut p = 0u8;
+            while b != 0 {
+                if b & 1 == 1 {
+                    p ^= a;
+                }
+                a = (a << 1) ^ if a & 0x80 != 0 { 0x1b } else { 0 };
+                b >>= 1;
+            }
+            p
+        };
+        let inv = (1..=255u8).find(|&y| mul(x, y) == 1).unwrap_or(0);
+        inv ^ inv.rotate_left(1) ^ inv.rotate_left(2) ^ inv.rotate_left(3) ^ inv.rotate_left(4) ^ 0x63
+    }
+
+    // A plaintext bit counting the gates evaluated on it
+    #[derive(Clone)]
+    struct Counted(bool);
+
+    impl Bit for Counted {
+        type Key = Arc<Mutex<GateCount>>;
+
+        fn xor(&self, other: &Self, key: &Self::Key) -> Self {
+            key.lock().unwrap().xor += 1;
+            Counted(self.0 ^ other.0)
+        }
+
+        fn and(&self, other: &Self, key: &Self::Key) -> Self {
+            key.lock().unwrap().and += 1;
+            Counted(self.0 & other.0)
+        }
+
+        fn xor_plain(&self, other: bool, key: &Self::Key) -> Self {
+            key.lock().unwrap().plain_xor += 1;
+            Counted(self.0 ^ other)
+        }
+
+        fn trivial(value: bool, _: &Self::Key) -> Self {
+            Counted(value)
+        }
+    }
+
+    #[test]
+    fn test_aes_vectors() {
+        for x in 0..=255u8 {
+            let bits: Byte<bool> = std::array::from_fn(|i| (x >> i) & 1 == 1);
+            assert_eq!(bits_to_hex(&sbox(&bits, &())), format!("{:02x}", reference_sbox(x)));
+        }
+
+        // FIPS-197, appendix C.1 : the counter block is the plaintext
+        let key = hex_to_bits::<128>("000102030405060708090a0b0c0d0e0f");
+        let iv = hex_to_bits::<128>("00112233445566778899aabbccddeeff");
+        let keystream = AesCtrStream::<bool>::new(key, iv, &()).next_n(128);
+        assert_eq!(bits_to_hex(&keystream), "69c4e0d86a7b0430d8cdb78070b4c55a");
+
+        // NIST SP 800-38A, F.5.1 (CTR-AES128.Encrypt), the stream being split across the blocks
+        let key = hex_to_bits::<128>("2b7e151628aed2a6abf7158809cf4f3c");
+        let iv = hex_to_bits::<128>("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        let data = hex_to_bits::<256>("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51");
+        let mut stream = AesCtrStream::<bool>::new(key, iv, &());
+        let mut keystream = stream.next_n(100);
+        keystream.extend(stream.next_n(156));
+        let ct: Vec<bool> = keystream.iter().zip(data).map(|(k, d)| k ^ d).collect();
+        assert_eq!(bits_to_hex(&ct), "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff");
+
+        let mut stream = AesCtrStream::<bool>::new(key, iv, &());
+        stream.skip_blocks(1);
+        assert_eq!(stream.next_n(128), keystream[128..]);
+
+        // the encrypted stream stays in sync, the trivial ciphertexts being evaluated in the clear
+        let (ck, sk) = gen_keys();
+        let key_ct = key.map(|b| sk.trivial_encrypt(b));
+        let fhe_keystream: Vec<bool> = AesCtrStream::new(key_ct, iv, &sk)
+            .next_n(256)
+            .iter()
+            .map(|ct| ck.decrypt(ct))
+            .collect();
+        assert_eq!(fhe_keystream, keystream);
+
+        // the gates match the count of gate_count, the negations being XORs with a plaintext one
+        let gates = Arc::new(Mutex::new(GateCount::default()));
+        let key_counted = key.map(Counted);
+        AesCtrStream::new(key_counted, iv, &gates).next_n(200);
+        let expected = keystream_gate_count(SymmetricCipher::Aes128Ctr, 200);
+        let counted = *gates.lock().unwrap();
+        assert_eq!(counted.bootstrapped(), expected.bootstrapped());
+        assert_eq!(counted.and, expected.and);
+        assert_eq!(counted.free_gates(), expected.free_gates());
+    }
+}
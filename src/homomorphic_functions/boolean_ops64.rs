@@ -1,44 +1,66 @@
-/// This module contains  operations on encrypted bit strings used in the sha3 function, implemented
-/// with homomorphic boolean operations. These use parallel optimizations.
-/// These functions were adapted from boolean_ops in zama's sha256 example
-use rayon::prelude::*;
+//! This module contains  operations on encrypted bit strings used in the sha3 function, implemented
+//! with homomorphic boolean operations. These use parallel optimizations.
+//! These functions were adapted from boolean_ops in zama's sha256 example
+//! The rotations and the parallel bitwise operations are those of `EncUint`, on 64 bits
+
 use std::array;
 use tfhe::boolean::prelude::{BinaryBooleanGates, Ciphertext, ServerKey};
+use crate::homomorphic_functions::bitwise;
 
-//  ------------------------------ CIPHERTEXT-CIPHERTEXT OPERATIONS --------------------------------
-/// This function rotates the Ciphertext to the right by n
-pub fn rotate_right(x: &[Ciphertext; 64], n: usize) -> [Ciphertext; 64] {
-    let mut result = x.clone();
-    result.rotate_right(n);
-    result
+/// The gates evaluated on the lanes of the Keccak permutation. A `ServerKey` evaluates them, and
+/// `keccak_f1600_counted_gates` runs the permutation with a key counting them instead
+pub trait LaneGates: Sync {
+    /// XOR of two ciphertexts, bootstrapped
+    fn xor_gate(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext;
+    /// AND of two ciphertexts, bootstrapped
+    fn and_gate(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext;
+    /// NOT of a ciphertext, a negation without bootstrapping
+    fn not_gate(&self, a: &Ciphertext) -> Ciphertext;
+    /// XOR with a plaintext bit, a negation (or a copy) without bootstrapping
+    fn xor_plain_gate(&self, a: &Ciphertext, b: bool) -> Ciphertext;
 }
 
+impl LaneGates for ServerKey {
+    fn xor_gate(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.xor(a, b)
+    }
+
+    fn and_gate(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.and(a, b)
+    }
 
+    fn not_gate(&self, a: &Ciphertext) -> Ciphertext {
+        self.not(a)
+    }
+
+    fn xor_plain_gate(&self, a: &Ciphertext, b: bool) -> Ciphertext {
+        self.xor(a, b)
+    }
+}
+
+//  ------------------------------ CIPHERTEXT-CIPHERTEXT OPERATIONS --------------------------------
 /// Parallelized homomorphic bitwise xor operation for two 64 bits ciphertexts
-pub fn xor_64(a: &[Ciphertext; 64], b: &[Ciphertext; 64], sk: &ServerKey) -> [Ciphertext; 64] {
-    let mut result = a.clone();
-    result
-        .par_iter_mut()
-        .zip(a.par_iter().zip(b.par_iter()))
-        .for_each(|(dst, (lhs, rhs))| *dst = sk.xor(lhs, rhs));
-    result
+pub fn xor_64<G: LaneGates + ?Sized>(a: &[Ciphertext; 64], b: &[Ciphertext; 64], sk: &G) -> [Ciphertext; 64] {
+    bitwise(a, b, |lhs, rhs| sk.xor_gate(lhs, rhs))
 }
 
 /// Parallelized homomorphic bitwise and operation for two 64 bits ciphertexts
-pub fn and_64(a: &[Ciphertext; 64], b: &[Ciphertext; 64], sk: &ServerKey) -> [Ciphertext; 64] {
-    let mut result = a.clone();
-    result
-        .par_iter_mut()
-        .zip(a.par_iter().zip(b.par_iter()))
-        .for_each(|(dst, (lhs, rhs))| *dst = sk.and(lhs, rhs));
-    result
+pub fn and_64<G: LaneGates + ?Sized>(a: &[Ciphertext; 64], b: &[Ciphertext; 64], sk: &G) -> [Ciphertext; 64] {
+    bitwise(a, b, |lhs, rhs| sk.and_gate(lhs, rhs))
+}
+
+/// Parallelized homomorphic bitwise (NOT a) AND b for two 64 bits ciphertexts. The NOT negates
+/// the ciphertext without bootstrapping, so this costs a single bootstrapped gate per bit, as
+/// did the XOR of a with a trivial all-ones lane followed by an AND
+pub fn andny_64<G: LaneGates + ?Sized>(a: &[Ciphertext; 64], b: &[Ciphertext; 64], sk: &G) -> [Ciphertext; 64] {
+    bitwise(a, b, |lhs, rhs| sk.and_gate(&sk.not_gate(lhs), rhs))
 }
 
 
 // ------------------------------ PLAINTEXT-CIPHERTEXT OPERATIONS ----------------------------------
 /// Homomorphic bitwise xor operation for one 64 bits ciphertext with one 64 bit plaintext
-pub fn xor_with_plain_64(a: &[Ciphertext; 64], b: &[bool; 64], sk: &ServerKey, ) -> [Ciphertext; 64]{
-     array::from_fn(|i| { sk.xor(&a[i], b[i]) })
+pub fn xor_with_plain_64<G: LaneGates + ?Sized>(a: &[Ciphertext; 64], b: &[bool; 64], sk: &G, ) -> [Ciphertext; 64]{
+     array::from_fn(|i| { sk.xor_plain_gate(&a[i], b[i]) })
 }
 
 /// Homomorphic bitwise xor operation for one n-bits ciphertext with one n-bit plaintext
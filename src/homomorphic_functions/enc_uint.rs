@@ -0,0 +1,570 @@
+//! This module contains unsigned integers of N bits encrypted bit by bit (`EncUint<N>`), with
+//! their plaintext twin (`PlainUint<N>`), so that the challenge and the hashes of any size share
+//! one implementation of the arithmetic. Like in `boolean_ops256`, the bits are big-endian : index
+//! 0 is the msb, index N - 1 the lsb. All the arithmetic is modulo 2^N.
+//! The additions use the Brent-Kung parallel prefix algorithm, adapted from add in zama's sha256
+//! example, and the multiplication by a plaintext uses its canonical signed digit (CSD)
+//! representation, which has at most N / 2 + 1 nonzero digits.
+
+use std::array;
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg, Not, Shl, Shr, Sub};
+use rayon::prelude::*;
+use tfhe::boolean::prelude::{BinaryBooleanGates, Ciphertext, ClientKey, ServerKey};
+use crate::error::FdeError;
+
+/// An unsigned integer of N bits encrypted bit by bit, big-endian
+#[derive(Clone)]
+pub struct EncUint<const N: usize>(pub [Ciphertext; N]);
+
+/// An unsigned integer of N bits, big-endian
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PlainUint<const N: usize>(pub [bool; N]);
+
+// ------------------------------------------ ENCUINT ----------------------------------------------
+
+impl<const N: usize> EncUint<N> {
+    /// Returns the trivial encryption of `x`
+    pub fn trivial(x: &PlainUint<N>, sk: &ServerKey) -> Self {
+        EncUint(array::from_fn(|i| sk.trivial_encrypt(x.0[i])))
+    }
+
+    pub fn encrypt(x: &PlainUint<N>, ck: &ClientKey) -> Self {
+        EncUint(array::from_fn(|i| ck.encrypt(x.0[i])))
+    }
+
+    pub fn decrypt(&self, ck: &ClientKey) -> PlainUint<N> {
+        PlainUint(array::from_fn(|i| ck.decrypt(&self.0[i])))
+    }
+
+    /// Returns `value` if `bit` is true and zero otherwise : the bits are copies of `bit` or
+    /// trivial zeros, so no gate is evaluated
+    pub fn bit_times(bit: &Ciphertext, value: &PlainUint<N>, sk: &ServerKey) -> Self {
+        EncUint(array::from_fn(|i| if value.0[i] { bit.clone() } else { sk.trivial_encrypt(false) }))
+    }
+
+    /// Returns the bit of weight 2^i
+    pub fn bit(&self, i: usize) -> &Ciphertext {
+        &self.0[N - 1 - i]
+    }
+
+    /// Returns the M low bits, zero-extended if M > N
+    pub fn resize<const M: usize>(&self, sk: &ServerKey) -> EncUint<M> {
+        EncUint(array::from_fn(|i| match (i + N).checked_sub(M) {
+            Some(j) => self.0[j].clone(),
+            None => sk.trivial_encrypt(false),
+        }))
+    }
+
+    //  ------------------------------------ BITWISE OPERATIONS ------------------------------------
+
+    pub fn xor(&self, other: &Self, sk: &ServerKey) -> Self {
+        EncUint(bitwise(&self.0, &other.0, |x, y| sk.xor(x, y)))
+    }
+
+    pub fn and(&self, other: &Self, sk: &ServerKey) -> Self {
+        EncUint(bitwise(&self.0, &other.0, |x, y| sk.and(x, y)))
+    }
+
+    pub fn xor_plain(&self, other: &PlainUint<N>, sk: &ServerKey) -> Self {
+        EncUint(array::from_fn(|i| sk.xor(&self.0[i], other.0[i])))
+    }
+
+    pub fn and_plain(&self, other: &PlainUint<N>, sk: &ServerKey) -> Self {
+        EncUint(array::from_fn(|i| sk.and(&self.0[i], other.0[i])))
+    }
+
+    /// Negates every bit, without bootstrapping
+    pub fn not(&self, sk: &ServerKey) -> Self {
+        EncUint(array::from_fn(|i| sk.not(&self.0[i])))
+    }
+
+    /// Returns `a` if `condition` is true and `b` otherwise : b ^ (condition & (a ^ b))
+    pub fn select(condition: &Ciphertext, a: &Self, b: &Self, sk: &ServerKey) -> Self {
+        EncUint(bitwise(&a.0, &b.0, |x, y| sk.xor(y, &sk.and(condition, &sk.xor(x, y)))))
+    }
+
+    //  ----------------------------------- SHIFTS AND ROTATIONS -----------------------------------
+
+    pub fn shl(&self, n: usize, sk: &ServerKey) -> Self {
+        let n = n.min(N);
+        let mut result = self.clone();
+        result.0.rotate_left(n % N.max(1));
+        result.0[N - n..].fill_with(|| sk.trivial_encrypt(false));
+        result
+    }
+
+    pub fn shr(&self, n: usize, sk: &ServerKey) -> Self {
+        let n = n.min(N);
+        let mut result = self.clone();
+        result.0.rotate_right(n % N.max(1));
+        result.0[..n].fill_with(|| sk.trivial_encrypt(false));
+        result
+    }
+
+    pub fn rotate_left(&self, n: usize) -> Self {
+        EncUint(rotate_left(&self.0, n))
+    }
+
+    pub fn rotate_right(&self, n: usize) -> Self {
+        EncUint(rotate_right(&self.0, n))
+    }
+
+    //  ----------------------------------------- ARITHMETIC ---------------------------------------
+
+    pub fn add(&self, other: &Self, sk: &ServerKey) -> Self {
+        let (propagate, generate) = rayon::join(|| self.xor(other, sk), || self.and(other, sk));
+        EncUint(sum(&propagate.0, &generate.0, false, sk).0)
+    }
+
+    pub fn add_plain(&self, other: &PlainUint<N>, sk: &ServerKey) -> Self {
+        let (propagate, generate) = (self.xor_plain(other, sk), self.and_plain(other, sk));
+        EncUint(sum(&propagate.0, &generate.0, false, sk).0)
+    }
+
+    /// Returns -self, the complement of the bits plus one
+    pub fn neg(&self, sk: &ServerKey) -> Self {
+        let not = self.not(sk);
+        let zero = PlainUint::ZERO;
+        EncUint(sum(&not.xor_plain(&zero, sk).0, &not.and_plain(&zero, sk).0, true, sk).0)
+    }
+
+    pub fn sub(&self, other: &Self, sk: &ServerKey) -> Self {
+        self.checked_sub(other, sk).0
+    }
+
+    pub fn sub_plain(&self, other: &PlainUint<N>, sk: &ServerKey) -> Self {
+        self.checked_sub_plain(other, sk).0
+    }
+
+    /// Returns self - other, and whether self >= other (the subtraction did not borrow) : it is
+    /// the carry out of self + !other + 1
+    pub fn checked_sub(&self, other: &Self, sk: &ServerKey) -> (Self, Ciphertext) {
+        let not = other.not(sk);
+        let (propagate, generate) = rayon::join(|| self.xor(&not, sk), || self.and(&not, sk));
+        let (diff, carry) = sum(&propagate.0, &generate.0, true, sk);
+        (EncUint(diff), carry)
+    }
+
+    /// Same as `checked_sub`, with a plaintext
+    pub fn checked_sub_plain(&self, other: &PlainUint<N>, sk: &ServerKey) -> (Self, Ciphertext) {
+        let not = !*other;
+        let (diff, carry) = sum(&self.xor_plain(&not, sk).0, &self.and_plain(&not, sk).0, true, sk);
+        (EncUint(diff), carry)
+    }
+
+    /// Multiplies by a plaintext : each nonzero digit of its CSD representation adds (or
+    /// subtracts) a shift of self, and the partial products are added up in a tree
+    pub fn mul_plain_csd(&self, p: &PlainUint<N>, sk: &ServerKey) -> Self {
+        let partials: Vec<Self> = p.to_csd()
+            .iter()
+            .enumerate()
+            .filter(|&(_, &digit)| digit != 0)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|(k, &digit)| if digit == 1 { self.shl(k, sk) } else { self.shl(k, sk).neg(sk) })
+            .collect();
+        if partials.is_empty() {
+            return Self::trivial(&PlainUint::ZERO, sk);
+        }
+        add_all(partials, sk)
+    }
+
+    //  ---------------------------------------- COMPARISONS ---------------------------------------
+
+    /// Returns the encryption of self >= other
+    pub fn ge(&self, other: &Self, sk: &ServerKey) -> Ciphertext {
+        let not = other.not(sk);
+        let (propagate, generate) = rayon::join(|| self.xor(&not, sk), || self.and(&not, sk));
+        carries(&propagate.0, &generate.0, true, sk).pop().unwrap()
+    }
+
+    pub fn lt(&self, other: &Self, sk: &ServerKey) -> Ciphertext {
+        sk.not(&self.ge(other, sk))
+    }
+
+    pub fn gt(&self, other: &Self, sk: &ServerKey) -> Ciphertext {
+        other.lt(self, sk)
+    }
+
+    pub fn le(&self, other: &Self, sk: &ServerKey) -> Ciphertext {
+        other.ge(self, sk)
+    }
+
+    /// Returns the encryption of self == other : the AND of the equalities of the bits
+    pub fn eq(&self, other: &Self, sk: &ServerKey) -> Ciphertext {
+        let mut nodes: Vec<Ciphertext> = bitwise(&self.0, &other.0, |x, y| sk.not(&sk.xor(x, y))).to_vec();
+        while nodes.len() > 1 {
+            nodes = nodes
+                .par_chunks(2)
+                .map(|chunk| if chunk.len() == 2 { sk.and(&chunk[0], &chunk[1]) } else { chunk[0].clone() })
+                .collect();
+        }
+        nodes.pop().unwrap_or_else(|| sk.trivial_encrypt(true))
+    }
+
+    /// Returns the encryption of self >= other
+    pub fn ge_plain(&self, other: &PlainUint<N>, sk: &ServerKey) -> Ciphertext {
+        let not = !*other;
+        carries(&self.xor_plain(&not, sk).0, &self.and_plain(&not, sk).0, true, sk).pop().unwrap()
+    }
+
+    pub fn lt_plain(&self, other: &PlainUint<N>, sk: &ServerKey) -> Ciphertext {
+        sk.not(&self.ge_plain(other, sk))
+    }
+}
+
+impl<const N: usize> TryFrom<Vec<Ciphertext>> for EncUint<N> {
+    type Error = FdeError;
+
+    fn try_from(bits: Vec<Ciphertext>) -> Result<Self, FdeError> {
+        let len = bits.len();
+        bits.try_into().map(EncUint).map_err(|_| FdeError::BadLength { what: "encrypted integer (in bits)", expected: N, got: len })
+    }
+}
+
+/// Adds up all the terms in a tree, the additions of a level running in parallel
+pub fn add_all<const N: usize>(mut nodes: Vec<EncUint<N>>, sk: &ServerKey) -> EncUint<N> {
+    while nodes.len() > 1 {
+        nodes = nodes
+            .par_chunks(2)
+            .map(|chunk| if chunk.len() == 2 { chunk[0].add(&chunk[1], sk) } else { chunk[0].clone() })
+            .collect();
+    }
+    nodes.pop().unwrap_or_else(|| EncUint::trivial(&PlainUint::ZERO, sk))
+}
+
+// ----------------------------------------- PLAINUINT ---------------------------------------------
+
+impl<const N: usize> PlainUint<N> {
+    pub const ZERO: Self = PlainUint([false; N]);
+    pub const MAX: Self = PlainUint([true; N]);
+
+    /// Returns 1
+    pub fn one() -> Self {
+        Self::from_u128(1)
+    }
+
+    /// Returns the N low bits of `x`
+    pub fn from_u128(x: u128) -> Self {
+        PlainUint(array::from_fn(|i| N - 1 - i < 128 && (x >> (N - 1 - i)) & 1 == 1))
+    }
+
+    /// Returns the N low bits of the big-endian integer `bytes`
+    pub fn from_be_bytes(bytes: &[u8]) -> Self {
+        let bits = bytes.len() * 8;
+        PlainUint(array::from_fn(|i| {
+            let k = N - 1 - i;
+            k < bits && (bytes[bytes.len() - 1 - k / 8] >> (k % 8)) & 1 == 1
+        }))
+    }
+
+    /// Returns the big-endian bytes, the first one being padded with zeros if N is not a
+    /// multiple of 8
+    pub fn to_be_bytes(&self) -> Vec<u8> {
+        let len = N.div_ceil(8);
+        (0..len)
+            .map(|j| (0..8).fold(0u8, |acc, b| acc | (self.bit(8 * (len - 1 - j) + b) as u8) << b))
+            .collect()
+    }
+
+    /// Returns the bit of weight 2^i (false above N)
+    pub fn bit(&self, i: usize) -> bool {
+        i < N && self.0[N - 1 - i]
+    }
+
+    /// Returns the M low bits, zero-extended if M > N
+    pub fn resize<const M: usize>(&self) -> PlainUint<M> {
+        PlainUint(array::from_fn(|i| (i + N).checked_sub(M).is_some_and(|j| self.0[j])))
+    }
+
+    pub fn rotate_left(&self, n: usize) -> Self {
+        PlainUint(rotate_left(&self.0, n))
+    }
+
+    pub fn rotate_right(&self, n: usize) -> Self {
+        PlainUint(rotate_right(&self.0, n))
+    }
+
+    /// Returns self + other and whether it overflowed
+    pub fn overflowing_add(&self, other: &Self) -> (Self, bool) {
+        let mut carry = false;
+        let mut result = [false; N];
+        for i in (0..N).rev() {
+            let (a, b) = (self.0[i], other.0[i]);
+            result[i] = a ^ b ^ carry;
+            carry = (a & b) | (carry & (a ^ b));
+        }
+        (PlainUint(result), carry)
+    }
+
+    /// Returns the canonical signed digit representation, little-endian : `csd[k]` in {-1, 0, 1}
+    /// is the digit of weight 2^k, and no two consecutive digits are nonzero. A carry beyond the
+    /// N digits is dropped, as the arithmetic is modulo 2^N
+    pub fn to_csd(&self) -> [i8; N] {
+        let mut csd = [0i8; N];
+        let mut i = 0;
+        while i < N {
+            if !self.bit(i) && csd[i] == 0 {
+                i += 1;
+                continue;
+            }
+            // a run of ones starts at i (bit i may be a carry of the previous run)
+            let mut run = 1;
+            while i + run < N && self.bit(i + run) {
+                run += 1;
+            }
+            if run == 1 {
+                // a single one stays as it is
+                csd[i] = 1;
+            } else {
+                // 2^i + ... + 2^(i+run-1) = 2^(i+run) - 2^i
+                csd[i] = -1;
+                if i + run < N {
+                    csd[i + run] = 1;
+                }
+            }
+            i += run;
+        }
+        csd
+    }
+}
+
+impl<const N: usize> Default for PlainUint<N> {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl<const N: usize> Ord for PlainUint<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // big-endian, so the lexicographic order is the numeric one
+        self.0.cmp(&other.0)
+    }
+}
+
+impl<const N: usize> PartialOrd for PlainUint<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const N: usize> Add for PlainUint<N> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.overflowing_add(&other).0
+    }
+}
+
+impl<const N: usize> Not for PlainUint<N> {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        PlainUint(self.0.map(|b| !b))
+    }
+}
+
+impl<const N: usize> Neg for PlainUint<N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        !self + Self::one()
+    }
+}
+
+impl<const N: usize> Sub for PlainUint<N> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl<const N: usize> Shl<usize> for PlainUint<N> {
+    type Output = Self;
+
+    fn shl(self, n: usize) -> Self {
+        PlainUint(array::from_fn(|i| i + n < N && self.0[i + n]))
+    }
+}
+
+impl<const N: usize> Shr<usize> for PlainUint<N> {
+    type Output = Self;
+
+    fn shr(self, n: usize) -> Self {
+        PlainUint(array::from_fn(|i| i >= n && self.0[i - n]))
+    }
+}
+
+impl<const N: usize> Mul for PlainUint<N> {
+    type Output = Self;
+
+    /// Shift and add
+    fn mul(self, other: Self) -> Self {
+        (0..N).filter(|&k| other.bit(k)).fold(Self::ZERO, |acc, k| acc + (self << k))
+    }
+}
+
+// --------------------------------------- HELPERS -------------------------------------------------
+
+/// Applies `f` to the bits of two arrays of ciphertexts, in parallel
+pub fn bitwise<const N: usize, F>(a: &[Ciphertext; N], b: &[Ciphertext; N], f: F) -> [Ciphertext; N]
+where
+    F: Fn(&Ciphertext, &Ciphertext) -> Ciphertext + Sync,
+{
+    let mut result = a.clone();
+    result
+        .par_iter_mut()
+        .zip(a.par_iter().zip(b.par_iter()))
+        .for_each(|(dst, (lhs, rhs))| *dst = f(lhs, rhs));
+    result
+}
+
+pub fn rotate_left<T: Clone, const N: usize>(x: &[T; N], n: usize) -> [T; N] {
+    let mut result = x.clone();
+    result.rotate_left(n % N.max(1));
+    result
+}
+
+pub fn rotate_right<T: Clone, const N: usize>(x: &[T; N], n: usize) -> [T; N] {
+    let mut result = x.clone();
+    result.rotate_right(n % N.max(1));
+    result
+}
+
+// Returns the sum of the operands given by their propagate (a ^ b) and generate (a & b) bits, with
+// a plaintext carry in, along with the carry out
+fn sum<const N: usize>(propagate: &[Ciphertext; N], generate: &[Ciphertext; N], carry_in: bool, sk: &ServerKey) -> ([Ciphertext; N], Ciphertext) {
+    let mut carries = carries(propagate, generate, carry_in, sk);
+    let carry_out = carries.pop().unwrap();
+    // the carry into the lsb is the carry in, which is a plaintext
+    let result = array::from_fn(|i| match (N - 1 - i).checked_sub(1) {
+        Some(k) => sk.xor(&propagate[i], &carries[k]),
+        None => sk.xor(&propagate[i], carry_in),
+    });
+    (result, carry_out)
+}
+
+// Implementation of the Brent Kung parallel prefix algorithm, on any number of bits
+// This function computes the carry out of every bit (little-endian, the last one being the carry
+// out of the sum) in parallel while minimizing the number of homomorphic operations
+// Modified from brent_kung in boolean_ops
+fn carries<const N: usize>(propagate: &[Ciphertext; N], generate: &[Ciphertext; N], carry_in: bool, sk: &ServerKey) -> Vec<Ciphertext> {
+    // little-endian copies
+    let mut propagate: Vec<Ciphertext> = propagate.iter().rev().cloned().collect();
+    let mut generate: Vec<Ciphertext> = generate.iter().rev().cloned().collect();
+    if N == 0 {
+        return vec![sk.trivial_encrypt(carry_in)];
+    }
+    if carry_in {
+        // g | (p & 1)
+        generate[0] = sk.or(&generate[0], &propagate[0]);
+    }
+
+    // combines the cell i with the cell i - stride below it. The propagate of a cell is only
+    // needed while its range does not start at bit 0 (a "black" cell, a "grey" one otherwise)
+    let combine = |propagate: &[Ciphertext], generate: &[Ciphertext], i: usize, stride: usize| {
+        let new_g = sk.or(&generate[i], &sk.and(&generate[i - stride], &propagate[i]));
+        let new_p = if i + 1 == 2 * stride { None } else { Some(sk.and(&propagate[i], &propagate[i - stride])) };
+        (i, new_g, new_p)
+    };
+
+    // up-sweep : the cells 2 x stride - 1, 4 x stride - 1, ... get the prefix of the 2 x stride
+    // bits below them
+    let mut stride = 1;
+    while stride < N {
+        let updates: Vec<(usize, Ciphertext, Option<Ciphertext>)> = (2 * stride - 1..N)
+            .step_by(2 * stride)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|i| combine(&propagate, &generate, i, stride))
+            .collect();
+        for (i, g, p) in updates {
+            generate[i] = g;
+            if let Some(p) = p {
+                propagate[i] = p;
+            }
+        }
+        stride *= 2;
+    }
+
+    // down-sweep : the cells 3 x stride - 1, 5 x stride - 1, ... get the prefix from bit 0, the
+    // cell stride below them already having it
+    while stride > 1 {
+        stride /= 2;
+        let updates: Vec<(usize, Ciphertext)> = (3 * stride - 1..N)
+            .step_by(2 * stride)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|i| (i, sk.or(&generate[i], &sk.and(&generate[i - stride], &propagate[i]))))
+            .collect();
+        for (i, g) in updates {
+            generate[i] = g;
+        }
+    }
+    generate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::boolean::prelude::*;
+
+    #[test]
+    fn test_plain_uint() {
+        let (x, y) = (PlainUint::<20>::from_u128(0xabcde), PlainUint::<20>::from_u128(0x12345));
+        assert_eq!(x + y, PlainUint::from_u128(0xabcde + 0x12345));
+        assert_eq!(y - x, PlainUint::from_u128((1 << 20) + 0x12345 - 0xabcde));
+        assert_eq!(x * y, PlainUint::from_u128((0xabcde * 0x12345) % (1 << 20)));
+        assert_eq!((x << 4, x >> 4), (PlainUint::from_u128(0xbcde0), PlainUint::from_u128(0xabcd)));
+        assert_eq!(x.rotate_left(4), PlainUint::from_u128(0xbcdea));
+        assert!(y < x && PlainUint::<20>::MAX > x);
+        assert_eq!(PlainUint::<12>::from_be_bytes(&[0xab, 0xcd]), PlainUint::from_u128(0xbcd));
+        assert_eq!(PlainUint::<12>::from_u128(0xbcd).to_be_bytes(), vec![0x0b, 0xcd]);
+        assert_eq!(x.resize::<8>(), PlainUint::from_u128(0xde));
+        assert_eq!(x.resize::<24>(), PlainUint::from_u128(0xabcde));
+
+        // the CSD digits give back the value, with no two consecutive nonzero digits
+        for v in [0u128, 1, 0b0111_0111, 0b1011_1011, 0xffff, 0xabcd] {
+            let csd = PlainUint::<16>::from_u128(v).to_csd();
+            let value: i128 = csd.iter().enumerate().map(|(k, &d)| d as i128 * (1 << k)).sum();
+            assert_eq!(value.rem_euclid(1 << 16) as u128, v);
+            assert!(csd.windows(2).all(|w| w[0] == 0 || w[1] == 0));
+        }
+    }
+
+    #[test]
+    fn test_enc_uint() {
+        let (ck, sk) = gen_keys();
+        let (x, y) = (PlainUint::<13>::from_u128(0x1abc), PlainUint::<13>::from_u128(0x0def));
+        let (ex, ey) = (EncUint::encrypt(&x, &ck), EncUint::encrypt(&y, &ck));
+
+        assert_eq!(ex.add(&ey, &sk).decrypt(&ck), x + y);
+        assert_eq!(ey.sub(&ex, &sk).decrypt(&ck), y - x);
+        assert_eq!(ex.neg(&sk).decrypt(&ck), -x);
+        assert_eq!(ex.add_plain(&y, &sk).decrypt(&ck), x + y);
+        assert_eq!(ex.mul_plain_csd(&y, &sk).decrypt(&ck), x * y);
+        assert_eq!(ex.shl(3, &sk).decrypt(&ck), x << 3);
+        assert_eq!(ex.shr(3, &sk).decrypt(&ck), x >> 3);
+        assert_eq!(ex.rotate_right(5).decrypt(&ck), x.rotate_right(5));
+        assert_eq!(ex.resize::<16>(&sk).decrypt(&ck), x.resize::<16>());
+
+        let (diff, ge) = ex.checked_sub(&ey, &sk);
+        assert_eq!((diff.decrypt(&ck), ck.decrypt(&ge)), (x - y, true));
+        let (diff, ge) = ey.checked_sub_plain(&x, &sk);
+        assert_eq!((diff.decrypt(&ck), ck.decrypt(&ge)), (y - x, false));
+        assert_eq!(EncUint::select(&ge, &ex, &ey, &sk).decrypt(&ck), y);
+        let decrypt = |ct: Ciphertext| ck.decrypt(&ct);
+        assert!(decrypt(ex.gt(&ey, &sk)) && !decrypt(ex.lt(&ey, &sk)) && decrypt(ey.le(&ex, &sk)));
+        assert!(decrypt(ex.ge(&ex, &sk)) && decrypt(ex.eq(&ex, &sk)) && !decrypt(ex.eq(&ey, &sk)));
+        assert!(decrypt(ey.lt_plain(&x, &sk)) && !decrypt(ey.ge_plain(&x, &sk)));
+
+        // the trivial ciphertexts are evaluated in the clear, on a width which is not a power of two
+        let (a, b) = (PlainUint::<200>::from_u128(u128::MAX) << 70, PlainUint::<200>::from_u128(0xdead_beef) << 3);
+        let ea = EncUint::trivial(&a, &sk);
+        assert_eq!(ea.add_plain(&b, &sk).decrypt(&ck), a + b);
+        assert_eq!(ea.mul_plain_csd(&b, &sk).decrypt(&ck), a * b);
+        assert!(matches!(EncUint::<8>::try_from(vec![]), Err(FdeError::BadLength { expected: 8, got: 0, .. })));
+    }
+}
@@ -1,8 +1,10 @@
-/// Encrypts and decrypts Ciphertext to booleans
-/// Taken from the tfhe-rs library in the sha256 example main
+//! Encrypts and decrypts Ciphertext to booleans
+//! Taken from the tfhe-rs library in the sha256 example main
 
 use tfhe::boolean::ciphertext::Ciphertext;
 use tfhe::boolean::client_key::ClientKey;
+use tfhe::prelude::*;
+use tfhe::FheUint64;
 
 /// encrypts booleans with a client key
 pub fn encrypt_bools(bools: Vec<bool>, ck: &ClientKey) -> Vec<Ciphertext> {
@@ -22,4 +24,25 @@ pub fn decrypt_bools(ciphertext: &Vec<Ciphertext>, ck: &ClientKey) -> Vec<bool>
         bools.push(ck.decrypt(cipher));
     }
     bools
-}
\ No newline at end of file
+}
+
+/// packs booleans into 64-bit lanes (lsb first, the last lane is padded with zeros) and
+/// encrypts each lane with a client key of the integer API
+pub fn encrypt_bools_uint64(bools: &[bool], ck: &tfhe::ClientKey) -> Vec<FheUint64> {
+    bools.chunks(64)
+        .map(|lane| {
+            let value = lane.iter().enumerate().fold(0u64, |acc, (i, &b)| acc | ((b as u64) << i));
+            FheUint64::encrypt(value, ck)
+        })
+        .collect()
+}
+
+/// decrypts 64-bit lanes to booleans (lsb first) with a client key of the integer API
+pub fn decrypt_bools_uint64(lanes: &[FheUint64], ck: &tfhe::ClientKey) -> Vec<bool> {
+    lanes.iter()
+        .flat_map(|lane| {
+            let value: u64 = lane.decrypt(ck);
+            (0..64).map(move |i| (value >> i) & 1 == 1)
+        })
+        .collect()
+}
@@ -0,0 +1,154 @@
+//! This module implements the FiLIP filter permutator with a direct sum of monomials (FiLIP-DSM,
+//! Méaux et al., "Improved Filter Permutators for Efficient FHE", 2019), generic over the
+//! representation of the bits like `TriviumStream`. For each bit of the keystream, a public
+//! PRNG seeded by the iv selects `n` bits of the `N`-bit key register in a random order and a
+//! whitening mask, and the filter is evaluated on the whitened bits. There is no state to warm
+//! up : the depth of a bit is the degree of the filter, whatever its position in the stream.
+
+use rayon::prelude::*;
+use crate::homomorphic_functions::{Bit, PublicPrng, StreamCipher};
+
+/// The parameters of FiLIP-DSM
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilipParams {
+    /// Number of bits of the key register (N)
+    pub key_len: usize,
+    /// Number of monomials of each degree of the filter, `monomials[d - 1]` being of degree d
+    pub monomials: Vec<usize>,
+}
+
+impl FilipParams {
+    /// FiLIP-1216, with 128-bit security : N = 16384 and 1216 bits in the filter
+    pub fn filip_1216() -> Self {
+        FilipParams { key_len: 16384, monomials: vec![128, 64, 0, 80, 0, 0, 0, 80] }
+    }
+
+    /// Returns the number of variables of the filter (n)
+    pub fn filter_len(&self) -> usize {
+        self.monomials.iter().enumerate().map(|(d, m)| (d + 1) * m).sum()
+    }
+
+    /// Returns the degrees of the monomials of the filter, by increasing degree
+    fn degrees(&self) -> impl Iterator<Item = usize> + '_ {
+        self.monomials.iter().enumerate().flat_map(|(d, &m)| std::iter::repeat_n(d + 1, m))
+    }
+}
+
+/// FilipStream: a struct implementing FiLIP-DSM, using T for the internal representation of bits
+/// (bool, Ciphertext or FheBool), with the key of T to compute the FHE operations.
+pub struct FilipStream<T: Bit> {
+    params: FilipParams,
+    key: Vec<T>,
+    // the indices of the key register, shuffled a little more for each bit of the keystream
+    indices: Vec<usize>,
+    prng: PublicPrng,
+    fhe_key: T::Key,
+}
+
+impl<T: Bit> FilipStream<T> {
+    /// Constructor for `FilipStream<T>`: arguments are the (encrypted) `params.key_len`-bit
+    /// secret key, the iv seeding the public PRNG, and the key of T
+    pub fn new(key: Vec<T>, iv: &[bool], params: FilipParams, fhe_key: &T::Key) -> FilipStream<T> {
+        assert_eq!(key.len(), params.key_len, "length mismatch");
+        assert!(params.filter_len() <= params.key_len, "the filter has more variables than the key");
+        FilipStream {
+            indices: (0..params.key_len).collect(),
+            params,
+            key,
+            prng: PublicPrng::new(b"FiLIP", iv),
+            fhe_key: fhe_key.clone(),
+        }
+    }
+
+    /// Draws the public inputs of the next bit of the keystream : the indices of the key bits
+    /// given to the filter (a partial Fisher-Yates shuffle) and the whitening mask
+    fn next_selection(&mut self) -> (Vec<usize>, Vec<bool>) {
+        let n = self.params.filter_len();
+        for i in 0..n {
+            let j = i + self.prng.below(self.params.key_len - i);
+            self.indices.swap(i, j);
+        }
+        let whitening = (0..n).map(|_| self.prng.bit()).collect();
+        (self.indices[..n].to_vec(), whitening)
+    }
+
+    /// Evaluates the filter on the whitened key bits : each monomial is the AND of its
+    /// variables, and the output is the XOR of the monomials
+    fn filter(&self, selection: &[usize], whitening: &[bool]) -> T {
+        let key = &self.fhe_key;
+        let mut start = 0;
+        let monomials: Vec<(usize, usize)> = self.params.degrees()
+            .map(|d| {
+                start += d;
+                (start - d, start)
+            })
+            .collect();
+        monomials.into_par_iter()
+            .map(|(start, end)| {
+                (start + 1..end).fold(self.key[selection[start]].xor_plain(whitening[start], key), |acc, i| {
+                    acc.and(&self.key[selection[i]].xor_plain(whitening[i], key), key)
+                })
+            })
+            .reduce_with(|a, b| a.xor(&b, key))
+            .expect("the filter has at least one monomial")
+    }
+}
+
+impl<T: Bit> StreamCipher<T> for FilipStream<T> {
+    fn next_n(&mut self, n: usize) -> Vec<T> {
+        // the public inputs are drawn in order, then the bits are computed in parallel
+        let selections: Vec<(Vec<usize>, Vec<bool>)> = (0..n).map(|_| self.next_selection()).collect();
+        selections.par_iter()
+            .map(|(selection, whitening)| self.filter(selection, whitening))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::boolean::prelude::*;
+    use crate::homomorphic_functions::sha3_256_function::bits_to_bytes_lsb;
+
+    #[test]
+    fn test_filip_stream() {
+        let params = FilipParams { key_len: 64, monomials: vec![3, 2, 0, 1] };
+        assert_eq!(params.filter_len(), 11);
+        assert_eq!(FilipParams::filip_1216().filter_len(), 1216);
+        let key: Vec<bool> = (0..64).map(|i| i % 3 == 0).collect();
+        let iv = [true; 128];
+
+        // the first bit, computed from the public inputs by hand
+        let mut stream = FilipStream::<bool>::new(key.clone(), &iv, params.clone(), &());
+        let (selection, whitening) = stream.next_selection();
+        let x: Vec<bool> = selection.iter().zip(&whitening).map(|(&i, &w)| key[i] ^ w).collect();
+        let expected = x[0] ^ x[1] ^ x[2] ^ (x[3] & x[4]) ^ (x[5] & x[6]) ^ (x[7] & x[8] & x[9] & x[10]);
+        let mut sorted = selection.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 11);
+        let mut stream = FilipStream::<bool>::new(key.clone(), &iv, params.clone(), &());
+        let plain = stream.next_n(200);
+        assert_eq!(plain[0], expected);
+        assert!(plain.iter().any(|&b| b) && plain.iter().any(|&b| !b));
+
+        // the encrypted stream stays in sync, the trivial ciphertexts being evaluated in the clear
+        let (ck, sk) = gen_keys();
+        let key_ct: Vec<Ciphertext> = key.iter().map(|&b| sk.trivial_encrypt(b)).collect();
+        let encrypted: Vec<bool> = FilipStream::new(key_ct, &iv, params, &sk)
+            .next_n(200)
+            .iter()
+            .map(|ct| ck.decrypt(ct))
+            .collect();
+        assert_eq!(encrypted, plain);
+    }
+    #[test]
+    fn test_filip_1216_keystream() {
+        // pins the first 64 bits of keystream, which depend on the public PRNG drawing the
+        // selections and the whitening
+        let key: Vec<bool> = (0..16384).map(|i| i % 3 == 0).collect();
+        let iv: Vec<bool> = (0..128).map(|i| i % 5 == 0).collect();
+        let keystream = FilipStream::<bool>::new(key, &iv, FilipParams::filip_1216(), &()).next_n(64);
+        assert_eq!(hex::encode(bits_to_bytes_lsb(&keystream)), "462060954b7b60e0");
+    }
+}
@@ -0,0 +1,280 @@
+//! Counts the gates evaluated by the homomorphic Keccak-f[1600] permutation and the sponge. With
+//! the boolean API, a binary gate between two encrypted bits is bootstrapped, while a NOT or a XOR
+//! with a plaintext (or trivial) bit only negates or copies the ciphertext. The bootstrapped gates make up
+//! almost all of the cost of the homomorphic hash, so these counts measure the circuit
+//! independently of the machine it runs on. The gates of the permutation are also counted on
+//! `keccak_f1600_boolean` itself, by running it on trivial ciphertexts with a key which counts
+//! each gate instead of evaluating it.
+
+use std::fmt;
+use std::ops::{Add, Mul};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tfhe::boolean::prelude::Ciphertext;
+use crate::homomorphic_functions::sha3_256_function::keccak_f1600_boolean;
+use crate::homomorphic_functions::{FilipParams, KeccakSponge, LaneGates, RastaParams, Sha3Backend, SymmetricCipher};
+
+/// Number of rounds of Keccak-f[1600]
+pub const KECCAK_ROUNDS: u64 = 24;
+/// Number of bits of the Keccak-f[1600] state
+const STATE_BITS: u64 = 1600;
+/// Number of bits of a lane of the state
+const LANE_BITS: u64 = 64;
+/// Number of warm-up steps of Trivium and Kreyvium
+const WARM_UP_STEPS: u64 = 1152;
+/// Number of rounds of AES-128
+const AES_ROUNDS: u64 = 10;
+/// Number of bits of an AES block
+const AES_BLOCK_BITS: u64 = 128;
+
+/// The gates evaluated by a homomorphic computation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GateCount {
+    /// XOR gates between two ciphertexts
+    pub xor: u64,
+    /// AND gates between two ciphertexts
+    pub and: u64,
+    /// Negations of a ciphertext, without bootstrapping
+    pub not: u64,
+    /// XOR gates with a plaintext (or trivial) bit, without bootstrapping
+    pub plain_xor: u64,
+}
+
+impl GateCount {
+    /// Returns the number of gates between two ciphertexts, each of which is bootstrapped
+    pub fn bootstrapped(&self) -> u64 {
+        self.xor + self.and
+    }
+
+    /// Returns the number of gates which do not need a bootstrap
+    pub fn free_gates(&self) -> u64 {
+        self.not + self.plain_xor
+    }
+}
+
+impl Add for GateCount {
+    type Output = GateCount;
+
+    fn add(self, other: GateCount) -> GateCount {
+        GateCount {
+            xor: self.xor + other.xor,
+            and: self.and + other.and,
+            not: self.not + other.not,
+            plain_xor: self.plain_xor + other.plain_xor,
+        }
+    }
+}
+
+impl Mul<u64> for GateCount {
+    type Output = GateCount;
+
+    fn mul(self, n: u64) -> GateCount {
+        GateCount { xor: self.xor * n, and: self.and * n, not: self.not * n, plain_xor: self.plain_xor * n }
+    }
+}
+
+impl fmt::Display for GateCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bootstrapped gates (xor = {}, and = {}), {} free gates (not = {}, plaintext xor = {})",
+            self.bootstrapped(), self.xor, self.and, self.free_gates(), self.not, self.plain_xor
+        )
+    }
+}
+
+/// Returns the gates of one round of Keccak-f[1600]
+pub fn keccak_round_gate_count() -> GateCount {
+    // θ : the parities of the 5 columns (4 XOR per bit of a plane), the D lanes (1 XOR per bit
+    // of a plane) and the XOR of D into the whole state. ρ and π only move ciphertexts around
+    let theta = GateCount { xor: 4 * 5 * LANE_BITS + 5 * LANE_BITS + STATE_BITS, ..GateCount::default() };
+    // χ : a[x] ^ (!a[x+1] & a[x+2]), a free NOT then one AND and one XOR per bit
+    let chi = GateCount { xor: STATE_BITS, and: STATE_BITS, not: STATE_BITS, ..GateCount::default() };
+    // ι : the round constant is XORed as a plaintext into the first lane
+    let iota = GateCount { plain_xor: LANE_BITS, ..GateCount::default() };
+    theta + chi + iota
+}
+
+/// Returns the gates of the whole Keccak-f[1600] permutation
+pub fn keccak_f1600_gate_count() -> GateCount {
+    keccak_round_gate_count() * KECCAK_ROUNDS
+}
+
+/// Returns the gates evaluated by `keccak_f1600_boolean`, counted by running it on a trivial state
+pub fn keccak_f1600_counted_gates() -> GateCount {
+    let zero_lane: [Ciphertext; 64] = std::array::from_fn(|_| Ciphertext::Trivial(false));
+    let mut state = std::array::from_fn(|_| std::array::from_fn(|_| zero_lane.clone()));
+    count_keccak_f1600(&mut state)
+}
+
+/// Returns the gates of absorbing one `rate`-bit block of encrypted data in `sponge` : the XOR of
+/// the block into the state, and one permutation (for the first block, the XOR into the all-zero
+/// initial state is actually free)
+pub fn sponge_block_gate_count(sponge: &KeccakSponge) -> GateCount {
+    GateCount { xor: sponge.rate() as u64, ..GateCount::default() } + keccak_f1600_gate_count()
+}
+
+/// Returns the bootstraps of absorbing one `rate`-bit block of encrypted data in `sponge` with
+/// `backend`. The shortint backend bootstraps each bit of the state twice per round at χ (once
+/// to reduce it to its parity, and once for the lookup table), plus the rate bits after the
+/// block is added to a non-zero state. It returns `None` for the integer backend, whose
+/// bootstraps depend on how the integer API splits each `FheUint64` into blocks
+pub fn sponge_block_bootstraps(sponge: &KeccakSponge, backend: Sha3Backend) -> Option<u64> {
+    match backend {
+        Sha3Backend::Boolean => Some(sponge_block_gate_count(sponge).bootstrapped()),
+        Sha3Backend::Shortint => Some(sponge.rate() as u64 + KECCAK_ROUNDS * 2 * STATE_BITS),
+        Sha3Backend::Integer => None,
+    }
+}
+
+/// Returns the gates of computing `bits` bits of encrypted keystream with `cipher` (at most, some
+/// of the first gates of Trivium and Kreyvium having trivial inputs). Trivium and Kreyvium run
+/// their warm-up first. AES computes its key schedule first, then whole blocks whose first
+/// AddRoundKey is free, the counter being public
+pub fn keystream_gate_count(cipher: SymmetricCipher, bits: u64) -> GateCount {
+    match cipher {
+        SymmetricCipher::Trivium => GateCount { xor: 11, and: 3, ..GateCount::default() } * (WARM_UP_STEPS + bits),
+        SymmetricCipher::Kreyvium => GateCount { xor: 12, and: 3, plain_xor: 1, ..GateCount::default() } * (WARM_UP_STEPS + bits),
+        SymmetricCipher::Aes128Ctr => {
+            // the S-box of Boyar and Peralta, and MixColumns : 3 XOR per byte for the XOR t of a
+            // column, then 27 per byte for a[i] ^ t ^ xtime(a[i] ^ a[i+1])
+            let sbox = GateCount { xor: 83, and: 32, not: 4, ..GateCount::default() };
+            let mix_columns = GateCount { xor: 4 * (3 * 8 + 4 * 27), ..GateCount::default() };
+            let add_round_key = GateCount { xor: AES_BLOCK_BITS, ..GateCount::default() };
+            let key_schedule = sbox * (4 * AES_ROUNDS)
+                + add_round_key * AES_ROUNDS
+                + GateCount { plain_xor: 8 * AES_ROUNDS, ..GateCount::default() };
+            let block = GateCount { plain_xor: AES_BLOCK_BITS, ..GateCount::default() }
+                + sbox * (16 * AES_ROUNDS)
+                + mix_columns * (AES_ROUNDS - 1)
+                + add_round_key * AES_ROUNDS;
+            key_schedule + block * bits.div_ceil(AES_BLOCK_BITS)
+        }
+    }
+}
+
+/// Returns the gates of computing `bits` bits of encrypted keystream with FiLIP, which has no
+/// state : each bit is a filter on whitened key bits
+pub fn filip_gate_count(params: &FilipParams, bits: u64) -> GateCount {
+    let monomials: u64 = params.monomials.iter().map(|&m| m as u64).sum();
+    let and: u64 = params.monomials.iter().enumerate().map(|(d, &m)| (d * m) as u64).sum();
+    GateCount { xor: monomials - 1, and, plain_xor: params.filter_len() as u64, ..GateCount::default() } * bits
+}
+
+/// Returns the gates of computing `bits` bits of encrypted keystream with Rasta, which computes
+/// whole blocks whose affine layers are random : the count is the expected one, each row of a
+/// matrix selecting half of the bits on average
+pub fn rasta_gate_count(params: RastaParams, bits: u64) -> GateCount {
+    let (n, rounds) = (params.block_len as u64, params.rounds as u64);
+    let affine = GateCount { xor: n * (n - 2) / 2, plain_xor: n, ..GateCount::default() };
+    let chi = GateCount { xor: n, and: n, not: n, ..GateCount::default() };
+    let feed_forward = GateCount { xor: n, ..GateCount::default() };
+    (affine * (rounds + 1) + chi * rounds + feed_forward) * bits.div_ceil(n)
+}
+
+// The gates of the permutation, counted instead of evaluated : the ciphertexts are trivial, so the
+// values are computed in plaintext
+#[derive(Default)]
+struct CountingGates {
+    xor: AtomicU64,
+    and: AtomicU64,
+    not: AtomicU64,
+    plain_xor: AtomicU64,
+}
+
+impl CountingGates {
+    fn count(&self) -> GateCount {
+        GateCount {
+            xor: self.xor.load(Ordering::Relaxed),
+            and: self.and.load(Ordering::Relaxed),
+            not: self.not.load(Ordering::Relaxed),
+            plain_xor: self.plain_xor.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl LaneGates for CountingGates {
+    fn xor_gate(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.xor.fetch_add(1, Ordering::Relaxed);
+        Ciphertext::Trivial(value(a) ^ value(b))
+    }
+
+    fn and_gate(&self, a: &Ciphertext, b: &Ciphertext) -> Ciphertext {
+        self.and.fetch_add(1, Ordering::Relaxed);
+        Ciphertext::Trivial(value(a) & value(b))
+    }
+
+    fn not_gate(&self, a: &Ciphertext) -> Ciphertext {
+        self.not.fetch_add(1, Ordering::Relaxed);
+        Ciphertext::Trivial(!value(a))
+    }
+
+    fn xor_plain_gate(&self, a: &Ciphertext, b: bool) -> Ciphertext {
+        self.plain_xor.fetch_add(1, Ordering::Relaxed);
+        Ciphertext::Trivial(value(a) ^ b)
+    }
+}
+
+// Returns the value of a trivial ciphertext
+fn value(ct: &Ciphertext) -> bool {
+    match ct {
+        Ciphertext::Trivial(value) => *value,
+        Ciphertext::Encrypted(_) => unreachable!("the gates are only counted on trivial ciphertexts"),
+    }
+}
+
+// Runs `keccak_f1600_boolean` on a trivial state and returns the gates it evaluated
+fn count_keccak_f1600(state: &mut [[[Ciphertext; 64]; 5]; 5]) -> GateCount {
+    let gates = CountingGates::default();
+    let mut c_buf = state[0].clone();
+    let mut d_buf = state[0].clone();
+    keccak_f1600_boolean(state, &gates, &mut c_buf, &mut d_buf);
+    gates.count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keccak_gate_count() {
+        // the gates evaluated by keccak_f1600_boolean, which also computes Keccak-f[1600] of the
+        // all-zero state on the trivial ciphertexts
+        let zero_lane: [Ciphertext; 64] = std::array::from_fn(|_| Ciphertext::Trivial(false));
+        let mut state = std::array::from_fn(|_| std::array::from_fn(|_| zero_lane.clone()));
+        let counted = count_keccak_f1600(&mut state);
+        let lane = state[0][0].iter().rev().fold(0u64, |acc, bit| acc << 1 | value(bit) as u64);
+        assert_eq!(lane, 0xF1258F7940E1DDE7);
+        assert_eq!(counted, keccak_f1600_gate_count());
+        assert_eq!(keccak_f1600_counted_gates(), counted);
+
+        // χ costs one AND and one XOR per bit : its NOT is free, as the XOR with a trivial all-ones
+        // lane it replaced already was, so a permutation costs 153,600 bootstraps either way
+        assert_eq!(counted.bootstrapped(), 153_600);
+        assert_eq!(counted.and, KECCAK_ROUNDS * STATE_BITS);
+        assert_eq!(counted.not, KECCAK_ROUNDS * STATE_BITS);
+        assert_eq!(counted.plain_xor, KECCAK_ROUNDS * LANE_BITS);
+
+        let block = sponge_block_gate_count(&KeccakSponge::sha3_256());
+        assert_eq!(block.bootstrapped(), 153_600 + 1088);
+        assert_eq!(sponge_block_bootstraps(&KeccakSponge::sha3_256(), Sha3Backend::Boolean), Some(block.bootstrapped()));
+        assert_eq!(sponge_block_bootstraps(&KeccakSponge::sha3_256(), Sha3Backend::Shortint), Some(76_800 + 1088));
+        assert_eq!(sponge_block_bootstraps(&KeccakSponge::sha3_256(), Sha3Backend::Integer), None);
+    }
+
+    #[test]
+    fn test_keystream_gate_count() {
+        // the warm-up of Trivium costs as much as 1152 bits of keystream
+        assert_eq!(keystream_gate_count(SymmetricCipher::Trivium, 0).bootstrapped(), 16_128);
+        assert_eq!(keystream_gate_count(SymmetricCipher::Trivium, 1088).bootstrapped(), 14 * 2240);
+        assert_eq!(keystream_gate_count(SymmetricCipher::Kreyvium, 0).plain_xor, 1152);
+        // FiLIP-1216 : 352 monomials with 864 AND gates, for each bit
+        assert_eq!(filip_gate_count(&FilipParams::filip_1216(), 1), GateCount { xor: 351, and: 864, plain_xor: 1216, not: 0 });
+        assert_eq!(filip_gate_count(&FilipParams::filip_1216(), 0), GateCount::default());
+        // Rasta computes a whole block of 351 bits
+        assert_eq!(rasta_gate_count(RastaParams::rasta_351(), 1), rasta_gate_count(RastaParams::rasta_351(), 351));
+        assert_eq!(rasta_gate_count(RastaParams::rasta_351(), 1).and, 6 * 351);
+        // AES-128 : 200 S-boxes of 115 gates per block, 40 of them in the key schedule
+        assert_eq!(keystream_gate_count(SymmetricCipher::Aes128Ctr, 0).bootstrapped(), 40 * 115 + 1280);
+        assert_eq!(keystream_gate_count(SymmetricCipher::Aes128Ctr, 129).and, 32 * (40 + 2 * 160));
+    }
+}
@@ -0,0 +1,197 @@
+//! Homomorphic Keccak sponge on the high-level integer API of TFHE. A lane of the state is one
+//! `FheUint64` instead of 64 boolean ciphertexts, and θ, ρ, π, χ and ι use the native xor,
+//! rotate, not and and of the encrypted integers (the round constants are XORed as plaintexts).
+//!
+//! It needs its own keys (`gen_integer_keys`), the data being encrypted lane by lane with
+//! `encrypt_bools_uint64`, so the protocols, which transcipher under boolean keys, do not use
+//! it : it is benchmarked with `fde bench --sha3-backend integer`.
+
+use tfhe::prelude::*;
+use tfhe::{generate_keys, with_server_key_as_context, ClientKey, ConfigBuilder, FheUint64, ServerKey};
+use crate::error::FdeError;
+use crate::homomorphic_functions::sha3_256_function::{N_ROUNDS, RC};
+use crate::homomorphic_functions::KeccakSponge;
+
+/// Generates the keys of the integer backend, with the default parameters of the integer API
+pub fn gen_integer_keys() -> (ClientKey, ServerKey) {
+    generate_keys(ConfigBuilder::default().build())
+}
+
+/// Incremental homomorphic hashing with the integer backend, the counterpart of `Sha3FheHasher`
+/// fed with lanes of 64 bits
+pub struct IntegerSha3Hasher {
+    sponge: KeccakSponge,
+    state: [[FheUint64; 5]; 5],
+    // the lanes received since the last absorbed block
+    pending: Vec<FheUint64>,
+    absorbed: usize,
+}
+
+impl IntegerSha3Hasher {
+    pub fn new(sponge: KeccakSponge, sk: &ServerKey) -> Self {
+        let state = with_server_key_as_context(sk.clone(), || {
+            std::array::from_fn(|_| std::array::from_fn(|_| FheUint64::encrypt_trivial(0u64)))
+        });
+        IntegerSha3Hasher { sponge, state, pending: Vec::with_capacity(sponge.rate() / 64), absorbed: 0 }
+    }
+
+    /// Returns the number of `rate`-bit blocks absorbed so far
+    pub fn absorbed_blocks(&self) -> usize {
+        self.absorbed
+    }
+
+    /// Feeds encrypted lanes to the sponge : every complete `rate`-bit block is absorbed right
+    /// away, and the remaining lanes are kept until the next call
+    pub fn update(&mut self, mut lanes: &[FheUint64], sk: &ServerKey) {
+        let rate_lanes = self.sponge.rate() / 64;
+        with_server_key_as_context(sk.clone(), || {
+            if !self.pending.is_empty() {
+                let missing = (rate_lanes - self.pending.len()).min(lanes.len());
+                self.pending.extend_from_slice(&lanes[..missing]);
+                lanes = &lanes[missing..];
+                if self.pending.len() < rate_lanes {
+                    return;
+                }
+                let block = std::mem::take(&mut self.pending);
+                self.absorb_block(&block);
+            }
+            let mut blocks = lanes.chunks_exact(rate_lanes);
+            for block in blocks.by_ref() {
+                self.absorb_block(block);
+            }
+            self.pending.extend_from_slice(blocks.remainder());
+        })
+    }
+
+    // XORs one block of lanes into the state, and performs the keccak permutation
+    fn absorb_block(&mut self, block: &[FheUint64]) {
+        for (j, lane) in block.iter().enumerate() {
+            self.state[j % 5][j / 5] ^= lane;
+        }
+        keccak_f1600_integer(&mut self.state);
+        self.absorbed += 1;
+    }
+
+    /// Squeezes the encrypted lanes holding the `output_len` bits of the hash (the bits of the
+    /// last lane after `output_len` are not part of it). The input must have been padded, so it
+    /// fails if no block was absorbed or if a partial block is left
+    pub fn finalize(mut self, sk: &ServerKey) -> Result<Vec<FheUint64>, FdeError> {
+        let rate = self.sponge.rate();
+        if self.absorbed == 0 || !self.pending.is_empty() {
+            let got = self.absorbed * rate + self.pending.len() * 64;
+            return Err(FdeError::BadLength { what: "padded input (in bits)", expected: got.div_ceil(rate).max(1) * rate, got });
+        }
+
+        // Squeeze `rate` bits at a time, permuting between two blocks of output
+        let output_lanes = self.sponge.output_len().div_ceil(64);
+        let mut out = Vec::with_capacity(output_lanes);
+        with_server_key_as_context(sk.clone(), || {
+            while out.len() < output_lanes {
+                if !out.is_empty() {
+                    keccak_f1600_integer(&mut self.state);
+                }
+                let squeezed = (rate / 64).min(output_lanes - out.len());
+                out.extend((0..squeezed).map(|j| self.state[j % 5][j / 5].clone()));
+            }
+        });
+        Ok(out)
+    }
+}
+
+impl KeccakSponge {
+    /// Returns an incremental hasher with this sponge on the integer backend
+    pub fn integer_hasher(&self, sk: &ServerKey) -> IntegerSha3Hasher {
+        IntegerSha3Hasher::new(*self, sk)
+    }
+
+    /// Hashes padded data encrypted with `encrypt_bools_uint64`, and returns the encrypted lanes
+    /// of the hash
+    pub fn hash_integer(&self, input: &[FheUint64], sk: &ServerKey) -> Result<Vec<FheUint64>, FdeError> {
+        let mut hasher = self.integer_hasher(sk);
+        hasher.update(input, sk);
+        hasher.finalize(sk)
+    }
+}
+
+// -------------------------- HELPER FUNCTIONS ---------------------------------------
+
+// This function does the keccak f1600 permutation on the integer backend, the server key must be
+// set on the calling thread
+#[allow(clippy::needless_range_loop)]
+fn keccak_f1600_integer(state: &mut [[FheUint64; 5]; 5]) {
+    for r in 0..N_ROUNDS {
+        // θ phase
+        let c: Vec<FheUint64> = (0..5)
+            .map(|x| (1..5).fold(state[x][0].clone(), |acc, y| acc ^ &state[x][y]))
+            .collect();
+        for x in 0..5 {
+            let d = &c[(x + 4) % 5] ^ &(&c[(x + 1) % 5]).rotate_left(1u32);
+            for y in 0..5 {
+                state[x][y] ^= &d;
+            }
+        }
+
+        // ρ + π phase
+        let mut x = 1;
+        let mut y = 0;
+        let mut current = state[x][y].clone();
+        for t in 0..24 {
+            let new_x = y;
+            let new_y = (2*x + 3*y) % 5;
+            let tmp = state[new_x][new_y].clone();
+            state[new_x][new_y] = (&current).rotate_left((((t+1)*(t+2)/2) % 64) as u32);
+            current = tmp;
+            x = new_x;
+            y = new_y;
+        }
+
+        // χ phase
+        for y in 0..5 {
+            let plane: Vec<FheUint64> = (0..5).map(|x| state[x][y].clone()).collect();
+            for x in 0..5 {
+                state[x][y] = &plane[x] ^ &(!&plane[(x + 1) % 5] & &plane[(x + 2) % 5]);
+            }
+        }
+
+        // ι phase, XOR with a plaintext constant
+        state[0][0] ^= RC[r];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic_functions::{bools_to_hex, decrypt_bools_uint64, encrypt_bools_uint64, HashFunction};
+
+    #[test]
+    fn test_integer_sponge() {
+        // trivial ciphertexts are evaluated in the clear, so the whole sponge runs quickly
+        let (ck, sk) = gen_integer_keys();
+        let hash = |hash_function: HashFunction, data: &[u8]| -> String {
+            let sponge = hash_function.sponge();
+            let bits = sponge.pad_bytes(data);
+            let input: Vec<FheUint64> = with_server_key_as_context(sk.clone(), || {
+                bits.chunks(64)
+                    .map(|lane| FheUint64::encrypt_trivial(lane.iter().rev().fold(0u64, |acc, &b| acc << 1 | b as u64)))
+                    .collect()
+            });
+            let mut hasher = sponge.integer_hasher(&sk);
+            for piece in input.chunks(11) {
+                hasher.update(piece, &sk);
+            }
+            bools_to_hex(&decrypt_bools_uint64(&hasher.finalize(&sk).unwrap(), &ck))
+        };
+
+        let data = b"fair data exchange";
+        assert_eq!(hash(HashFunction::Sha3_256, data), HashFunction::Sha3_256.hex_digest(data));
+        assert_eq!(hash(HashFunction::Keccak256, data), HashFunction::Keccak256.hex_digest(data));
+        let long_data = vec![7u8; 200];
+        assert_eq!(hash(HashFunction::Sha3_256, &long_data), HashFunction::Sha3_256.hex_digest(&long_data));
+
+        assert!(matches!(KeccakSponge::sha3_256().hash_integer(&[], &sk), Err(FdeError::BadLength { .. })));
+        let bits: Vec<bool> = (0..70).map(|i| i % 3 == 0).collect();
+        let lanes = encrypt_bools_uint64(&bits, &ck);
+        assert_eq!(lanes.len(), 2);
+        assert_eq!(decrypt_bools_uint64(&lanes, &ck)[..70], bits[..]);
+    }
+}
@@ -0,0 +1,434 @@
+//! Homomorphic Keccak sponge over ciphertexts, parameterized by its rate, capacity, domain
+//! separator and output length. It offers SHA3-224/256/384/512 and SHAKE128/256 (with an
+//! arbitrary-length squeeze), so that datasets committed with other hash standards than
+//! SHA3-256 can be exchanged, and the original Keccak-256 computed by the EVM.
+
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::error::{check_len, FdeError};
+use crate::homomorphic_functions::sha3_256_function::keccak_f1600_boolean;
+use crate::homomorphic_functions::{hex_keccak256, hex_sha3, keccak256_hash_from_vec_bool, sha3_hash_from_vec_bool, xor_64};
+
+/// Width of the Keccak-f[1600] state, in bits
+pub const KECCAK_WIDTH: usize = 1600;
+/// Domain separator of the SHA3 hash functions (the 01 suffix followed by the first padding bit)
+pub const SHA3_SUFFIX: u8 = 0x06;
+/// Domain separator of the original Keccak submission used by Ethereum (no suffix, only the
+/// first padding bit)
+pub const KECCAK_SUFFIX: u8 = 0x01;
+/// Domain separator of the SHAKE extendable-output functions (the 1111 suffix followed by the
+/// first padding bit)
+pub const SHAKE_SUFFIX: u8 = 0x1F;
+
+/// The parameters of a Keccak sponge. All the lengths are in bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeccakSponge {
+    rate: usize,
+    capacity: usize,
+    suffix: u8,
+    output_len: usize,
+}
+
+impl KeccakSponge {
+    /// Creates a sponge absorbing `rate` bits per permutation and squeezing `output_len` bits.
+    /// `suffix` holds the domain separation bits followed by the first bit of the pad10*1 padding
+    pub fn new(rate: usize, capacity: usize, suffix: u8, output_len: usize) -> Result<Self, FdeError> {
+        check_len("rate + capacity (in bits)", KECCAK_WIDTH, rate + capacity)?;
+        if rate == 0 || !rate.is_multiple_of(64) {
+            return Err(FdeError::BadLength { what: "rate (in bits)", expected: rate.div_ceil(64).max(1) * 64, got: rate });
+        }
+        if !output_len.is_multiple_of(8) {
+            return Err(FdeError::BadLength { what: "output (in bits)", expected: output_len.div_ceil(8) * 8, got: output_len });
+        }
+        if suffix == 0 || suffix & 0x80 != 0 {
+            return Err(FdeError::Deserialize(format!("invalid domain separator {:#04x}", suffix)));
+        }
+        Ok(KeccakSponge { rate, capacity, suffix, output_len })
+    }
+
+    // The standard variants, with a capacity of twice the security level
+    fn standard(capacity: usize, suffix: u8, output_len: usize) -> Self {
+        KeccakSponge { rate: KECCAK_WIDTH - capacity, capacity, suffix, output_len }
+    }
+
+    pub fn sha3_224() -> Self {
+        Self::standard(448, SHA3_SUFFIX, 224)
+    }
+
+    pub fn sha3_256() -> Self {
+        Self::standard(512, SHA3_SUFFIX, 256)
+    }
+
+    pub fn sha3_384() -> Self {
+        Self::standard(768, SHA3_SUFFIX, 384)
+    }
+
+    pub fn sha3_512() -> Self {
+        Self::standard(1024, SHA3_SUFFIX, 512)
+    }
+
+    /// Keccak-256 as computed by the `keccak256` of the EVM
+    pub fn keccak256() -> Self {
+        Self::standard(512, KECCAK_SUFFIX, 256)
+    }
+
+    /// SHAKE128, squeezing `output_len` bits (a multiple of 8)
+    pub fn shake128(output_len: usize) -> Result<Self, FdeError> {
+        Self::new(1344, 256, SHAKE_SUFFIX, output_len)
+    }
+
+    /// SHAKE256, squeezing `output_len` bits (a multiple of 8)
+    pub fn shake256(output_len: usize) -> Result<Self, FdeError> {
+        Self::new(1088, 512, SHAKE_SUFFIX, output_len)
+    }
+
+    pub fn rate(&self) -> usize {
+        self.rate
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn suffix(&self) -> u8 {
+        self.suffix
+    }
+
+    pub fn output_len(&self) -> usize {
+        self.output_len
+    }
+
+    /// Returns the padding bytes appended to a message of `len` bytes
+    pub fn padding(&self, len: usize) -> Vec<u8> {
+        let rate_bytes = self.rate / 8;
+        let pad_len = rate_bytes - len % rate_bytes;
+        // If we only need one byte to reach a block, the suffix and the final bit share it
+        if pad_len == 1 {
+            return vec![self.suffix | 0x80];
+        }
+        // Otherwise, the suffix, zeros and finally 0x80
+        let mut padding = vec![0x00; pad_len];
+        padding[0] = self.suffix;
+        padding[pad_len - 1] = 0x80;
+        padding
+    }
+
+    /// Pads plaintext data before it is encrypted and then hashed
+    pub fn pad_bytes(&self, data: &[u8]) -> Vec<bool> {
+        data.iter()
+            .chain(self.padding(data.len()).iter())
+            .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8))
+            .collect()
+    }
+
+    /// Pads encrypted data (whole bytes) with trivial ciphertexts
+    pub fn pad_cipher(&self, mut ct: Vec<Ciphertext>, sk: &ServerKey) -> Result<Vec<Ciphertext>, FdeError> {
+        if !ct.len().is_multiple_of(8) {
+            return Err(FdeError::BadLength { what: "data to pad (in bits)", expected: ct.len().div_ceil(8) * 8, got: ct.len() });
+        }
+        for byte in self.padding(ct.len() / 8) {
+            ct.extend((0..8).map(|i| sk.trivial_encrypt((byte >> i) & 1 == 1)));
+        }
+        Ok(ct)
+    }
+
+    /// Removes the padding of decrypted data, and returns its bytes
+    pub fn unpad_bytes(&self, padded_bits: &[bool]) -> Result<Vec<u8>, FdeError> {
+        if padded_bits.is_empty() || !padded_bits.len().is_multiple_of(8) {
+            return Err(FdeError::BadLength {
+                what: "padded data (in bits)",
+                expected: padded_bits.len().div_ceil(8).max(1) * 8,
+                got: padded_bits.len(),
+            });
+        }
+        let mut bytes: Vec<u8> = padded_bits.chunks(8)
+            .map(|chunk| chunk.iter().enumerate().fold(0u8, |acc, (i, &bit)| if bit { acc | (1 << i) } else { acc }))
+            .collect();
+
+        let last = bytes.pop().unwrap();
+        if last == self.suffix | 0x80 {
+            return Ok(bytes);
+        }
+        if last == 0x80 {
+            // the bytes between the suffix and 0x80 must all be zero
+            while bytes.last() == Some(&0x00) {
+                bytes.pop();
+            }
+            if bytes.pop() == Some(self.suffix) {
+                return Ok(bytes);
+            }
+        }
+        Err(FdeError::BadPadding)
+    }
+
+    /// Hashes padded encrypted data homomorphically, and returns `output_len` encrypted bits
+    pub fn hash(&self, input: &[Ciphertext], sk: &ServerKey) -> Result<Vec<Ciphertext>, FdeError> {
+        let mut hasher = self.hasher(sk);
+        hasher.update(input, sk);
+        hasher.finalize(sk)
+    }
+
+    /// Returns an incremental hasher with this sponge, to absorb the padded data as it arrives
+    pub fn hasher(&self, sk: &ServerKey) -> Sha3FheHasher {
+        Sha3FheHasher::new(*self, sk)
+    }
+}
+
+/// Incremental homomorphic hashing : the blocks of padded encrypted data are absorbed as soon as
+/// they are received (or decrypted), so that the whole input never has to be held in memory
+pub struct Sha3FheHasher {
+    sponge: KeccakSponge,
+    state: [[[Ciphertext; 64]; 5]; 5],
+    // the bits received since the last absorbed block
+    pending: Vec<Ciphertext>,
+    absorbed: usize,
+    // scratch buffers of the permutation
+    c_buf: [[Ciphertext; 64]; 5],
+    d_buf: [[Ciphertext; 64]; 5],
+}
+
+impl Sha3FheHasher {
+    pub fn new(sponge: KeccakSponge, sk: &ServerKey) -> Self {
+        // Prepare trivial ciphertexts
+        let zero = sk.trivial_encrypt(false);
+        let zero_uint64: [Ciphertext; 64] = std::array::from_fn(|_| zero.clone());
+        let five_zero_uint64: [[Ciphertext; 64]; 5] = std::array::from_fn(|_| zero_uint64.clone());
+
+        Sha3FheHasher {
+            sponge,
+            state: std::array::from_fn(|_| five_zero_uint64.clone()),
+            pending: Vec::with_capacity(sponge.rate),
+            absorbed: 0,
+            c_buf: five_zero_uint64.clone(),
+            d_buf: five_zero_uint64,
+        }
+    }
+
+    /// A hasher computing SHA3-256
+    pub fn sha3_256(sk: &ServerKey) -> Self {
+        Self::new(KeccakSponge::sha3_256(), sk)
+    }
+
+    pub fn sponge(&self) -> &KeccakSponge {
+        &self.sponge
+    }
+
+    /// Returns the number of `rate`-bit blocks absorbed so far
+    pub fn absorbed_blocks(&self) -> usize {
+        self.absorbed
+    }
+
+    /// Feeds encrypted bits to the sponge : every complete `rate`-bit block is absorbed right
+    /// away, and the remaining bits are kept until the next call
+    pub fn update(&mut self, mut bits: &[Ciphertext], sk: &ServerKey) {
+        let rate = self.sponge.rate;
+        if !self.pending.is_empty() {
+            let missing = (rate - self.pending.len()).min(bits.len());
+            self.pending.extend_from_slice(&bits[..missing]);
+            bits = &bits[missing..];
+            if self.pending.len() < rate {
+                return;
+            }
+            let block = std::mem::take(&mut self.pending);
+            self.absorb_block(&block, sk);
+        }
+        let mut blocks = bits.chunks_exact(rate);
+        for block in blocks.by_ref() {
+            self.absorb_block(block, sk);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    // XORs one `rate`-bit block into the state, and performs the keccak permutation
+    fn absorb_block(&mut self, block: &[Ciphertext], sk: &ServerKey) {
+        for (j, lane) in block.chunks(64).enumerate() {
+            let lane: [Ciphertext; 64] = std::array::from_fn(|i| lane[i].clone());
+            self.state[j % 5][j / 5] = xor_64(&self.state[j % 5][j / 5], &lane, sk);
+        }
+        keccak_f1600_boolean(&mut self.state, sk, &mut self.c_buf, &mut self.d_buf);
+        self.absorbed += 1;
+    }
+
+    /// Squeezes the `output_len` encrypted bits of the hash. The input must have been padded, so
+    /// it fails if no block was absorbed or if a partial block is left
+    pub fn finalize(mut self, sk: &ServerKey) -> Result<Vec<Ciphertext>, FdeError> {
+        let rate = self.sponge.rate;
+        if self.absorbed == 0 || !self.pending.is_empty() {
+            let got = self.absorbed * rate + self.pending.len();
+            return Err(FdeError::BadLength { what: "padded input (in bits)", expected: got.div_ceil(rate).max(1) * rate, got });
+        }
+
+        // Squeeze `rate` bits at a time, permuting between two blocks of output
+        let output_len = self.sponge.output_len;
+        let mut out = Vec::with_capacity(output_len);
+        while out.len() < output_len {
+            if !out.is_empty() {
+                keccak_f1600_boolean(&mut self.state, sk, &mut self.c_buf, &mut self.d_buf);
+            }
+            let squeezed = rate.min(output_len - out.len());
+            out.extend((0..squeezed).map(|k| self.state[(k / 64) % 5][(k / 64) / 5][k % 64].clone()));
+        }
+        Ok(out)
+    }
+}
+
+/// The 256-bit hash function of an exchange : it hashes the data, and in Protocol II the
+/// symmetric key and the random value a
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashFunction {
+    #[default]
+    Sha3_256,
+    /// Keccak-256, so that the hashes can be checked by the `keccak256` of a smart contract
+    Keccak256,
+}
+
+impl HashFunction {
+    /// Returns the homomorphic sponge of the hash function
+    pub fn sponge(self) -> KeccakSponge {
+        match self {
+            HashFunction::Sha3_256 => KeccakSponge::sha3_256(),
+            HashFunction::Keccak256 => KeccakSponge::keccak256(),
+        }
+    }
+
+    /// Returns the hex digest of `data`
+    pub fn hex_digest(self, data: &[u8]) -> String {
+        match self {
+            HashFunction::Sha3_256 => hex_sha3(data),
+            HashFunction::Keccak256 => hex_keccak256(data),
+        }
+    }
+
+    /// Returns the hex digest of the bytes of `data` (lsb first in each byte)
+    pub fn hash_from_vec_bool(self, data: Vec<bool>) -> String {
+        match self {
+            HashFunction::Sha3_256 => sha3_hash_from_vec_bool(data),
+            HashFunction::Keccak256 => keccak256_hash_from_vec_bool(data),
+        }
+    }
+}
+
+impl FromStr for HashFunction {
+    type Err = FdeError;
+
+    fn from_str(s: &str) -> Result<Self, FdeError> {
+        match s {
+            "sha3_256" => Ok(HashFunction::Sha3_256),
+            "keccak256" => Ok(HashFunction::Keccak256),
+            _ => Err(FdeError::Deserialize(format!("unknown hash function {} (expected sha3_256 or keccak256)", s))),
+        }
+    }
+}
+
+/// The TFHE backend computing the homomorphic sponge in `fde bench`. The protocols always hash
+/// with `Boolean`, as the data is transciphered under the boolean keys of the server
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sha3Backend {
+    /// The boolean API, every gate between two ciphertexts is bootstrapped (`Sha3FheHasher`)
+    #[default]
+    Boolean,
+    /// The shortint API, the linear layers are leveled and only χ is bootstrapped
+    /// (`ShortintSha3Hasher`)
+    Shortint,
+    /// The integer API, a lane is one `FheUint64` with native xor, rotate, not and and
+    /// (`IntegerSha3Hasher`)
+    Integer,
+}
+
+impl FromStr for Sha3Backend {
+    type Err = FdeError;
+
+    fn from_str(s: &str) -> Result<Self, FdeError> {
+        match s {
+            "boolean" => Ok(Sha3Backend::Boolean),
+            "shortint" => Ok(Sha3Backend::Shortint),
+            "integer" => Ok(Sha3Backend::Integer),
+            _ => Err(FdeError::Deserialize(format!("unknown backend {} (expected boolean, shortint or integer)", s))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha3::digest::{ExtendableOutput, Update, XofReader};
+    use sha3::{Digest, Sha3_224, Sha3_384, Sha3_512, Shake128};
+    use crate::homomorphic_functions::{bits_to_bytes_lsb, decrypt_bools, pad_sha3_256_bytes, unpad_sha3_256_bytes};
+
+    #[test]
+    fn test_sponge_padding() {
+        let sha3_256 = KeccakSponge::sha3_256();
+        let shake = KeccakSponge::shake128(256).unwrap();
+        for len in [0, 1, 134, 135, 136, 137, 300] {
+            let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            assert_eq!(sha3_256.pad_bytes(&data), pad_sha3_256_bytes(&data));
+            for sponge in [sha3_256, shake, KeccakSponge::sha3_512()] {
+                let padded = sponge.pad_bytes(&data);
+                assert!(padded.len().is_multiple_of(sponge.rate()));
+                assert_eq!(sponge.unpad_bytes(&padded).unwrap(), data);
+            }
+        }
+        // SHA3 padding is not valid SHAKE padding
+        assert!(matches!(shake.unpad_bytes(&sha3_256.pad_bytes(&[1, 2])), Err(FdeError::BadPadding)));
+        assert!(unpad_sha3_256_bytes(&shake.pad_bytes(&[1, 2])).is_err());
+
+        assert!(matches!(KeccakSponge::new(1024, 512, SHA3_SUFFIX, 256), Err(FdeError::BadLength { .. })));
+        assert!(matches!(KeccakSponge::new(1000, 600, SHA3_SUFFIX, 256), Err(FdeError::BadLength { .. })));
+        assert!(matches!(KeccakSponge::shake256(12), Err(FdeError::BadLength { .. })));
+        assert!(KeccakSponge::new(1088, 512, 0x86, 256).is_err());
+
+        // the keccak256 of the EVM
+        assert_eq!(hex_keccak256(b""), "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470");
+        let keccak = KeccakSponge::keccak256();
+        assert_eq!(keccak.unpad_bytes(&keccak.pad_bytes(&[0x01; 135])).unwrap(), vec![0x01; 135]);
+        assert_eq!("keccak256".parse::<HashFunction>().unwrap(), HashFunction::Keccak256);
+        assert!("keccak".parse::<HashFunction>().is_err());
+    }
+
+    #[test]
+    fn test_sponge_variants() {
+        // trivial ciphertexts are evaluated in the clear, so the whole sponge runs quickly
+        let (ck, sk) = gen_keys();
+        let data = b"fair data exchange".to_vec();
+        let hash = |sponge: KeccakSponge| -> Vec<u8> {
+            let input: Vec<Ciphertext> = sponge.pad_bytes(&data).iter().map(|&b| sk.trivial_encrypt(b)).collect();
+            let out = sponge.hash(&input, &sk).unwrap();
+            assert_eq!(out.len(), sponge.output_len());
+            bits_to_bytes_lsb(&decrypt_bools(&out, &ck))
+        };
+
+        assert_eq!(hash(KeccakSponge::sha3_224()), Sha3_224::digest(&data).to_vec());
+        assert_eq!(hex::encode(hash(KeccakSponge::sha3_256())), crate::homomorphic_functions::hex_sha3(&data));
+        assert_eq!(hash(KeccakSponge::sha3_384()), Sha3_384::digest(&data).to_vec());
+        assert_eq!(hash(KeccakSponge::sha3_512()), Sha3_512::digest(&data).to_vec());
+        assert_eq!(hex::encode(hash(HashFunction::Keccak256.sponge())), HashFunction::Keccak256.hex_digest(&data));
+
+        // squeeze more than one block of output
+        let mut expected = vec![0u8; 200];
+        let mut shake = Shake128::default();
+        shake.update(&data);
+        shake.finalize_xof().read(&mut expected);
+        assert_eq!(hash(KeccakSponge::shake128(1600).unwrap()), expected);
+
+        let sponge = KeccakSponge::sha3_256();
+        assert!(matches!(sponge.hash(&[], &sk), Err(FdeError::BadLength { .. })));
+
+        // the incremental hasher does not depend on how the blocks are split when they arrive
+        let long_data = vec![7u8; 200];
+        let input: Vec<Ciphertext> = sponge.pad_bytes(&long_data).iter().map(|&b| sk.trivial_encrypt(b)).collect();
+        let mut hasher = sponge.hasher(&sk);
+        for piece in input.chunks(700) {
+            hasher.update(piece, &sk);
+        }
+        assert_eq!(hasher.absorbed_blocks(), 2);
+        let out = hasher.finalize(&sk).unwrap();
+        assert_eq!(hex::encode(bits_to_bytes_lsb(&decrypt_bools(&out, &ck))), crate::homomorphic_functions::hex_sha3(&long_data));
+        let mut hasher = sponge.hasher(&sk);
+        hasher.update(&input[..100], &sk);
+        assert!(matches!(hasher.finalize(&sk), Err(FdeError::BadLength { expected: 1088, got: 100, .. })));
+        assert!(matches!(sponge.pad_cipher(vec![sk.trivial_encrypt(true)], &sk), Err(FdeError::BadLength { .. })));
+    }
+}
@@ -0,0 +1,234 @@
+//! This module implements the Kreyvium stream cipher, a variant of Trivium with a 128-bit key and
+//! iv (Canteaut et al., FSE 2016), generic over the representation of the inner bits like
+//! `TriviumStream`. On top of the three registers of Trivium, the key and the iv are kept in two
+//! 128-bit registers, one bit of each being XORed in the state at every step. The iv register is
+//! public, so it is always kept in plaintext.
+//! The bits of the key and iv are in the same order as for Trivium, the bits of each byte being
+//! lsb first.
+
+use crate::static_deque::StaticDeque;
+use rayon::prelude::*;
+use tfhe::boolean::prelude::*;
+use crate::homomorphic_functions::{Bit, StreamCipher};
+
+/// KreyviumStream: a struct implementing the Kreyvium stream cipher, using T for the internal
+/// representation of bits (bool, Ciphertext or FheBool), with the key of T to compute the FHE
+/// operations (`()` for bool).
+pub struct KreyviumStream<T: Bit> {
+    a: StaticDeque<93, T>,
+    b: StaticDeque<84, T>,
+    c: StaticDeque<111, T>,
+    k: StaticDeque<128, T>,
+    iv: StaticDeque<128, bool>,
+    fhe_key: T::Key,
+}
+
+impl<T: Bit> KreyviumStream<T> {
+    /// Constructor for `KreyviumStream<T>`: arguments are the (encrypted) secret key, the input
+    /// vector, and the key of T.
+    /// Outputs a KreyviumStream object already initialized (1152 steps have been run before
+    /// returning)
+    pub fn new(mut key: [T; 128], mut iv: [bool; 128], fhe_key: &T::Key) -> KreyviumStream<T> {
+        // Initialization of the registers: a has the first 93 bits of the secret key, b the first
+        // 84 bits of the input vector, and c its 44 last bits followed by ones and a zero
+        let a_register: [T; 93] = std::array::from_fn(|i| key[128 - 93 + i].clone());
+        let b_register: [T; 84] = std::array::from_fn(|i| T::trivial(iv[128 - 84 + i], fhe_key));
+        let c_register: [T; 111] = std::array::from_fn(|i| match i {
+            0 => T::trivial(false, fhe_key),
+            1..=66 => T::trivial(true, fhe_key),
+            _ => T::trivial(iv[i - 67], fhe_key),
+        });
+
+        // the key and iv registers are read from their last bit
+        key.reverse();
+        iv.reverse();
+        let mut ret = Self {
+            a: StaticDeque::<93, T>::new(a_register),
+            b: StaticDeque::<84, T>::new(b_register),
+            c: StaticDeque::<111, T>::new(c_register),
+            k: StaticDeque::<128, T>::new(key),
+            iv: StaticDeque::<128, bool>::new(iv),
+            fhe_key: fhe_key.clone(),
+        };
+        ret.init();
+        ret
+    }
+
+    /// The specification of Kreyvium includes running 1152 (= 18*64) unused steps to mix up the
+    /// registers, before starting the proper stream
+    fn init(&mut self) {
+        for _ in 0..18 {
+            self.next_64();
+        }
+    }
+
+    /// Computes one turn of the stream, updating registers and outputting the new bit.
+    pub fn next_bool(&mut self) -> T {
+
+        let [o, a, b, c] = self.get_output_and_values(0);
+
+        self.a.push(a);
+        self.b.push(b);
+        self.c.push(c);
+        self.k.shift();
+        self.iv.shift();
+
+        o
+    }
+
+    /// Computes a potential future step of Kreyvium, n terms in the future. This does not update
+    /// registers, but rather returns with the output, the three values that will be used to
+    /// update the registers, when the time is right. This function is meant to be used in
+    /// parallel.
+    fn get_output_and_values(&self, n: usize) -> [T; 4] {
+        assert!(n < 65);
+        let key = &self.fhe_key;
+        // the key and iv registers rotate, their oldest bit is the one of the current step
+        let (k, iv) = (&self.k[127 - n], self.iv[127 - n]);
+
+        let (((temp_a, temp_b), (temp_c, a_and)), (b_and, c_and)) = rayon::join(
+            || {
+                rayon::join(
+                    || {
+                        rayon::join(
+                            || self.a[65 - n].xor(&self.a[92 - n], key),
+                            || self.b[68 - n].xor(&self.b[83 - n], key),
+                        )
+                    },
+                    || {
+                        rayon::join(
+                            || Self::triple_xor(&self.c[65 - n], &self.c[110 - n], k, key),
+                            || self.a[91 - n].and(&self.a[90 - n], key),
+                        )
+                    },
+                )
+            },
+            || {
+                rayon::join(
+                    || self.b[82 - n].and(&self.b[81 - n], key),
+                    || self.c[109 - n].and(&self.c[108 - n], key),
+                )
+            },
+        );
+
+        let ((o, a), (b, c)) = rayon::join(
+            || {
+                rayon::join(
+                    || Self::triple_xor(&temp_a, &temp_b, &temp_c, key),
+                    || Self::triple_xor(&temp_c, &c_and, &self.a[68 - n], key),
+                )
+            },
+            || {
+                rayon::join(
+                    || Self::triple_xor(&temp_a, &a_and, &self.b[77 - n], key).xor_plain(iv, key),
+                    || Self::triple_xor(&temp_b, &b_and, &self.c[86 - n], key),
+                )
+            },
+        );
+
+        [o, a, b, c]
+    }
+
+    /// This calls `get_output_and_values` in parallel 64 times, and stores all results in a Vec.
+    fn get_64_output_and_values(&self) -> Vec<[T; 4]> {
+        (0..64)
+            .into_par_iter()
+            .map(|x| self.get_output_and_values(x))
+            .rev()
+            .collect()
+    }
+
+    /// Computes 64 turns of the stream, outputting the 64 bits all at once in a
+    /// Vec (first value is oldest, last is newest)
+    pub fn next_64(&mut self) -> Vec<T> {
+        let mut values = self.get_64_output_and_values();
+
+        let mut ret = Vec::<T>::with_capacity(64);
+
+        while let Some([o, a, b, c]) = values.pop() {
+            ret.push(o);
+            self.a.push(a);
+            self.b.push(b);
+            self.c.push(c);
+        }
+        self.k.n_shifts(64);
+        self.iv.n_shifts(64);
+        ret
+    }
+
+    /// Computes n turns of the stream, 64 at a time as long as possible
+    pub fn next_n(&mut self, n: usize) -> Vec<T> {
+        let mut ret = Vec::<T>::with_capacity(n);
+        while ret.len() + 64 <= n {
+            ret.extend(self.next_64());
+        }
+        while ret.len() < n {
+            ret.push(self.next_bool());
+        }
+        ret
+    }
+
+    fn triple_xor(a: &T, b: &T, c: &T, key: &T::Key) -> T {
+        let inter = a.xor(b, key);
+        c.xor(&inter, key)
+    }
+}
+
+impl<T: Bit> StreamCipher<T> for KreyviumStream<T> {
+    fn next_n(&mut self, n: usize) -> Vec<T> {
+        KreyviumStream::next_n(self, n)
+    }
+}
+
+// This function returns the Kreyvium keystream derived from initial key and iv
+pub fn get_plain_kreyvium_keystream_n(key: [bool; 128], iv: [bool; 128], size: usize) -> Vec<bool> {
+    KreyviumStream::<bool>::new(key, iv, &()).next_n(size)
+}
+
+// This function returns the homomorphic encryption of the Kreyvium keystream derived from initial
+// key and iv
+pub fn get_cipher_kreyvium_keystream_n(key: [Ciphertext; 128], iv: [bool; 128], size: usize, sk: &ServerKey) -> Vec<Ciphertext> {
+    KreyviumStream::<Ciphertext>::new(key, iv, sk).next_n(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic_functions::{symmetric_dec, symmetric_enc};
+
+    // Gets the bits (lsb first in each byte) of a hex string, like the keys of Protocol II
+    fn hex_to_bits<const N: usize>(hex: &str) -> [bool; N] {
+        let bytes = hex::decode(hex).unwrap();
+        std::array::from_fn(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+    }
+
+    fn bits_to_hex(bits: &[bool]) -> String {
+        bits.chunks(8)
+            .map(|byte| format!("{:02X}", byte.iter().rev().fold(0u8, |acc, &b| acc << 1 | b as u8)))
+            .collect()
+    }
+
+    #[test]
+    fn test_kreyvium_vectors() {
+        // test vectors of the tfhe-rs Kreyvium app
+        let keystream = get_plain_kreyvium_keystream_n([false; 128], [false; 128], 64);
+        assert_eq!(bits_to_hex(&keystream), "26DCF1F4BC0F1922");
+        let key = hex_to_bits::<128>("0053A6F94C9FF24598EB000000000000");
+        let iv = hex_to_bits::<128>("0D74DB42A91077DE45AC000000000000");
+        let keystream = get_plain_kreyvium_keystream_n(key, iv, 100);
+        assert_eq!(bits_to_hex(&keystream[..64]), "D1F0303482061111");
+
+        // the encrypted stream stays in sync, the trivial ciphertexts being evaluated in the clear
+        let (ck, sk) = gen_keys();
+        let key_ct = key.map(|b| sk.trivial_encrypt(b));
+        let fhe_keystream: Vec<bool> = get_cipher_kreyvium_keystream_n(key_ct, iv, 100, &sk)
+            .iter()
+            .map(|ct| ck.decrypt(ct))
+            .collect();
+        assert_eq!(fhe_keystream, keystream);
+
+        let data: Vec<bool> = (0..100).map(|i| i % 7 == 0).collect();
+        let ct = symmetric_enc(data.clone(), &mut KreyviumStream::<bool>::new(key, iv, &()));
+        assert_eq!(symmetric_dec(ct, &mut KreyviumStream::<bool>::new(key, iv, &())), data);
+    }
+}
@@ -0,0 +1,64 @@
+//! Decrypts boolean ciphertexts with plain integer arithmetic, given only the bits of the LWE
+//! secret key. Since the key is binary, the decryption of a ciphertext (a, b) is a subset sum
+//! b - sum(a_i for s_i = 1) modulo 2^32, which a smart contract can check without the TFHE
+//! library and without the whole bincode `ClientKey`.
+
+use tfhe::boolean::ciphertext::Ciphertext;
+use tfhe::boolean::client_key::ClientKey;
+use crate::error::{check_len, FdeError};
+
+/// Returns the bits of the LWE secret key used to encrypt with `ck`, packed into bytes
+/// (least significant bit first)
+pub fn lwe_key_bytes(ck: &ClientKey) -> Vec<u8> {
+    let (key, _) = ck.encryption_key_and_noise();
+    let mut bytes = vec![0u8; key.as_ref().len().div_ceil(8)];
+    for (i, &bit) in key.as_ref().iter().enumerate() {
+        bytes[i / 8] |= ((bit & 1) as u8) << (i % 8);
+    }
+    bytes
+}
+
+/// Decrypts one ciphertext with the packed LWE secret key `key`
+pub fn lwe_decrypt_bool(ct: &Ciphertext, key: &[u8]) -> Result<bool, FdeError> {
+    match ct {
+        Ciphertext::Trivial(b) => Ok(*b),
+        Ciphertext::Encrypted(lwe) => {
+            // the container holds the mask a followed by the body b
+            let (body, mask) = lwe.as_ref().split_last().ok_or(FdeError::BadLength { what: "LWE ciphertext", expected: 1, got: 0 })?;
+            check_len("LWE secret key (in bytes)", mask.len().div_ceil(8), key.len())?;
+            let phase = mask.iter().enumerate()
+                .filter(|(i, _)| key[i / 8] >> (i % 8) & 1 == 1)
+                .fold(*body, |phase, (_, a)| phase.wrapping_sub(*a));
+            // true is encoded around 2^29, false around -2^29
+            Ok(phase < (1 << 31))
+        }
+    }
+}
+
+/// Decrypts ciphertexts to booleans with the packed LWE secret key `key`
+pub fn lwe_decrypt_bools(ciphertext: &[Ciphertext], key: &[u8]) -> Result<Vec<bool>, FdeError> {
+    ciphertext.iter().map(|ct| lwe_decrypt_bool(ct, key)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::boolean::parameters::DEFAULT_PARAMETERS;
+    use crate::homomorphic_functions::{decrypt_bools, encrypt_bools};
+
+    #[test]
+    fn test_lwe_decrypt_bools() {
+        let ck = ClientKey::new(&DEFAULT_PARAMETERS);
+        let bools: Vec<bool> = (0..64).map(|i| i % 3 == 0).collect();
+        let ct = encrypt_bools(bools.clone(), &ck);
+        let key = lwe_key_bytes(&ck);
+        assert_eq!(lwe_decrypt_bools(&ct, &key).unwrap(), bools);
+        assert_eq!(decrypt_bools(&ct, &ck), bools);
+
+        // a key of the wrong size is rejected, another key decrypts to garbage
+        assert!(matches!(lwe_decrypt_bools(&ct, &key[1..]), Err(FdeError::BadLength { .. })));
+        let other = lwe_key_bytes(&ClientKey::new(&DEFAULT_PARAMETERS));
+        assert_ne!(lwe_decrypt_bools(&ct, &other).unwrap(), bools);
+        assert!(lwe_decrypt_bool(&Ciphertext::Trivial(true), &[]).unwrap());
+    }
+}
@@ -0,0 +1,73 @@
+//! This module contains the homomorphic hashing of a Merkle leaf and of its path to the root, so
+//! that a client can check an encrypted chunk against the root of the data without decrypting it.
+//! Every hash is one run of the sponge of `sha3_256_fhe` (or of Keccak-256) : the prefixes and the
+//! siblings of the proof are plaintext, and are given as trivial ciphertexts.
+
+use tfhe::boolean::prelude::{Ciphertext, ServerKey};
+use crate::error::FdeError;
+use crate::homomorphic_functions::HashFunction;
+use crate::merkle::{MerkleProof, LEAF_PREFIX, NODE_PREFIX};
+
+/// Hashes an encrypted chunk (whole bytes, lsb first in each byte) homomorphically:
+/// H(0x00 || chunk)
+pub fn merkle_leaf_fhe(chunk: &[Ciphertext], hash_function: HashFunction, sk: &ServerKey) -> Result<[Ciphertext; 256], FdeError> {
+    let mut input = trivial_bytes(&[LEAF_PREFIX], sk);
+    input.extend_from_slice(chunk);
+    hash(input, hash_function, sk)
+}
+
+/// Hashes the path of `proof` from the encrypted `leaf` homomorphically, each level being
+/// H(0x01 || left || right). Returns the encrypted root
+pub fn merkle_root_fhe(leaf: [Ciphertext; 256], proof: &MerkleProof, hash_function: HashFunction, sk: &ServerKey) -> Result<[Ciphertext; 256], FdeError> {
+    proof.siblings.iter().enumerate().try_fold(leaf, |node, (level, sibling)| {
+        let sibling = trivial_bytes(sibling, sk);
+        let mut input = trivial_bytes(&[NODE_PREFIX], sk);
+        if (proof.index >> level) & 1 == 0 {
+            input.extend(node);
+            input.extend(sibling);
+        } else {
+            input.extend(sibling);
+            input.extend(node);
+        }
+        hash(input, hash_function, sk)
+    })
+}
+
+// -------------------------- HELPER FUNCTIONS ---------------------------------------
+
+// Pads and hashes homomorphically
+fn hash(input: Vec<Ciphertext>, hash_function: HashFunction, sk: &ServerKey) -> Result<[Ciphertext; 256], FdeError> {
+    let sponge = hash_function.sponge();
+    let out = sponge.hash(&sponge.pad_cipher(input, sk)?, sk)?;
+    Ok(std::array::from_fn(|k| out[k].clone()))
+}
+
+// Returns the trivial encryption of the bits of `bytes`, lsb first in each byte
+fn trivial_bytes(bytes: &[u8], sk: &ServerKey) -> Vec<Ciphertext> {
+    bytes.iter().flat_map(|byte| (0..8).map(move |i| sk.trivial_encrypt((byte >> i) & 1 == 1))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::boolean::prelude::*;
+    use crate::homomorphic_functions::bools_to_hex;
+    use crate::merkle::MerkleTree;
+
+    #[test]
+    fn test_merkle_path_fhe() {
+        let (ck, sk) = gen_keys();
+        let data = b"four chunks of eight bytes each!";
+        let tree = MerkleTree::new(data, 8, HashFunction::Sha3_256);
+        let chunk = tree.chunk(data, 2).unwrap();
+
+        // the chunk is a trivial ciphertext, so the hashes are evaluated in the clear
+        let chunk_ct = trivial_bytes(chunk, &sk);
+        let leaf = merkle_leaf_fhe(&chunk_ct, HashFunction::Sha3_256, &sk).unwrap();
+        let root = merkle_root_fhe(leaf, &tree.proof(2).unwrap(), HashFunction::Sha3_256, &sk).unwrap();
+        let root: Vec<bool> = root.iter().map(|bit| ck.decrypt(bit)).collect();
+        assert_eq!(bools_to_hex(&root), tree.hex_root());
+
+        assert!(matches!(merkle_leaf_fhe(&chunk_ct[1..], HashFunction::Sha3_256, &sk), Err(FdeError::BadLength { .. })));
+    }
+}
@@ -1,14 +1,40 @@
 
 pub mod boolean_ops64;
 pub mod boolean_ops256;
+pub mod enc_uint;
+pub mod prime_field;
 pub mod new_trivium;
+pub mod kreyvium;
+pub mod filip;
+pub mod rasta;
+pub mod aes;
+pub mod symmetric_cipher;
 pub mod padding;
 pub mod sha3_256_function;
 pub mod encryption;
+pub mod lwe;
+pub mod keccak_sponge;
+pub mod merkle_path;
+pub mod gate_count;
+pub mod shortint_sha3;
+pub mod integer_sha3;
 
 pub use boolean_ops64::*;
 pub use boolean_ops256::*;
+pub use enc_uint::*;
+pub use prime_field::*;
 pub use new_trivium::*;
+pub use kreyvium::*;
+pub use filip::*;
+pub use rasta::*;
+pub use aes::*;
+pub use symmetric_cipher::*;
 pub use padding::*;
 pub use sha3_256_function::*;
-pub use encryption::*;
\ No newline at end of file
+pub use encryption::*;
+pub use lwe::*;
+pub use keccak_sponge::*;
+pub use merkle_path::*;
+pub use gate_count::*;
+pub use shortint_sha3::*;
+pub use integer_sha3::*;
\ No newline at end of file
@@ -1,204 +1,134 @@
-/// This module implements the Trivium stream cipher, using boolean or Ciphertext
-/// for the representation of the inner bits.
-/// This was taken from trivium in the zama library and adapted for the boolean API.
+//! This module implements the Trivium stream cipher, generic over the representation of the
+//! inner bits (bool, boolean Ciphertext or FheBool) through the `Bit` trait.
+//! This was taken from trivium in the zama library and adapted for the boolean API.
 
 use crate::static_deque::StaticDeque;
 use rayon::prelude::*;
 use tfhe::boolean::prelude::*;
-use crate::homomorphic_functions::xor_with_plain;
-
-/// TriviumStream: a struct implementing the Trivium stream cipher, using T for the internal
-/// representation of bits (bool or FheBool). To be able to compute FHE operations, it also owns
-/// an Option for a ServerKey.
-pub struct TriviumStream<T> {
-    a: StaticDeque<93, T>,
-    b: StaticDeque<84, T>,
-    c: StaticDeque<111, T>,
-    // only present for the encrypted version:
-    fhe_key: Option<ServerKey>,
+use tfhe::prelude::FheTrivialEncrypt;
+use tfhe::{with_server_key_as_context, FheBool};
+use crate::homomorphic_functions::StreamCipher;
+
+/// Bit: the operations Trivium needs on its inner bits. `Key` is whatever the operations need
+/// to be evaluated (nothing for bool, the server key for the encrypted bits).
+pub trait Bit: Clone + Send + Sync {
+    type Key: Clone + Send + Sync;
+
+    fn xor(&self, other: &Self, key: &Self::Key) -> Self;
+    fn and(&self, other: &Self, key: &Self::Key) -> Self;
+    /// XOR with a plaintext bit
+    fn xor_plain(&self, other: bool, key: &Self::Key) -> Self;
+    /// Returns the bit `value`, trivially encrypted for the encrypted bits
+    fn trivial(value: bool, key: &Self::Key) -> Self;
 }
 
-impl TriviumStream<bool> {
-    /// Constructor for `TriviumStream<bool>`: arguments are the secret key and the input vector.
-    /// Outputs a TriviumStream object already initialized (1152 steps have been run before
-    /// returning)
-    pub fn new(key: [bool; 80], iv: [bool; 80]) -> TriviumStream<bool> {
-        // Initialization of Trivium registers: a has the secret key, b the input vector,
-        // and c a few ones.
-        let mut a_register = [false; 93];
-        let mut b_register = [false; 84];
-        let mut c_register = [false; 111];
-
-        for i in 0..80 {
-            a_register[93 - 80 + i] = key[i];
-            b_register[84 - 80 + i] = iv[i];
-        }
-
-        c_register[0] = true;
-        c_register[1] = true;
-        c_register[2] = true;
+impl Bit for bool {
+    type Key = ();
 
-        TriviumStream::<bool>::new_from_registers(a_register, b_register, c_register, None)
+    fn xor(&self, other: &bool, _: &()) -> bool {
+        self ^ other
     }
 
-    // COPY OF GENERIC TRIVIUM, REPLACED T by bool
-    fn new_from_registers(
-        a_register: [bool; 93],
-        b_register: [bool; 84],
-        c_register: [bool; 111],
-        key: Option<ServerKey>,
-    ) -> Self {
-        let mut ret = Self {
-            a: StaticDeque::<93, bool>::new(a_register),
-            b: StaticDeque::<84, bool>::new(b_register),
-            c: StaticDeque::<111, bool>::new(c_register),
-            fhe_key: key,
-        };
-        ret.init();
-        ret
+    fn and(&self, other: &bool, _: &()) -> bool {
+        self & other
     }
 
-    /// The specification of Trivium includes running 1152 (= 18*64) unused steps to mix up the
-    /// registers, before starting the proper stream
-    fn init(&mut self) {
-        for _ in 0..18 {
-            self.next_64();
-        }
+    fn xor_plain(&self, other: bool, _: &()) -> bool {
+        self ^ other
     }
 
-    /// Computes one turn of the stream, updating registers and outputting the new bit.
-    pub fn next_bool(&mut self) -> bool {
-
-        let [o, a, b, c] = self.get_output_and_values(0);
-
-        self.a.push(a);
-        self.b.push(b);
-        self.c.push(c);
-
-        o
+    fn trivial(value: bool, _: &()) -> bool {
+        value
     }
+}
 
-    /// Computes a potential future step of Trivium, n terms in the future. This does not update
-    /// registers, but rather returns with the output, the three values that will be used to
-    /// update the registers, when the time is right. This function is meant to be used in
-    /// parallel.
-    fn get_output_and_values(&self, n: usize) -> [bool; 4] {
-        assert!(n < 65);
+impl Bit for Ciphertext {
+    type Key = ServerKey;
 
-        let (((temp_a, temp_b), (temp_c, a_and)), (b_and, c_and)) = rayon::join(
-            || {
-                rayon::join(
-                    || {
-                        rayon::join(
-                            || &self.a[65 - n] ^ &self.a[92 - n],
-                            || &self.b[68 - n] ^ &self.b[83 - n],
-                        )
-                    },
-                    || {
-                        rayon::join(
-                            || &self.c[65 - n] ^ &self.c[110 - n],
-                            || &self.a[91 - n] & &self.a[90 - n],
-                        )
-                    },
-                )
-            },
-            || {
-                rayon::join(
-                    || &self.b[82 - n] & &self.b[81 - n],
-                    || &self.c[109 - n] & &self.c[108 - n],
-                )
-            },
-        );
+    fn xor(&self, other: &Ciphertext, sk: &ServerKey) -> Ciphertext {
+        sk.xor(self, other)
+    }
 
-        let ((o, a), (b, c)) = rayon::join(
-            || {
-                rayon::join(
-                    || &(&temp_a ^ &temp_b) ^ &temp_c,
-                    || &temp_c ^ &(&c_and ^ &self.a[68 - n]),
-                )
-            },
-            || {
-                rayon::join(
-                    || &temp_a ^ &(&a_and ^ &self.b[77 - n]),
-                    || &temp_b ^ &(&b_and ^ &self.c[86 - n]),
-                )
-            },
-        );
+    fn and(&self, other: &Ciphertext, sk: &ServerKey) -> Ciphertext {
+        sk.and(self, other)
+    }
 
-        [o, a, b, c]
+    fn xor_plain(&self, other: bool, sk: &ServerKey) -> Ciphertext {
+        sk.xor(self, other)
     }
 
-    /// This calls `get_output_and_values` in parallel 64 times, and stores all results in a Vec.
-    fn get_64_output_and_values(&self) -> Vec<[bool; 4]> {
-        (0..64)
-            .into_par_iter()
-            .map(|x| self.get_output_and_values(x))
-            .rev()
-            .collect()
+    fn trivial(value: bool, sk: &ServerKey) -> Ciphertext {
+        sk.trivial_encrypt(value)
     }
+}
 
-    /// Computes 64 turns of the stream, outputting the 64 bits all at once in a
-    /// Vec (first value is oldest, last is newest)
-    pub fn next_64(&mut self) -> Vec<bool> {
+// The key of the integer API is set on the calling thread for each operation, as they run on the
+// threads of rayon
+impl Bit for FheBool {
+    type Key = tfhe::ServerKey;
 
-        let mut values = self.get_64_output_and_values();
+    fn xor(&self, other: &FheBool, sk: &tfhe::ServerKey) -> FheBool {
+        with_server_key_as_context(sk.clone(), || self ^ other)
+    }
 
+    fn and(&self, other: &FheBool, sk: &tfhe::ServerKey) -> FheBool {
+        with_server_key_as_context(sk.clone(), || self & other)
+    }
 
-        let mut ret = Vec::<bool>::with_capacity(64);
+    fn xor_plain(&self, other: bool, sk: &tfhe::ServerKey) -> FheBool {
+        with_server_key_as_context(sk.clone(), || self ^ other)
+    }
 
-        while let Some([o, a, b, c]) = values.pop() {
-            ret.push(o);
-            self.a.push(a);
-            self.b.push(b);
-            self.c.push(c);
-        }
-        ret
+    fn trivial(value: bool, sk: &tfhe::ServerKey) -> FheBool {
+        with_server_key_as_context(sk.clone(), || FheBool::encrypt_trivial(value))
     }
 }
 
-impl TriviumStream<Ciphertext> {
-    /// Constructor for `TriviumStream<Ciphertext>`: arguments are the encrypted secret key and input
-    /// vector, and the FHE server key.
+/// TriviumStream: a struct implementing the Trivium stream cipher, using T for the internal
+/// representation of bits (bool, Ciphertext or FheBool). To be able to compute FHE operations,
+/// it also owns the key of T (`()` for bool).
+pub struct TriviumStream<T: Bit> {
+    a: StaticDeque<93, T>,
+    b: StaticDeque<84, T>,
+    c: StaticDeque<111, T>,
+    fhe_key: T::Key,
+}
+
+impl<T: Bit> TriviumStream<T> {
+    /// Constructor for `TriviumStream<T>`: arguments are the (encrypted) secret key, the input
+    /// vector, and the key of T.
     /// Outputs a TriviumStream object already initialized (1152 steps have been run before
     /// returning)
-    pub fn new(key: [Ciphertext; 80], iv: [bool; 80], sk: &ServerKey) -> TriviumStream<Ciphertext> {
-
+    pub fn new(key: [T; 80], iv: [bool; 80], fhe_key: &T::Key) -> TriviumStream<T> {
         // Initialization of Trivium registers: a has the secret key, b the input vector,
         // and c a few ones.
-        let mut a_register: [Ciphertext; 93] = std::array::from_fn(|_| { sk.trivial_encrypt(false)});
-        let mut b_register: [Ciphertext; 84] = std::array::from_fn(|_| { sk.trivial_encrypt(false)});
-        let mut c_register: [Ciphertext; 111] = std::array::from_fn(|_| { sk.trivial_encrypt(false)});
+        let mut a_register: [T; 93] = std::array::from_fn(|_| T::trivial(false, fhe_key));
+        let mut b_register: [T; 84] = std::array::from_fn(|_| T::trivial(false, fhe_key));
+        let mut c_register: [T; 111] = std::array::from_fn(|_| T::trivial(false, fhe_key));
 
-        for i in 0..80 {
-            a_register[93 - 80 + i] = key[i].clone();
-            b_register[84 - 80 + i] = sk.trivial_encrypt(iv[i]);
+        for (i, bit) in key.into_iter().enumerate() {
+            a_register[93 - 80 + i] = bit;
+            b_register[84 - 80 + i] = T::trivial(iv[i], fhe_key);
         }
 
-        c_register[0] = sk.trivial_encrypt(true);
-        c_register[1] = sk.trivial_encrypt(true);
-        c_register[2] = sk.trivial_encrypt(true);
+        c_register[0] = T::trivial(true, fhe_key);
+        c_register[1] = T::trivial(true, fhe_key);
+        c_register[2] = T::trivial(true, fhe_key);
 
-        TriviumStream::<Ciphertext>::new_from_registers(
-            a_register,
-            b_register,
-            c_register,
-            Some(sk.clone()),
-        )
+        TriviumStream::<T>::new_from_registers(a_register, b_register, c_register, fhe_key.clone())
     }
 
-
-    // COPY FROM GENERIC TRIVIUM, REPLACED IT BY CIPHERTEXT
     fn new_from_registers(
-        a_register: [Ciphertext; 93],
-        b_register: [Ciphertext; 84],
-        c_register: [Ciphertext; 111],
-        key: Option<ServerKey>,
+        a_register: [T; 93],
+        b_register: [T; 84],
+        c_register: [T; 111],
+        fhe_key: T::Key,
     ) -> Self {
         let mut ret = Self {
-            a: StaticDeque::<93, Ciphertext>::new(a_register),
-            b: StaticDeque::<84, Ciphertext>::new(b_register),
-            c: StaticDeque::<111, Ciphertext>::new(c_register),
-            fhe_key: key,
+            a: StaticDeque::<93, T>::new(a_register),
+            b: StaticDeque::<84, T>::new(b_register),
+            c: StaticDeque::<111, T>::new(c_register),
+            fhe_key,
         };
         ret.init();
         ret
@@ -213,7 +143,7 @@ impl TriviumStream<Ciphertext> {
     }
 
     /// Computes one turn of the stream, updating registers and outputting the new bit.
-    pub fn next_bool(&mut self) -> Ciphertext {
+    pub fn next_bool(&mut self) -> T {
 
         let [o, a, b, c] = self.get_output_and_values(0);
 
@@ -228,32 +158,31 @@ impl TriviumStream<Ciphertext> {
     /// registers, but rather returns with the output, the three values that will be used to
     /// update the registers, when the time is right. This function is meant to be used in
     /// parallel.
-    fn get_output_and_values(&self, n: usize) -> [Ciphertext; 4] {
+    fn get_output_and_values(&self, n: usize) -> [T; 4] {
         assert!(n < 65);
-        let sk: ServerKey = self.fhe_key.clone().expect("TriviumStream<Ciphertext> must have an FHE key");
+        let key = &self.fhe_key;
 
         let (((temp_a, temp_b), (temp_c, a_and)), (b_and, c_and)) = rayon::join(
             || {
                 rayon::join(
                     || {
                         rayon::join(
-                            || sk.xor(&self.a[65 - n], &self.a[92 - n]),
-                            || sk.xor(&self.b[68 - n], &self.b[83 - n]),
-
+                            || self.a[65 - n].xor(&self.a[92 - n], key),
+                            || self.b[68 - n].xor(&self.b[83 - n], key),
                         )
                     },
                     || {
                         rayon::join(
-                            ||sk.xor(&self.c[65 - n], &self.c[110 - n]),
-                            ||sk.and(&self.a[91 - n], &self.a[90 - n]),
+                            || self.c[65 - n].xor(&self.c[110 - n], key),
+                            || self.a[91 - n].and(&self.a[90 - n], key),
                         )
                     },
                 )
             },
             || {
                 rayon::join(
-                    || sk.and(&self.b[82 - n], &self.b[81 - n]),
-                    || sk.and(&self.c[109 - n], &self.c[108 - n]),
+                    || self.b[82 - n].and(&self.b[81 - n], key),
+                    || self.c[109 - n].and(&self.c[108 - n], key),
                 )
             },
         );
@@ -261,15 +190,14 @@ impl TriviumStream<Ciphertext> {
         let ((o, a), (b, c)) = rayon::join(
             || {
                 rayon::join(
-
-                    ||Self::triple_xor(&temp_a, &temp_b, &temp_c, &sk),
-                    ||Self::triple_xor(&temp_c, &c_and, &self.a[68 - n], &sk),
+                    || Self::triple_xor(&temp_a, &temp_b, &temp_c, key),
+                    || Self::triple_xor(&temp_c, &c_and, &self.a[68 - n], key),
                 )
             },
             || {
                 rayon::join(
-                    ||Self::triple_xor(&temp_a, &a_and, &self.b[77 - n], &sk),
-                    ||Self::triple_xor(&temp_b, &b_and, &self.c[86 - n], &sk),
+                    || Self::triple_xor(&temp_a, &a_and, &self.b[77 - n], key),
+                    || Self::triple_xor(&temp_b, &b_and, &self.c[86 - n], key),
                 )
             },
         );
@@ -278,7 +206,7 @@ impl TriviumStream<Ciphertext> {
     }
 
     /// This calls `get_output_and_values` in parallel 64 times, and stores all results in a Vec.
-    fn get_64_output_and_values(&self) -> Vec<[Ciphertext; 4]> {
+    fn get_64_output_and_values(&self) -> Vec<[T; 4]> {
         (0..64)
             .into_par_iter()
             .map(|x| self.get_output_and_values(x))
@@ -288,10 +216,10 @@ impl TriviumStream<Ciphertext> {
 
     /// Computes 64 turns of the stream, outputting the 64 bits all at once in a
     /// Vec (first value is oldest, last is newest)
-    pub fn next_64(&mut self) -> Vec<Ciphertext> {
+    pub fn next_64(&mut self) -> Vec<T> {
         let mut values = self.get_64_output_and_values();
 
-        let mut ret = Vec::<Ciphertext>::with_capacity(64);
+        let mut ret = Vec::<T>::with_capacity(64);
 
         while let Some([o, a, b, c]) = values.pop() {
             ret.push(o);
@@ -302,68 +230,72 @@ impl TriviumStream<Ciphertext> {
         ret
     }
 
-    fn triple_xor (a : &Ciphertext, b: &Ciphertext, c: &Ciphertext, sk : &ServerKey) -> Ciphertext {
-        let inter = sk.xor(a, b);
-        sk.xor(c, &inter)
+    /// Computes n turns of the stream, 64 at a time as long as possible
+    pub fn next_n(&mut self, n: usize) -> Vec<T> {
+        let mut ret = Vec::<T>::with_capacity(n);
+        while ret.len() + 64 <= n {
+            ret.extend(self.next_64());
+        }
+        while ret.len() < n {
+            ret.push(self.next_bool());
+        }
+        ret
+    }
+
+    fn triple_xor(a: &T, b: &T, c: &T, key: &T::Key) -> T {
+        let inter = a.xor(b, key);
+        c.xor(&inter, key)
+    }
+}
+
+impl<T: Bit> StreamCipher<T> for TriviumStream<T> {
+    fn next_n(&mut self, n: usize) -> Vec<T> {
+        TriviumStream::next_n(self, n)
     }
 }
 
 // This function returns the symmetric keystream derived from initial key and iv
 pub fn get_plain_keystream_n (key : [bool; 80], iv : [bool; 80], size : usize) -> Vec<bool>{
-    let mut clear_trivium = TriviumStream::<bool>::new(key, iv);
-    let mut keystream: Vec<bool> = Vec::with_capacity(size);
-    while keystream.len() + 64 <= size {
-        let cipher_outputs = clear_trivium.next_64();
-        for c in cipher_outputs {
-            keystream.push(c)
-        }
-    }
-    while keystream.len()  < size {
-        let c = clear_trivium.next_bool();
-        keystream.push(c)
-    }
-    keystream
+    let mut clear_trivium = TriviumStream::<bool>::new(key, iv, &());
+    clear_trivium.next_n(size)
 }
 
 // This function returns the homomorphic encryption of the symmetric keystream derived from initial
 // key and iv
 pub fn get_cipher_keystream_n (key : [Ciphertext; 80], iv : [bool; 80], size : usize, sk: &ServerKey) -> Vec<Ciphertext>{
     let mut fhe_trivium =
-        TriviumStream::<Ciphertext>::new(key, iv.clone(), sk);
-    let mut fhe_keystream: Vec<Ciphertext> = Vec::with_capacity(size);
-    while fhe_keystream.len() + 64 <= size {
-        let cipher_outputs = fhe_trivium.next_64();
-        for c in cipher_outputs {
-            fhe_keystream.push(c)
-        }
-    }
-    while fhe_keystream.len()  < size {
-        let c = fhe_trivium.next_bool();
-        fhe_keystream.push(c)
-    }
-    fhe_keystream
+        TriviumStream::<Ciphertext>::new(key, iv, sk);
+    fhe_trivium.next_n(size)
 }
 
-// Performs the trivium symmetric encryption
-pub fn symmetric_enc(input : Vec<bool>, key : [bool; 80], iv : [bool; 80] ) -> Vec<bool> {
-    let keystream = get_plain_keystream_n(key, iv, input.len());
-    let sym_end_data = keystream.iter()
-        .zip(input.iter())
-        .map(|(&bit_a, &bit_b)| bit_a ^ bit_b)
-        .collect();
-    sym_end_data
-}
-
-// Performs the trivium symmetric decryption
-pub fn symmetric_dec(input : Vec<bool>, key : [bool; 80], iv : [bool; 80] ) -> Vec<bool> {
-   // same as encryption as it is just xoring
-    symmetric_enc(input, key, iv)
-}
-
-
-// Performs the trivium symmetric decryption
-pub fn homomoprhic_symmetric_dec(input : Vec<bool>, key : [Ciphertext; 80], iv : [bool; 80], sk : &ServerKey) -> Vec<Ciphertext> {
-    let fhe_keystream = get_cipher_keystream_n(key, iv, input.len(), sk);
-    xor_with_plain(&fhe_keystream, &input, &sk)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::prelude::FheDecrypt;
+    use crate::homomorphic_functions::gen_integer_keys;
+
+    #[test]
+    fn test_trivium_backends_in_sync() {
+        // trivial ciphertexts are evaluated in the clear, so the encrypted streams run quickly
+        let key: [bool; 80] = std::array::from_fn(|i| i % 3 == 1);
+        let iv: [bool; 80] = std::array::from_fn(|i| i % 5 < 2);
+        let plain = get_plain_keystream_n(key, iv, 100);
+
+        let (boolean_ck, boolean_sk) = gen_keys();
+        let key_ct = key.map(|b| boolean_sk.trivial_encrypt(b));
+        let boolean: Vec<bool> = get_cipher_keystream_n(key_ct, iv, 100, &boolean_sk)
+            .iter()
+            .map(|ct| boolean_ck.decrypt(ct))
+            .collect();
+        assert_eq!(boolean, plain);
+
+        let (integer_ck, integer_sk) = gen_integer_keys();
+        let key_fhe = key.map(|b| FheBool::trivial(b, &integer_sk));
+        let integer: Vec<bool> = TriviumStream::<FheBool>::new(key_fhe, iv, &integer_sk)
+            .next_n(100)
+            .iter()
+            .map(|ct| ct.decrypt(&integer_ck))
+            .collect();
+        assert_eq!(integer, plain);
+    }
 }
-
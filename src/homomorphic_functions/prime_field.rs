@@ -0,0 +1,362 @@
+//! This module contains the challenge of Protocol II over a prime field. Modulo 2^256, a difference
+//! of hashes H - h = 2^t x u (u odd) is annihilated by every coefficient which is a multiple of
+//! 2^(256 - t), so a wrong hash goes unnoticed with probability 2^(t - 256) instead of 2^(-256).
+//! Modulo a prime p, b x (H - h) is zero only if b is, unless H and h differ by a multiple of p
+//! (a hash equal to one of the few values h + k x p below 2^256, which is as hard as a preimage).
+//! The hashes are multiplied by the plaintext coefficients without any reduction along the way:
+//! b x H = sum of H[i] x (b x 2^i mod p), so each encrypted bit of H selects a plaintext residue
+//! (for free), all the residues are added up, and the sum is reduced once at the end (once every
+//! three hashes for a batch of files). The 256-bit values are big-endian bit strings, and the wider
+//! intermediate sums are `EncUint`s.
+
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use rand::Rng;
+use tfhe::boolean::prelude::{Ciphertext, ServerKey};
+use tfhe::integer::U512;
+use crate::error::{check_len, FdeError};
+use crate::homomorphic_functions::boolean_ops256::fold_challenge;
+use crate::homomorphic_functions::{add_all, EncUint, PlainUint};
+
+/// Number of hashes whose residues are added up before the sum is reduced
+const PAIRS_PER_SUM: usize = 3;
+/// Number of bits of a sum of up to 2^10 residues (each below p < 2^256), e.g. the 256 x
+/// PAIRS_PER_SUM residues of the hashes, the plaintext part and the reduced previous sum
+const SUM_BITS: usize = 266;
+/// Number of bits of the sum once its bits above 256 are folded back modulo p
+const FOLDED_BITS: usize = 260;
+
+/// The arithmetic of the challenge of Protocol II
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChallengeField {
+    /// The integers modulo 2^256 (`compute_challenge`)
+    #[default]
+    Mod2_256,
+    /// The scalar field of the BN254 curve, of 254 bits
+    Bn254,
+    /// The scalar field of the secp256k1 curve, of 256 bits
+    Secp256k1,
+}
+
+impl ChallengeField {
+    /// All the supported arithmetics
+    pub const ALL: [ChallengeField; 3] = [ChallengeField::Mod2_256, ChallengeField::Bn254, ChallengeField::Secp256k1];
+
+    /// Returns the modulus of the arithmetic (2^256 for `Mod2_256`)
+    pub fn modulus(&self) -> U512 {
+        match self {
+            ChallengeField::Mod2_256 => U512::ONE << 256u32,
+            ChallengeField::Bn254 => from_hex("30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001"),
+            ChallengeField::Secp256k1 => from_hex("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"),
+        }
+    }
+
+    /// Checks that `x` is a canonical element : a 256-bit string whose value is below the modulus.
+    /// The smart contract only accepts a canonical â, so that it has a single encoding
+    pub fn contains(&self, x: &[bool]) -> bool {
+        x.len() == 256 && to_u512(x.try_into().unwrap()) < self.modulus()
+    }
+
+    /// Returns random (a, b, c) below the modulus, b and c being nonzero
+    pub fn rand_abc(&self) -> ([bool; 256], [bool; 256], [bool; 256]) {
+        let (a, coefs) = self.rand_coefficients(2);
+        (a, coefs[0], coefs[1])
+    }
+
+    /// Returns a random a and `n` random nonzero coefficients, all below the modulus
+    pub fn rand_coefficients(&self, n: usize) -> ([bool; 256], Vec<[bool; 256]>) {
+        let modulus = self.modulus();
+        let sample = |nonzero: bool| loop {
+            let mut bytes = [0u8; 32];
+            rand::thread_rng().fill(&mut bytes[..]);
+            let x = from_be_bytes(&bytes);
+            if x < modulus && (!nonzero || x != U512::ZERO) {
+                return to_bits(x);
+            }
+        };
+        (sample(false), (0..n).map(|_| sample(true)).collect())
+    }
+
+    /// Computes the chal homomorphically:
+    /// a + b x (comp_hash1 - exp_hash1) + c x (comp_hash2 - exp_hash2) modulo the modulus
+    #[allow(clippy::too_many_arguments)]
+    pub fn compute_challenge(
+        &self,
+        comp_hash1: &[Ciphertext; 256],
+        comp_hash2: &[Ciphertext; 256],
+        exp_hash1: &[bool; 256],
+        exp_hash2: &[bool; 256],
+        a: &[bool; 256],
+        b: &[bool; 256],
+        c: &[bool; 256],
+        sk: &ServerKey,
+    ) -> [Ciphertext; 256] {
+        self.fold_challenge(&[(comp_hash1, exp_hash1), (comp_hash2, exp_hash2)], a, &[*b, *c], sk)
+    }
+
+    /// Computes the chal homomorphically for any number of (computed hash, expected hash) pairs:
+    /// a + coefs[0] x (comp_hash0 - exp_hash0) + coefs[1] x (comp_hash1 - exp_hash1) + ... modulo
+    /// the modulus. Returns an error if there is not one coefficient per pair
+    pub fn compute_challenge_n(
+        &self,
+        pairs: &[(&[Ciphertext; 256], &[bool; 256])],
+        a: &[bool; 256],
+        coefs: &[[bool; 256]],
+        sk: &ServerKey,
+    ) -> Result<[Ciphertext; 256], FdeError> {
+        check_len("coefficients", pairs.len(), coefs.len())?;
+        Ok(self.fold_challenge(pairs, a, coefs, sk))
+    }
+
+    // Computes the chal of `compute_challenge_n`, with one coefficient per pair
+    fn fold_challenge(
+        &self,
+        pairs: &[(&[Ciphertext; 256], &[bool; 256])],
+        a: &[bool; 256],
+        coefs: &[[bool; 256]],
+        sk: &ServerKey,
+    ) -> [Ciphertext; 256] {
+        if *self == ChallengeField::Mod2_256 {
+            return fold_challenge(pairs, a, coefs, sk);
+        }
+        let p = self.modulus();
+        let coefs: Vec<U512> = coefs.iter().map(|coef| to_u512(coef) % p).collect();
+
+        // the plaintext part : a - coefs[0] x exp_hash0 - coefs[1] x exp_hash1 - ...
+        let plain_part = pairs.iter()
+            .zip(&coefs)
+            .fold(to_u512(a) % p, |acc, ((_, exp), &coef)| sub_mod(acc, mul_mod(coef, to_u512(exp), p), p));
+
+        // each bit of the hashes selects the residue of its weight times the coefficient. The
+        // residues of PAIRS_PER_SUM hashes are added up to the reduced sum of the previous ones
+        let mut sum = EncUint::trivial(&to_plain(plain_part), sk);
+        let pairs: Vec<_> = pairs.iter().zip(coefs).collect();
+        for chunk in pairs.chunks(PAIRS_PER_SUM) {
+            let mut terms = vec![sum];
+            for ((hash, _), coef) in chunk {
+                let mut residue = *coef;
+                for i in 0..256 {
+                    terms.push(EncUint::bit_times(&hash[255 - i], &to_plain(residue), sk));
+                    residue = (residue << 1u32) % p;
+                }
+            }
+            sum = reduce(&add_all(terms, sk), p, sk).resize::<SUM_BITS>(sk);
+        }
+        sum.resize::<256>(sk).0
+    }
+
+    /// Computes the chal in plaintext, with the same arithmetic as `compute_challenge`
+    #[allow(clippy::too_many_arguments)]
+    pub fn plain_challenge(
+        &self,
+        comp_hash1: &[bool; 256],
+        comp_hash2: &[bool; 256],
+        exp_hash1: &[bool; 256],
+        exp_hash2: &[bool; 256],
+        a: &[bool; 256],
+        b: &[bool; 256],
+        c: &[bool; 256],
+    ) -> [bool; 256] {
+        self.fold_plain_challenge(&[(comp_hash1, exp_hash1), (comp_hash2, exp_hash2)], a, &[*b, *c])
+    }
+
+    /// Computes the chal in plaintext, with the same arithmetic as `compute_challenge_n`. Returns
+    /// an error if there is not one coefficient per pair
+    pub fn plain_challenge_n(&self, pairs: &[(&[bool; 256], &[bool; 256])], a: &[bool; 256], coefs: &[[bool; 256]]) -> Result<[bool; 256], FdeError> {
+        check_len("coefficients", pairs.len(), coefs.len())?;
+        Ok(self.fold_plain_challenge(pairs, a, coefs))
+    }
+
+    // Computes the chal of `plain_challenge_n`, with one coefficient per pair
+    fn fold_plain_challenge(&self, pairs: &[(&[bool; 256], &[bool; 256])], a: &[bool; 256], coefs: &[[bool; 256]]) -> [bool; 256] {
+        let m = self.modulus();
+        let diff = |comp: &[bool; 256], exp: &[bool; 256]| sub_mod(to_u512(comp) % m, to_u512(exp) % m, m);
+        let chal = pairs.iter()
+            .zip(coefs)
+            .fold(to_u512(a) % m, |acc, ((comp, exp), coef)| add_mod(acc, mul_mod(to_u512(coef) % m, diff(comp, exp), m), m));
+        to_bits(chal)
+    }
+}
+
+impl fmt::Display for ChallengeField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChallengeField::Mod2_256 => write!(f, "mod2_256"),
+            ChallengeField::Bn254 => write!(f, "bn254"),
+            ChallengeField::Secp256k1 => write!(f, "secp256k1"),
+        }
+    }
+}
+
+impl FromStr for ChallengeField {
+    type Err = FdeError;
+
+    fn from_str(s: &str) -> Result<Self, FdeError> {
+        match s {
+            "mod2_256" => Ok(ChallengeField::Mod2_256),
+            "bn254" => Ok(ChallengeField::Bn254),
+            "secp256k1" => Ok(ChallengeField::Secp256k1),
+            _ => Err(FdeError::Deserialize(format!("unknown challenge field {} (expected mod2_256, bn254 or secp256k1)", s))),
+        }
+    }
+}
+
+//  ------------------------------ CIPHERTEXT-CIPHERTEXT OPERATIONS --------------------------------
+
+// Subtracts m from x if x >= m : the subtraction does not borrow exactly when x >= m, and its flag
+// selects the difference
+fn conditional_sub<const N: usize>(x: &EncUint<N>, m: U512, sk: &ServerKey) -> EncUint<N> {
+    let (diff, ge) = x.checked_sub_plain(&to_plain(m), sk);
+    EncUint::select(&ge, &diff, x, sk)
+}
+
+// Reduces the sum of the residues modulo p : the bits above 256 are folded back as residues of
+// their weight, and the result is brought below p by subtracting 2^k x p, ..., 2p, p when possible
+fn reduce(sum: &EncUint<SUM_BITS>, p: U512, sk: &ServerKey) -> EncUint<FOLDED_BITS> {
+    let mut terms = vec![sum.resize::<256>(sk).resize::<FOLDED_BITS>(sk)];
+    for j in 0..SUM_BITS - 256 {
+        terms.push(EncUint::bit_times(sum.bit(256 + j), &to_plain((U512::ONE << (256 + j)) % p), sk));
+    }
+    let mut folded = add_all(terms, sk);
+
+    // the folded sum is below 2^256 + (SUM_BITS - 256) x p, which is at most 2^(k+1) x p
+    let bound = (U512::ONE << 256u32) + p * U512::from((SUM_BITS - 256) as u64);
+    let k = (0u32..).find(|&k| p << (k + 1) >= bound).unwrap();
+    for shift in (0..=k).rev() {
+        folded = conditional_sub(&folded, p << shift, sk);
+    }
+    folded
+}
+
+// ------------------------------- PLAINTEXT-PLAINTEXT OPERATIONS ----------------------------------
+
+fn add_mod(x: U512, y: U512, m: U512) -> U512 {
+    (x + y) % m
+}
+
+fn sub_mod(x: U512, y: U512, m: U512) -> U512 {
+    (x + m - y) % m
+}
+
+// The operands are below 2^256, so their product fits in 512 bits
+fn mul_mod(x: U512, y: U512, m: U512) -> U512 {
+    (x * y) % m
+}
+
+// --------------------------------------- UTILS ---------------------------------------------------
+
+trait Bits {
+    fn bit(&self, i: usize) -> bool;
+}
+
+impl Bits for U512 {
+    fn bit(&self, i: usize) -> bool {
+        (*self >> i) & U512::ONE == U512::ONE
+    }
+}
+
+fn from_be_bytes(bytes: &[u8]) -> U512 {
+    let mut padded = [0u8; 64];
+    padded[64 - bytes.len()..].copy_from_slice(bytes);
+    let mut x = U512::ZERO;
+    x.copy_from_be_byte_slice(&padded);
+    x
+}
+
+fn from_hex(hex: &str) -> U512 {
+    from_be_bytes(&hex::decode(hex).unwrap())
+}
+
+// Reads a big-endian bit string
+fn to_u512(bits: &[bool; 256]) -> U512 {
+    let bytes: Vec<u8> = bits.chunks(8).map(|byte| byte.iter().fold(0u8, |acc, &b| acc << 1 | b as u8)).collect();
+    from_be_bytes(&bytes)
+}
+
+// Returns the N low bits
+fn to_plain<const N: usize>(x: U512) -> PlainUint<N> {
+    let mut bytes = [0u8; 64];
+    x.copy_to_be_byte_slice(&mut bytes);
+    PlainUint::from_be_bytes(&bytes)
+}
+
+// Writes the 256 low bits as a big-endian bit string
+fn to_bits(x: U512) -> [bool; 256] {
+    std::array::from_fn(|i| x.bit(255 - i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::boolean::prelude::*;
+
+    fn trivial(bits: &[bool; 256], sk: &ServerKey) -> [Ciphertext; 256] {
+        std::array::from_fn(|i| sk.trivial_encrypt(bits[i]))
+    }
+
+    #[test]
+    fn test_prime_field_challenge() {
+        let (ck, sk) = gen_keys();
+        for field in ChallengeField::ALL {
+            assert_eq!(field.to_string().parse::<ChallengeField>().unwrap(), field);
+            let (a, b, c) = field.rand_abc();
+            assert!(field.contains(&a) && field.contains(&b));
+            let (h1, h2) = (ChallengeField::Mod2_256.rand_abc().0, [true; 256]);
+            let (e1, e2) = (ChallengeField::Mod2_256.rand_abc().0, h2);
+
+            // with the expected hashes, the challenge is a ; otherwise it matches the plaintext
+            // arithmetic, the trivial ciphertexts being evaluated in the clear
+            assert_eq!(field.plain_challenge(&h1, &h2, &h1, &h2, &a, &b, &c), a);
+            for exp1 in [h1, e1] {
+                let chal = field.compute_challenge(&trivial(&h1, &sk), &trivial(&h2, &sk), &exp1, &e2, &a, &b, &c, &sk);
+                let chal: [bool; 256] = std::array::from_fn(|i| ck.decrypt(&chal[i]));
+                assert_eq!(chal, field.plain_challenge(&h1, &h2, &exp1, &e2, &a, &b, &c));
+                assert!(field.contains(&chal));
+            }
+        }
+
+        // a difference of 2^255 is annihilated by an even b modulo 2^256, but not modulo p
+        let (zero, mut top) = ([false; 256], [false; 256]);
+        top[0] = true;
+        let mut two = [false; 256];
+        two[254] = true;
+        assert_eq!(ChallengeField::Mod2_256.plain_challenge(&top, &zero, &zero, &zero, &zero, &two, &two), zero);
+        assert_ne!(ChallengeField::Bn254.plain_challenge(&top, &zero, &zero, &zero, &zero, &two, &two), zero);
+        assert!(!ChallengeField::Secp256k1.contains(&[true; 256]));
+        assert!(ChallengeField::Mod2_256.contains(&[true; 256]));
+        assert!(matches!("bls12_381".parse::<ChallengeField>(), Err(FdeError::Deserialize(_))));
+    }
+
+    #[test]
+    fn test_batch_challenge() {
+        let (ck, sk) = gen_keys();
+        let hashes: Vec<[bool; 256]> = (0..4).map(|_| ChallengeField::Mod2_256.rand_abc().0).collect();
+        let trivials: Vec<[Ciphertext; 256]> = hashes.iter().map(|h| trivial(h, &sk)).collect();
+        let mut expected = hashes.clone();
+        for field in ChallengeField::ALL {
+            // the 4 hashes take two sums in a prime field
+            let (a, coefs) = field.rand_coefficients(4);
+            assert!(coefs.iter().all(|coef| field.contains(coef) && *coef != [false; 256]));
+            for wrong in [false, true] {
+                expected[3][7] ^= wrong;
+                let pairs: Vec<_> = trivials.iter().zip(&expected).collect();
+                let chal = field.compute_challenge_n(&pairs, &a, &coefs, &sk).unwrap();
+                let chal: [bool; 256] = std::array::from_fn(|i| ck.decrypt(&chal[i]));
+                let plain_pairs: Vec<_> = hashes.iter().zip(&expected).collect();
+                assert_eq!(chal, field.plain_challenge_n(&plain_pairs, &a, &coefs).unwrap());
+                assert_eq!(chal == a, !wrong);
+                expected[3][7] ^= wrong;
+            }
+
+            // one coefficient is missing
+            let pairs: Vec<_> = trivials.iter().zip(&expected).collect();
+            assert!(matches!(field.compute_challenge_n(&pairs, &a, &coefs[1..], &sk),
+                Err(FdeError::BadLength { what: "coefficients", expected: 4, got: 3 })));
+            let plain_pairs: Vec<_> = hashes.iter().zip(&expected).collect();
+            assert!(matches!(field.plain_challenge_n(&plain_pairs, &a, &coefs[1..]),
+                Err(FdeError::BadLength { what: "coefficients", expected: 4, got: 3 })));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! This module implements the Rasta stream cipher (Dobraunig et al., CRYPTO 2018), generic over the
+//! representation of the bits like `TriviumStream`. Each block of `n` bits of keystream is the
+//! key permuted by `r` rounds of an affine layer and a χ layer, followed by a last affine layer
+//! and a feed-forward of the key. The affine layers are random invertible matrices and
+//! constants, drawn by a public PRNG seeded by the iv and the index of the block, so the
+//! multiplicative depth of the keystream is only `r`.
+
+use rayon::prelude::*;
+use crate::homomorphic_functions::{Bit, PublicPrng, StreamCipher};
+
+/// The parameters of Rasta
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RastaParams {
+    /// Number of bits of the key, of the state and of a block of keystream (n, which is odd)
+    pub block_len: usize,
+    /// Number of rounds (r)
+    pub rounds: usize,
+}
+
+impl RastaParams {
+    /// The parameters of Rasta with 128-bit security and 6 rounds
+    pub fn rasta_351() -> Self {
+        RastaParams { block_len: 351, rounds: 6 }
+    }
+}
+
+/// An affine layer : the rows of an invertible matrix over GF(2), as words of 64 bits, and the
+/// constant added after the product
+struct AffineLayer {
+    rows: Vec<Vec<u64>>,
+    constant: Vec<bool>,
+}
+
+impl AffineLayer {
+    /// Draws a random invertible matrix and a random constant from `prng`
+    fn random(n: usize, prng: &mut PublicPrng) -> AffineLayer {
+        let words = n.div_ceil(64);
+        let last_mask = if n.is_multiple_of(64) { u64::MAX } else { (1u64 << (n % 64)) - 1 };
+        loop {
+            let rows: Vec<Vec<u64>> = (0..n)
+                .map(|_| {
+                    let mut row: Vec<u64> = (0..words).map(|_| prng.word()).collect();
+                    row[words - 1] &= last_mask;
+                    row
+                })
+                .collect();
+            if is_invertible(&rows, n) {
+                let constant = (0..n).map(|_| prng.bit()).collect();
+                return AffineLayer { rows, constant };
+            }
+        }
+    }
+
+    /// Computes the affine layer on `state`, each row being the XOR of the bits it selects
+    fn apply<T: Bit>(&self, state: &[T], key: &T::Key) -> Vec<T> {
+        self.rows.par_iter()
+            .zip(self.constant.par_iter())
+            .map(|(row, &constant)| {
+                let sum = (0..state.len())
+                    .filter(|&j| (row[j / 64] >> (j % 64)) & 1 == 1)
+                    .fold(None, |acc: Option<T>, j| match acc {
+                        None => Some(state[j].clone()),
+                        Some(acc) => Some(acc.xor(&state[j], key)),
+                    });
+                // an invertible matrix has no zero row
+                sum.expect("the matrix is invertible").xor_plain(constant, key)
+            })
+            .collect()
+    }
+}
+
+/// RastaStream: a struct implementing Rasta, using T for the internal representation of bits
+/// (bool, Ciphertext or FheBool), with the key of T to compute the FHE operations.
+pub struct RastaStream<T: Bit> {
+    params: RastaParams,
+    key: Vec<T>,
+    iv: Vec<bool>,
+    counter: u64,
+    // the bits of the last block which were not output yet
+    buffer: Vec<T>,
+    fhe_key: T::Key,
+}
+
+impl<T: Bit> RastaStream<T> {
+    /// Constructor for `RastaStream<T>`: arguments are the (encrypted) `params.block_len`-bit
+    /// secret key, the iv (nonce) seeding the public PRNG, and the key of T
+    pub fn new(key: Vec<T>, iv: &[bool], params: RastaParams, fhe_key: &T::Key) -> RastaStream<T> {
+        assert_eq!(key.len(), params.block_len, "length mismatch");
+        assert!(!params.block_len.is_multiple_of(2), "the χ layer is only invertible on an odd number of bits");
+        RastaStream { params, key, iv: iv.to_vec(), counter: 0, buffer: vec![], fhe_key: fhe_key.clone() }
+    }
+
+    /// Computes the next block of keystream
+    fn next_block(&mut self) -> Vec<T> {
+        let n = self.params.block_len;
+        let key = &self.fhe_key;
+        let mut seed = self.iv.clone();
+        seed.extend((0..64).map(|i| (self.counter >> i) & 1 == 1));
+        let mut prng = PublicPrng::new(b"Rasta", &seed);
+        self.counter += 1;
+
+        let mut state = self.key.clone();
+        for _ in 0..self.params.rounds {
+            state = AffineLayer::random(n, &mut prng).apply(&state, key);
+            state = chi(&state, key);
+        }
+        state = AffineLayer::random(n, &mut prng).apply(&state, key);
+        state.par_iter_mut()
+            .zip(self.key.par_iter())
+            .for_each(|(bit, key_bit)| *bit = bit.xor(key_bit, key));
+        state
+    }
+}
+
+impl<T: Bit> StreamCipher<T> for RastaStream<T> {
+    fn next_n(&mut self, n: usize) -> Vec<T> {
+        while self.buffer.len() < n {
+            let block = self.next_block();
+            self.buffer.extend(block);
+        }
+        let rest = self.buffer.split_off(n);
+        std::mem::replace(&mut self.buffer, rest)
+    }
+}
+
+// -------------------------- HELPER FUNCTIONS ---------------------------------------
+
+// The χ layer : x[i] ^ (!x[i+1] & x[i+2]), the negation being a XOR with a plaintext one
+fn chi<T: Bit>(state: &[T], key: &T::Key) -> Vec<T> {
+    let n = state.len();
+    (0..n).into_par_iter()
+        .map(|i| state[i].xor(&state[(i + 1) % n].xor_plain(true, key).and(&state[(i + 2) % n], key), key))
+        .collect()
+}
+
+// Checks with a Gaussian elimination that the n x n matrix is invertible over GF(2)
+fn is_invertible(rows: &[Vec<u64>], n: usize) -> bool {
+    let mut rows = rows.to_vec();
+    for col in 0..n {
+        let (word, bit) = (col / 64, col % 64);
+        let Some(pivot) = (col..n).find(|&i| (rows[i][word] >> bit) & 1 == 1) else {
+            return false;
+        };
+        rows.swap(col, pivot);
+        let pivot_row = rows[col].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i != col && (row[word] >> bit) & 1 == 1 {
+                row.iter_mut().zip(&pivot_row).for_each(|(a, b)| *a ^= b);
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tfhe::boolean::prelude::*;
+    use crate::homomorphic_functions::sha3_256_function::bits_to_bytes_lsb;
+
+    #[test]
+    fn test_rasta_stream() {
+        assert!(is_invertible(&[vec![0b01], vec![0b11]], 2));
+        assert!(!is_invertible(&[vec![0b11], vec![0b11]], 2));
+
+        let params = RastaParams { block_len: 21, rounds: 2 };
+        let key: Vec<bool> = (0..21).map(|i| i % 3 == 0).collect();
+        let iv = [true; 128];
+
+        // the stream does not depend on how it is split, and the blocks differ
+        let plain = RastaStream::<bool>::new(key.clone(), &iv, params, &()).next_n(50);
+        let mut stream = RastaStream::<bool>::new(key.clone(), &iv, params, &());
+        let mut pieces = stream.next_n(5);
+        pieces.extend(stream.next_n(30));
+        pieces.extend(stream.next_n(15));
+        assert_eq!(pieces, plain);
+        assert_ne!(plain[..21], plain[21..42]);
+
+        // the encrypted stream stays in sync, the trivial ciphertexts being evaluated in the clear
+        let (ck, sk) = gen_keys();
+        let key_ct: Vec<Ciphertext> = key.iter().map(|&b| sk.trivial_encrypt(b)).collect();
+        let encrypted: Vec<bool> = RastaStream::new(key_ct, &iv, params, &sk)
+            .next_n(50)
+            .iter()
+            .map(|ct| ck.decrypt(ct))
+            .collect();
+        assert_eq!(encrypted, plain);
+    }
+    #[test]
+    fn test_rasta_351_keystream() {
+        // pins the first 64 bits of keystream, which depend on the public PRNG drawing the
+        // matrices and the round constants
+        let key: Vec<bool> = (0..351).map(|i| i % 3 == 0).collect();
+        let iv: Vec<bool> = (0..128).map(|i| i % 5 == 0).collect();
+        let keystream = RastaStream::<bool>::new(key, &iv, RastaParams::rasta_351(), &()).next_n(64);
+        assert_eq!(hex::encode(bits_to_bytes_lsb(&keystream)), "dc17d0ac35a3afe7");
+    }
+}
@@ -1,14 +1,17 @@
-/// Homomorphic SHA3-256 implementation using TFHE-rs Boolean API
-///
-/// This file also provides `sha3_fhe` which takes a fixed-size block of 1088 encrypted bits
-/// and returns 256 encrypted bits representing the SHA3-256 digest.
+//! Homomorphic SHA3-256 implementation using TFHE-rs Boolean API
+//!
+//! This file also provides `sha3_256_fhe` which takes padded blocks of 1088 encrypted bits
+//! and returns 256 encrypted bits representing the SHA3-256 digest, using `KeccakSponge`.
+
 use tfhe::boolean::prelude::*;
-use sha3::{Digest, Sha3_256};
+use sha3::{Digest, Keccak256, Sha3_256};
 
-use crate::homomorphic_functions::{rotate_right, xor_64, and_64, xor_with_plain_64};
+use tfhe::FheUint64;
+use crate::error::FdeError;
+use crate::homomorphic_functions::{rotate_right, xor_64, andny_64, xor_with_plain_64, KeccakSponge, LaneGates, Sha3FheHasher};
 
 /// Round constants for Keccak-f[1600]
-const N_ROUNDS : usize = 24; // number of rounds nᵣ = 12 + 2ℓ, hence 24 for Keccak-f[1600] [Keccak §1.2]
+pub(crate) const N_ROUNDS : usize = 24; // number of rounds nᵣ = 12 + 2ℓ, hence 24 for Keccak-f[1600] [Keccak §1.2]
 
 /**
  * Round constants: output of a maximum-length linear feedback shift register (LFSR) for the
@@ -18,7 +21,7 @@ const N_ROUNDS : usize = 24; // number of rounds nᵣ = 12 + 2ℓ, hence 24 for
  * where
  *   rc[t] = ( xᵗ mod x⁸ + x⁶ + x⁵ + x⁴ + 1 ) mod x in GF(2)[x].
  */
-const RC: [u64; 24] = [
+pub(crate) const RC: [u64; 24] = [
     0x0000000000000001, 0x0000000000008082, 0x800000000000808a,
     0x8000000080008000, 0x000000000000808b, 0x0000000080000001,
     0x8000000080008081, 0x8000000000008009, 0x000000000000008a,
@@ -42,88 +45,58 @@ pub fn hex_sha3(data : &[u8]) -> String {
     let mut hasher = Sha3_256::new();
     hasher.update(data);
     let result = hasher.finalize();
-    let hex   = hex::encode(result);
-    hex
+    hex::encode(result)
+}
+
+/// The plaintext implementation of Keccak-256, as computed by the `keccak256` of the EVM
+pub fn hex_keccak256(data : &[u8]) -> String {
+    let mut hasher = Keccak256::new();
+    hasher.update(data);
+    let result = hasher.finalize();
+    hex::encode(result)
 }
 
 /// Used to get the hash of data in the form of Vec<bool>
 pub fn sha3_hash_from_vec_bool(data: Vec<bool>) -> String {
-    // Get bytes from Vec<bool>
-    let bytes : Vec<u8> = data.chunks(8)
-        .map(|chunk| {
-            chunk.iter().enumerate().fold(0u8, |acc, (i, &bit)| {
-                if bit {
-                    acc | (1 << i)
-                } else {
-                    acc
-                }
-            })
-        })
-        .collect();
-    // then hash normally
-    hex_sha3(&bytes)
+    // Get bytes from Vec<bool>, then hash normally
+    hex_sha3(&bits_to_bytes_lsb(&data))
+}
+
+/// Used to get the Keccak-256 hash of data in the form of Vec<bool>
+pub fn keccak256_hash_from_vec_bool(data: Vec<bool>) -> String {
+    hex_keccak256(&bits_to_bytes_lsb(&data))
 }
 
 /// Homomorphic SHA3-256, returns 256 Ciphertext bits
-/// Expects a padded ciphertext
+/// Expects a padded ciphertext, returns an error if the input is not a whole number of blocks
 pub fn sha3_256_fhe(
     input: Vec<Ciphertext>,
     sk: &ServerKey,
-) -> [Ciphertext; 256] {
-    // Prepare trivial ciphertexts
-    let zero = sk.trivial_encrypt(false);
-    let one = sk.trivial_encrypt(true);
-    let one_lane: [Ciphertext; 64] = std::array::from_fn(|_| one.clone());
-    let zero_uint64 =  std::array::from_fn(|_| zero.clone());
-    let five_zero_uint64 = std::array::from_fn(|_| zero_uint64.clone());
-
-    // Gather input
-    let bits_ct = input.clone();
-
-    // Allocate fixed buffers
-    let mut state: [[[Ciphertext; 64]; 5]; 5] = std::array::from_fn(|_| five_zero_uint64.clone());
-    let mut c_buf: [[Ciphertext; 64]; 5] = five_zero_uint64.clone();
-    let mut d_buf: [[Ciphertext; 64]; 5] = five_zero_uint64.clone();
-
-    // Process each 1088-bit block
-    for (_, block) in bits_ct.chunks(1088).enumerate() {
-        // Absorb
-        for (j, ct) in block.chunks(64).enumerate() {
-            let new_cipher_u64: [Ciphertext; 64] = std::array::from_fn(|i| {ct[i].clone()});
-            let x = j % 5;
-            let y = j / 5;
-            state[x][y] = xor_64(&state[x][y], &new_cipher_u64, &sk);
-        }
-
-        // Perform the keccak permutation
-        keccak_f1600_boolean(
-            &mut state,
-            sk,
-            &one_lane,
-            &mut c_buf,
-            &mut d_buf,
-        );
-    }
+) -> Result<[Ciphertext; 256], FdeError> {
+    let mut hasher = Sha3FheHasher::sha3_256(sk);
+    hasher.update(&input, sk);
+    let out = hasher.finalize(sk)?;
+    Ok(std::array::from_fn(|k| out[k].clone()))
+}
 
-    // Squeeze first 256 bits
-    let out: [Ciphertext; 256] = std::array::from_fn(|k| {
-        // compute which lane (x,y) and which bit z within that lane
-        let x = (k / 64) % 5;
-        let y = (k / 64) / 5;
-        let z = k % 64;
-        // clone out that bit-ciphertext
-        state[x][y][z].clone()
-    });
-    out
+/// Homomorphic SHA3-256 on 64-bit lanes of the integer API, returns the 4 lanes of the hash
+/// Expects a padded ciphertext, returns an error if the input is not a whole number of blocks
+pub fn sha3_256_fhe_integer(
+    input: Vec<FheUint64>,
+    sk: &tfhe::ServerKey,
+) -> Result<[FheUint64; 4], FdeError> {
+    let out = KeccakSponge::sha3_256().hash_integer(&input, sk)?;
+    Ok(std::array::from_fn(|k| out[k].clone()))
 }
 
 // -------------------------- HELPER FUNCTIONS ---------------------------------------
 
-// This function does the keccak f1600 permutation for sha3-256
-fn keccak_f1600_boolean(
+// This function does the keccak f1600 permutation, shared by all the sponges. The gates are
+// evaluated by a `ServerKey`, or counted by `keccak_f1600_counted_gates`
+#[allow(clippy::needless_range_loop)]
+pub(crate) fn keccak_f1600_boolean<G: LaneGates + ?Sized>(
     state: &mut [[[Ciphertext; 64]; 5]; 5],
-    sk: &ServerKey,
-    one_lane: &[Ciphertext; 64],
+    sk: &G,
     c_buf: &mut [[Ciphertext; 64]; 5],
     d_buf: &mut [[Ciphertext; 64]; 5],
 ) {
@@ -172,32 +145,26 @@ fn keccak_f1600_boolean(
                 let cx   = &col[x];
                 let cx1  = &col[(x + 1) % 5];
                 let cx2  = &col[(x + 2) % 5];
-                // homomorphic NOT = XOR with all-ones
-                let not_cx1 = xor_64(cx1, &one_lane, sk);
-                // and-part: (~C[x+1]) & C[x+2]
-                let and_part = and_64(&not_cx1, cx2, sk);
+                // and-part: (~C[x+1]) & C[x+2], the NOT is free
+                let and_part = andny_64(cx1, cx2, sk);
                 // final: C[x] ^ and_part
                 state[x][y] = xor_64(cx, &and_part, sk);
             }
         }
 
-        // ι phase
+        // ι phase, XOR with a plaintext constant : a free negation of the bits set in RC[r]
         let rc_r_bits = u64_to_bits_lsb(RC[r]);
-        state[0][0] = xor_with_plain_64(&state[0][0] , &rc_r_bits, &sk);
+        state[0][0] = xor_with_plain_64(&state[0][0] , &rc_r_bits, sk);
     }
 }
 
 // transforms a u64 into an array of 64 bool
 fn u64_to_bits_lsb(x: u64) -> [bool; 64] {
-    let mut bits = [false; 64];
-    for i in 0..64 {
-        bits[i] = ((x >> i) & 1) != 0;
-    }
-    bits
+    std::array::from_fn(|i| ((x >> i) & 1) != 0)
 }
 
 // transforms bits to bytes
-fn bits_to_bytes_lsb(bits: &[bool]) -> Vec<u8> {
+pub(crate) fn bits_to_bytes_lsb(bits: &[bool]) -> Vec<u8> {
     bits.chunks(8)
         .map(|chunk| {
             chunk.iter()
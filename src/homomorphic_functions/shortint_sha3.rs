@@ -0,0 +1,281 @@
+//! Homomorphic Keccak sponge on the `shortint` backend of TFHE. With the boolean API every XOR
+//! of θ is bootstrapped, although XOR is linear. Here, a bit of the state is a shortint
+//! ciphertext whose value is a sum of bits : the bit is the parity of the value. θ is then
+//! computed with leveled additions (the carry space holds the sums), ρ and π only move
+//! ciphertexts, and the only bootstraps are at χ, where each bit is brought back to its parity
+//! and a lookup table computes a[x] ^ (!a[x+1] & a[x+2]) ^ RC (ι is folded in the table).
+//!
+//! It needs its own keys (`gen_shortint_keys`), the data being encrypted bit by bit with
+//! `encrypt_bits_shortint`, so the protocols, which transcipher under boolean keys, do not use
+//! it : it is benchmarked with `fde bench --sha3-backend shortint`.
+
+use rayon::prelude::*;
+use tfhe::shortint::parameters::v1_2::V1_2_PARAM_MESSAGE_1_CARRY_3_KS_PBS_GAUSSIAN_2M128;
+use tfhe::shortint::parameters::ClassicPBSParameters;
+use tfhe::shortint::server_key::LookupTableOwned;
+use tfhe::shortint::{Ciphertext, ClientKey, ServerKey};
+use crate::error::FdeError;
+use crate::homomorphic_functions::sha3_256_function::{N_ROUNDS, RC};
+use crate::homomorphic_functions::KeccakSponge;
+
+/// Parameters of the shortint backend : one message bit and three carry bits, so that a sum of
+/// up to 15 bits fits in a ciphertext before it has to be bootstrapped
+pub const SHORTINT_SHA3_PARAMETERS: ClassicPBSParameters = V1_2_PARAM_MESSAGE_1_CARRY_3_KS_PBS_GAUSSIAN_2M128;
+
+// A lane of the state, 64 ciphertexts
+type Lane = Vec<Ciphertext>;
+
+/// Generates the keys of the shortint backend
+pub fn gen_shortint_keys() -> (ClientKey, ServerKey) {
+    tfhe::shortint::gen_keys(SHORTINT_SHA3_PARAMETERS)
+}
+
+/// Encrypts bits with the shortint backend, one ciphertext per bit
+pub fn encrypt_bits_shortint(bits: &[bool], ck: &ClientKey) -> Vec<Ciphertext> {
+    bits.par_iter().map(|&b| ck.encrypt(b as u64)).collect()
+}
+
+/// Decrypts ciphertexts of the shortint backend to bits (the parity of their value)
+pub fn decrypt_bits_shortint(ct: &[Ciphertext], ck: &ClientKey) -> Vec<bool> {
+    ct.par_iter().map(|c| ck.decrypt_message_and_carry(c) % 2 == 1).collect()
+}
+
+/// The lookup tables of the permutation
+struct KeccakLuts {
+    // x -> x mod 2, to reset the degree of a sum of bits
+    parity: LookupTableOwned,
+    // 4a + 2b + c -> a ^ (!b & c), and its negation when the round constant bit is set
+    chi: LookupTableOwned,
+    chi_not: LookupTableOwned,
+}
+
+impl KeccakLuts {
+    fn new(sk: &ServerKey) -> Self {
+        let chi = |v: u64| ((v >> 2) ^ (!(v >> 1) & v)) & 1;
+        KeccakLuts {
+            parity: sk.generate_lookup_table(|v| v & 1),
+            chi: sk.generate_lookup_table(chi),
+            chi_not: sk.generate_lookup_table(move |v| chi(v) ^ 1),
+        }
+    }
+}
+
+/// Incremental homomorphic hashing with the shortint backend, the counterpart of
+/// `Sha3FheHasher`
+pub struct ShortintSha3Hasher {
+    sponge: KeccakSponge,
+    state: [[Lane; 5]; 5],
+    // the bits received since the last absorbed block
+    pending: Vec<Ciphertext>,
+    absorbed: usize,
+    luts: KeccakLuts,
+}
+
+impl ShortintSha3Hasher {
+    pub fn new(sponge: KeccakSponge, sk: &ServerKey) -> Self {
+        let zero = sk.create_trivial(0);
+        ShortintSha3Hasher {
+            sponge,
+            state: std::array::from_fn(|_| std::array::from_fn(|_| vec![zero.clone(); 64])),
+            pending: Vec::with_capacity(sponge.rate()),
+            absorbed: 0,
+            luts: KeccakLuts::new(sk),
+        }
+    }
+
+    /// Returns the number of `rate`-bit blocks absorbed so far
+    pub fn absorbed_blocks(&self) -> usize {
+        self.absorbed
+    }
+
+    /// Feeds encrypted bits to the sponge : every complete `rate`-bit block is absorbed right
+    /// away, and the remaining bits are kept until the next call
+    pub fn update(&mut self, mut bits: &[Ciphertext], sk: &ServerKey) {
+        let rate = self.sponge.rate();
+        if !self.pending.is_empty() {
+            let missing = (rate - self.pending.len()).min(bits.len());
+            self.pending.extend_from_slice(&bits[..missing]);
+            bits = &bits[missing..];
+            if self.pending.len() < rate {
+                return;
+            }
+            let block = std::mem::take(&mut self.pending);
+            self.absorb_block(&block, sk);
+        }
+        let mut blocks = bits.chunks_exact(rate);
+        for block in blocks.by_ref() {
+            self.absorb_block(block, sk);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    // Adds one `rate`-bit block to the state, and performs the keccak permutation
+    fn absorb_block(&mut self, block: &[Ciphertext], sk: &ServerKey) {
+        for (j, lane) in block.chunks(64).enumerate() {
+            let parity = &self.luts.parity;
+            self.state[j % 5][j / 5].par_iter_mut().zip(lane.par_iter()).for_each(|(dst, bit)| {
+                sk.unchecked_add_assign(dst, bit);
+                // θ expects bits of degree at most 1 (a bit added to the all-zero state is one)
+                if dst.degree.get() > 1 {
+                    sk.apply_lookup_table_assign(dst, parity);
+                }
+            });
+        }
+        keccak_f1600_shortint(&mut self.state, sk, &self.luts);
+        self.absorbed += 1;
+    }
+
+    /// Squeezes the `output_len` encrypted bits of the hash. The input must have been padded, so
+    /// it fails if no block was absorbed or if a partial block is left
+    pub fn finalize(mut self, sk: &ServerKey) -> Result<Vec<Ciphertext>, FdeError> {
+        let rate = self.sponge.rate();
+        if self.absorbed == 0 || !self.pending.is_empty() {
+            let got = self.absorbed * rate + self.pending.len();
+            return Err(FdeError::BadLength { what: "padded input (in bits)", expected: got.div_ceil(rate).max(1) * rate, got });
+        }
+
+        // Squeeze `rate` bits at a time, permuting between two blocks of output
+        let output_len = self.sponge.output_len();
+        let mut out = Vec::with_capacity(output_len);
+        while out.len() < output_len {
+            if !out.is_empty() {
+                keccak_f1600_shortint(&mut self.state, sk, &self.luts);
+            }
+            let squeezed = rate.min(output_len - out.len());
+            out.extend((0..squeezed).map(|k| self.state[(k / 64) % 5][(k / 64) / 5][k % 64].clone()));
+        }
+        Ok(out)
+    }
+}
+
+impl KeccakSponge {
+    /// Returns an incremental hasher with this sponge on the shortint backend
+    pub fn shortint_hasher(&self, sk: &ServerKey) -> ShortintSha3Hasher {
+        ShortintSha3Hasher::new(*self, sk)
+    }
+
+    /// Hashes padded data encrypted with `encrypt_bits_shortint`, and returns `output_len`
+    /// encrypted bits
+    pub fn hash_shortint(&self, input: &[Ciphertext], sk: &ServerKey) -> Result<Vec<Ciphertext>, FdeError> {
+        let mut hasher = self.shortint_hasher(sk);
+        hasher.update(input, sk);
+        hasher.finalize(sk)
+    }
+}
+
+// -------------------------- HELPER FUNCTIONS ---------------------------------------
+
+// Rotates a lane like `rotate_right` does for the boolean backend
+fn rotate_lane(lane: &Lane, n: usize) -> Lane {
+    let mut result = lane.clone();
+    result.rotate_right(n);
+    result
+}
+
+// Adds two lanes without bootstrapping
+fn add_lanes(a: &Lane, b: &Lane, sk: &ServerKey) -> Lane {
+    a.iter().zip(b.iter()).map(|(a, b)| sk.unchecked_add(a, b)).collect()
+}
+
+// This function does the keccak f1600 permutation on the shortint backend. The bits of the state
+// must have a degree of at most 1 : θ then sums at most 11 of them, which fits in the carry
+// space, and χ brings every bit back to a degree of 1
+#[allow(clippy::needless_range_loop)]
+fn keccak_f1600_shortint(state: &mut [[Lane; 5]; 5], sk: &ServerKey, luts: &KeccakLuts) {
+    for r in 0..N_ROUNDS {
+        // θ phase, leveled
+        let c: Vec<Lane> = (0..5)
+            .map(|x| (1..5).fold(state[x][0].clone(), |acc, y| add_lanes(&acc, &state[x][y], sk)))
+            .collect();
+        for x in 0..5 {
+            let d = add_lanes(&c[(x + 4) % 5], &rotate_lane(&c[(x + 1) % 5], 1), sk);
+            for y in 0..5 {
+                state[x][y] = add_lanes(&state[x][y], &d, sk);
+            }
+        }
+
+        // ρ + π phase
+        let mut x = 1;
+        let mut y = 0;
+        let mut current = state[x][y].clone();
+        for t in 0..24 {
+            let new_x = y;
+            let new_y = (2*x + 3*y) % 5;
+            let tmp = state[new_x][new_y].clone();
+            state[new_x][new_y] = rotate_lane(&current, ((t+1)*(t+2)/2) % 64);
+            current = tmp;
+            x = new_x;
+            y = new_y;
+        }
+
+        // χ phase : bring each bit back to its parity, then one lookup table per bit
+        state.par_iter_mut().for_each(|column| {
+            column.par_iter_mut().for_each(|lane| {
+                lane.par_iter_mut().for_each(|ct| sk.apply_lookup_table_assign(ct, &luts.parity));
+            });
+        });
+        for y in 0..5 {
+            let plane: Vec<Lane> = (0..5).map(|x| state[x][y].clone()).collect();
+            for (x, lane) in state.iter_mut().enumerate() {
+                lane[y] = (0..64).into_par_iter().map(|z| {
+                    let a = sk.unchecked_scalar_mul(&plane[x][z], 4);
+                    let b = sk.unchecked_scalar_mul(&plane[(x + 1) % 5][z], 2);
+                    let packed = sk.unchecked_add(&sk.unchecked_add(&a, &b), &plane[(x + 2) % 5][z]);
+                    // ι phase, folded in the lookup table of the first lane
+                    let lut = if x == 0 && y == 0 && (RC[r] >> z) & 1 == 1 { &luts.chi_not } else { &luts.chi };
+                    sk.apply_lookup_table(&packed, lut)
+                }).collect();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic_functions::HashFunction;
+
+    #[test]
+    fn test_shortint_sponge() {
+        // trivial ciphertexts are evaluated in the clear, so the whole sponge runs quickly
+        let (ck, sk) = gen_shortint_keys();
+        let hash = |hash_function: HashFunction, data: &[u8], piece: usize| -> String {
+            let sponge = hash_function.sponge();
+            let input: Vec<Ciphertext> = sponge.pad_bytes(data).iter().map(|&b| sk.create_trivial(b as u64)).collect();
+            let mut hasher = sponge.shortint_hasher(&sk);
+            for chunk in input.chunks(piece) {
+                hasher.update(chunk, &sk);
+            }
+            let bits = decrypt_bits_shortint(&hasher.finalize(&sk).unwrap(), &ck);
+            crate::homomorphic_functions::bools_to_hex(&bits)
+        };
+
+        let data = b"fair data exchange";
+        assert_eq!(hash(HashFunction::Sha3_256, data, 1088), HashFunction::Sha3_256.hex_digest(data));
+        assert_eq!(hash(HashFunction::Keccak256, data, 1088), HashFunction::Keccak256.hex_digest(data));
+        // several blocks, the rate bits are reduced to their parity after the first one
+        let long_data = vec![7u8; 300];
+        assert_eq!(hash(HashFunction::Sha3_256, &long_data, 700), HashFunction::Sha3_256.hex_digest(&long_data));
+
+        let sponge = KeccakSponge::sha3_256();
+        assert!(matches!(sponge.hash_shortint(&[], &sk), Err(FdeError::BadLength { .. })));
+        let bits = [true, false, true];
+        assert_eq!(decrypt_bits_shortint(&encrypt_bits_shortint(&bits, &ck), &ck), bits);
+
+        // the noise and degree budget of a round on real ciphertexts : θ sums 11 bits, which are
+        // reduced to their parity before the lookup table of χ
+        let luts = KeccakLuts::new(&sk);
+        let bits: Vec<bool> = (0..11).map(|i| i % 3 == 0).collect();
+        let ct = encrypt_bits_shortint(&bits, &ck);
+        let mut sum = ct[0].clone();
+        for c in &ct[1..] {
+            sk.unchecked_add_assign(&mut sum, c);
+        }
+        let parity = sk.apply_lookup_table(&sum, &luts.parity);
+        assert_eq!(decrypt_bits_shortint(std::slice::from_ref(&parity), &ck), [false]);
+        let packed = sk.unchecked_add(&sk.unchecked_add(&sk.unchecked_scalar_mul(&parity, 4), &sk.unchecked_scalar_mul(&ct[1], 2)), &ct[0]);
+        let chi = [sk.apply_lookup_table(&packed, &luts.chi), sk.apply_lookup_table(&packed, &luts.chi_not)];
+        // 0 ^ (!0 & 1)
+        assert_eq!(decrypt_bits_shortint(&chi, &ck), [true, false]);
+    }
+}
@@ -0,0 +1,231 @@
+//! The stream ciphers encrypting the data symmetrically in Protocol II. Each cipher implements
+//! `StreamCipher` for plaintext and encrypted bits, on which the symmetric encryption and the
+//! homomorphic decryption are generic. The server proposes one in its first message, and the
+//! client only goes on if it accepts it, so both sides build the stream from a `SymmetricCipher`
+//! value. FiLIP and Rasta also implement `StreamCipher`, but they cannot be negotiated : with
+//! the boolean API every XOR of their large linear layers is bootstrapped, so they are much
+//! slower than Trivium and are only kept as library experiments.
+
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Shake128, Shake128Reader};
+use tfhe::boolean::prelude::*;
+use crate::error::{check_len, FdeError};
+use crate::homomorphic_functions::sha3_256_function::bits_to_bytes_lsb;
+use crate::homomorphic_functions::{
+    xor_with_plain, AesCtrStream, Bit, KreyviumStream, TriviumStream,
+};
+
+/// A stream cipher generating its keystream as bits of type T (plaintext or encrypted)
+pub trait StreamCipher<T: Bit> {
+    /// Computes the next n bits of the keystream
+    fn next_n(&mut self, n: usize) -> Vec<T>;
+}
+
+/// The stream cipher of the symmetric encryption of the data
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SymmetricCipher {
+    /// Trivium, with an 80-bit key and iv
+    #[default]
+    Trivium,
+    /// Kreyvium, with a 128-bit key and iv
+    Kreyvium,
+    /// AES-128 in counter mode, with a 128-bit key and a 128-bit iv (the first counter block)
+    Aes128Ctr,
+}
+
+impl SymmetricCipher {
+    /// All the supported ciphers
+    pub const ALL: [SymmetricCipher; 3] = [
+        SymmetricCipher::Trivium,
+        SymmetricCipher::Kreyvium,
+        SymmetricCipher::Aes128Ctr,
+    ];
+
+    /// Returns the length of the key in bits
+    pub fn key_len(&self) -> usize {
+        match self {
+            SymmetricCipher::Trivium => 80,
+            SymmetricCipher::Kreyvium | SymmetricCipher::Aes128Ctr => 128,
+        }
+    }
+
+    /// Returns the length of the iv in bits
+    pub fn iv_len(&self) -> usize {
+        match self {
+            SymmetricCipher::Trivium => 80,
+            _ => 128,
+        }
+    }
+
+    /// Returns the stream of the cipher with the (encrypted) key and the iv, along with the key
+    /// of T
+    pub fn stream<T: Bit + 'static>(&self, key: Vec<T>, iv: &[bool], fhe_key: &T::Key) -> Result<Box<dyn StreamCipher<T>>, FdeError> {
+        check_len("k", self.key_len(), key.len())?;
+        check_len("iv", self.iv_len(), iv.len())?;
+        Ok(match self {
+            SymmetricCipher::Trivium => {
+                Box::new(TriviumStream::new(to_array(key), to_array(iv.to_vec()), fhe_key))
+            }
+            SymmetricCipher::Kreyvium => {
+                Box::new(KreyviumStream::new(to_array(key), to_array(iv.to_vec()), fhe_key))
+            }
+            SymmetricCipher::Aes128Ctr => {
+                Box::new(AesCtrStream::new(to_array(key), to_array(iv.to_vec()), fhe_key))
+            }
+        })
+    }
+
+    /// Encrypts `input` symmetrically, which also decrypts it as the keystream is just xored
+    pub fn symmetric_enc(&self, input: Vec<bool>, key: &[bool], iv: &[bool]) -> Result<Vec<bool>, FdeError> {
+        Ok(symmetric_enc(input, self.stream(key.to_vec(), iv, &())?.as_mut()))
+    }
+
+    /// Decrypts `input` homomorphically with the encrypted key, handing each decrypted block of
+    /// `block_len` bits to `f` as soon as its keystream is computed
+    pub fn homomorphic_dec_blocks<F>(&self, input: &[bool], key_ct: Vec<Ciphertext>, iv: &[bool], block_len: usize, sk: &ServerKey, f: F) -> Result<(), FdeError>
+    where
+        F: FnMut(Vec<Ciphertext>),
+    {
+        let mut stream = self.stream(key_ct, iv, sk).map_err(|e| match e {
+            FdeError::BadLength { what: "k", expected, got } => FdeError::BadLength { what: "k_ct", expected, got },
+            e => e,
+        })?;
+        homomorphic_symmetric_dec_blocks(input, stream.as_mut(), block_len, sk, f);
+        Ok(())
+    }
+}
+
+impl fmt::Display for SymmetricCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymmetricCipher::Trivium => write!(f, "trivium"),
+            SymmetricCipher::Kreyvium => write!(f, "kreyvium"),
+            SymmetricCipher::Aes128Ctr => write!(f, "aes128_ctr"),
+        }
+    }
+}
+
+impl FromStr for SymmetricCipher {
+    type Err = FdeError;
+
+    fn from_str(s: &str) -> Result<Self, FdeError> {
+        match s {
+            "trivium" => Ok(SymmetricCipher::Trivium),
+            "kreyvium" => Ok(SymmetricCipher::Kreyvium),
+            "aes128_ctr" => Ok(SymmetricCipher::Aes128Ctr),
+            _ => Err(FdeError::Deserialize(format!(
+                "unknown cipher {} (expected trivium, kreyvium or aes128_ctr)", s
+            ))),
+        }
+    }
+}
+
+// Performs the symmetric encryption with the keystream of `stream`
+pub fn symmetric_enc<S: StreamCipher<bool> + ?Sized>(input: Vec<bool>, stream: &mut S) -> Vec<bool> {
+    let keystream = stream.next_n(input.len());
+    keystream.iter()
+        .zip(input.iter())
+        .map(|(&bit_a, &bit_b)| bit_a ^ bit_b)
+        .collect()
+}
+
+// Performs the symmetric decryption
+pub fn symmetric_dec<S: StreamCipher<bool> + ?Sized>(input: Vec<bool>, stream: &mut S) -> Vec<bool> {
+   // same as encryption as it is just xoring
+    symmetric_enc(input, stream)
+}
+
+// Performs the symmetric decryption homomorphically, with the encrypted keystream of `stream`
+pub fn homomoprhic_symmetric_dec<S: StreamCipher<Ciphertext> + ?Sized>(input: Vec<bool>, stream: &mut S, sk: &ServerKey) -> Vec<Ciphertext> {
+    let fhe_keystream = stream.next_n(input.len());
+    xor_with_plain(&fhe_keystream, &input, sk)
+}
+
+// Performs the symmetric decryption homomorphically block by block : each decrypted block of
+// `block_len` bits is handed to `f` as soon as its keystream is computed, so that the whole
+// plaintext is never held in memory
+pub fn homomorphic_symmetric_dec_blocks<S, F>(input: &[bool], stream: &mut S, block_len: usize, sk: &ServerKey, mut f: F)
+where
+    S: StreamCipher<Ciphertext> + ?Sized,
+    F: FnMut(Vec<Ciphertext>),
+{
+    for block in input.chunks(block_len) {
+        let fhe_keystream = stream.next_n(block.len());
+        f(xor_with_plain(&fhe_keystream, block, sk));
+    }
+}
+
+/// The public PRNG of FiLIP and Rasta : SHAKE128 of a domain separator and of a seed of bits
+pub struct PublicPrng {
+    reader: Shake128Reader,
+}
+
+impl PublicPrng {
+    pub fn new(domain: &[u8], seed: &[bool]) -> Self {
+        let mut shake = Shake128::default();
+        shake.update(domain);
+        shake.update(&bits_to_bytes_lsb(seed));
+        PublicPrng { reader: shake.finalize_xof() }
+    }
+
+    /// Returns 64 random bits
+    pub fn word(&mut self) -> u64 {
+        let mut buf = [0u8; 8];
+        self.reader.read(&mut buf);
+        u64::from_le_bytes(buf)
+    }
+
+    /// Returns a random bit
+    pub fn bit(&mut self) -> bool {
+        let mut buf = [0u8; 1];
+        self.reader.read(&mut buf);
+        buf[0] & 1 == 1
+    }
+
+    /// Returns a uniform random integer below `bound`, rejecting the words which would bias it
+    pub fn below(&mut self, bound: usize) -> usize {
+        let bound = bound as u64;
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let word = self.word();
+            if word < zone {
+                return (word % bound) as usize;
+            }
+        }
+    }
+}
+
+// -------------------------- HELPER FUNCTIONS ---------------------------------------
+
+// Converts the key or the iv to the array expected by a cipher, whose length was checked
+fn to_array<T, const N: usize>(bits: Vec<T>) -> [T; N] {
+    bits.try_into().unwrap_or_else(|_| panic!("length mismatch"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol2::get_rand_key_iv_n;
+
+    #[test]
+    fn test_symmetric_ciphers() {
+        let data: Vec<bool> = (0..500).map(|i| i % 5 == 1).collect();
+        for cipher in SymmetricCipher::ALL {
+            assert_eq!(cipher.to_string().parse::<SymmetricCipher>().unwrap(), cipher);
+            let (key, iv, key_bytes) = get_rand_key_iv_n(cipher.key_len() / 8, cipher.iv_len() / 8);
+            assert_eq!(key_bytes.len() * 8, cipher.key_len());
+
+            let ct = cipher.symmetric_enc(data.clone(), &key, &iv).unwrap();
+            assert_ne!(ct, data);
+            assert_eq!(cipher.symmetric_enc(ct, &key, &iv).unwrap(), data);
+            assert!(matches!(cipher.symmetric_enc(data.clone(), &key[1..], &iv), Err(FdeError::BadLength { what: "k", .. })));
+            assert!(matches!(cipher.symmetric_enc(data.clone(), &key, &iv[1..]), Err(FdeError::BadLength { what: "iv", .. })));
+        }
+        assert!(matches!("aes".parse::<SymmetricCipher>(), Err(FdeError::Deserialize(_))));
+        assert!(matches!("filip".parse::<SymmetricCipher>(), Err(FdeError::Deserialize(_))));
+    }
+}
@@ -0,0 +1,124 @@
+//! This module contains a simulated ledger, used by the smart contracts of both protocols to hold
+//! the payment of the client. The client locks its payment in escrow along with its submission,
+//! and the smart contract releases it to the server only if the verification succeeds. Otherwise,
+//! the payment is refunded to the client.
+
+use std::collections::HashMap;
+use crate::error::FdeError;
+
+/// The identifier of a payment locked in escrow
+pub type EscrowId = u64;
+
+/// A payment locked in escrow, until it is released to the payee or refunded to the payer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Escrow {
+    pub payer: String,
+    pub payee: String,
+    pub amount: u64,
+}
+
+/// The terms of a sale : the account of the server and the price of the data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Terms {
+    pub seller: String,
+    pub price: u64,
+}
+
+impl Default for Terms {
+    /// A free exchange, paid to the account "server"
+    fn default() -> Self {
+        Terms { seller: String::from("server"), price: 0 }
+    }
+}
+
+/// The accounts and their balances, along with the payments in escrow
+#[derive(Clone, Debug, Default)]
+pub struct Ledger {
+    balances: HashMap<String, u64>,
+    escrows: HashMap<EscrowId, Escrow>,
+    next_id: EscrowId,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the balance of `account` (0 for an unknown account)
+    pub fn balance(&self, account: &str) -> u64 {
+        self.balances.get(account).copied().unwrap_or(0)
+    }
+
+    /// Credits `amount` to `account`, creating the account if needed
+    pub fn deposit(&mut self, account: &str, amount: u64) {
+        *self.balances.entry(account.to_string()).or_insert(0) += amount;
+    }
+
+    /// Moves `amount` from the balance of `payer` to a new escrow for `payee`
+    pub fn escrow(&mut self, payer: &str, payee: &str, amount: u64) -> Result<EscrowId, FdeError> {
+        let available = self.balance(payer);
+        if available < amount {
+            return Err(FdeError::InsufficientFunds { needed: amount, available });
+        }
+        self.balances.insert(payer.to_string(), available - amount);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.escrows.insert(id, Escrow { payer: payer.to_string(), payee: payee.to_string(), amount });
+        Ok(id)
+    }
+
+    /// Returns the payment locked in the escrow `id`, if it was neither released nor refunded
+    pub fn escrowed(&self, id: EscrowId) -> Option<&Escrow> {
+        self.escrows.get(&id)
+    }
+
+    /// Pays the escrow `id` to its payee
+    pub fn release(&mut self, id: EscrowId) -> Result<(), FdeError> {
+        let escrow = self.escrows.remove(&id).ok_or(FdeError::UnknownEscrow(id))?;
+        self.deposit(&escrow.payee, escrow.amount);
+        Ok(())
+    }
+
+    /// Pays the escrow `id` back to its payer
+    pub fn refund(&mut self, id: EscrowId) -> Result<(), FdeError> {
+        let escrow = self.escrows.remove(&id).ok_or(FdeError::UnknownEscrow(id))?;
+        self.deposit(&escrow.payer, escrow.amount);
+        Ok(())
+    }
+
+    /// Returns the sum of the balances and of the payments in escrow
+    pub fn total(&self) -> u64 {
+        self.balances.values().sum::<u64>() + self.escrows.values().map(|e| e.amount).sum::<u64>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ledger_escrow() {
+        let mut ledger = Ledger::new();
+        ledger.deposit("client", 100);
+        assert_eq!(ledger.balance("server"), 0);
+
+        let paid = ledger.escrow("client", "server", 60).unwrap();
+        assert_eq!(ledger.balance("client"), 40);
+        assert_eq!(ledger.escrowed(paid).unwrap().amount, 60);
+        assert!(matches!(ledger.escrow("client", "server", 60),
+            Err(FdeError::InsufficientFunds { needed: 60, available: 40 })));
+        let refunded = ledger.escrow("client", "server", 40).unwrap();
+        assert_eq!(ledger.total(), 100);
+
+        ledger.release(paid).unwrap();
+        ledger.refund(refunded).unwrap();
+        assert_eq!(ledger.balance("client"), 40);
+        assert_eq!(ledger.balance("server"), 60);
+        assert_eq!(ledger.total(), 100);
+
+        // a payment can only be released or refunded once
+        assert!(ledger.escrowed(paid).is_none());
+        assert!(matches!(ledger.refund(paid), Err(FdeError::UnknownEscrow(0))));
+        assert!(matches!(ledger.release(refunded), Err(FdeError::UnknownEscrow(1))));
+    }
+}
@@ -1,4 +1,12 @@
 pub mod homomorphic_functions;
 pub mod static_deque;
 pub mod commitment;
-pub mod prot_utils;
\ No newline at end of file
+pub mod config;
+pub mod error;
+pub mod gas;
+pub mod ledger;
+pub mod merkle;
+pub mod prot_utils;
+pub mod protocol1;
+pub mod protocol2;
+pub mod transport;
\ No newline at end of file
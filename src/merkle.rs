@@ -0,0 +1,164 @@
+//! This module contains the Merkle tree over the fixed-size chunks of the data, whose root is the
+//! public commitment of the chunked variant of Protocol II : a client can buy a subset of the
+//! chunks and check each of them against the root with its Merkle proof. The leaves are padded
+//! with empty chunks to a power of two, and the leaves and the inner nodes are hashed with
+//! different prefixes, so that every path has the same length and a leaf cannot pass for a node.
+
+use serde::{Deserialize, Serialize};
+use crate::error::FdeError;
+use crate::homomorphic_functions::HashFunction;
+
+/// Prefix of the hash of a leaf : H(0x00 || chunk)
+pub const LEAF_PREFIX: u8 = 0x00;
+/// Prefix of the hash of an inner node : H(0x01 || left || right)
+pub const NODE_PREFIX: u8 = 0x01;
+
+/// A Merkle tree over the chunks of the data
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    hash_function: HashFunction,
+    chunk_size: usize,
+    num_chunks: usize,
+    /// The hashes of every level, from the (padded) leaves to the root
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+/// The proof that a chunk is the leaf `index` of a tree : the hashes of the siblings on the path
+/// from the leaf to the root
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub index: usize,
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl MerkleTree {
+    /// Builds the tree over the chunks of `chunk_size` bytes of `data` (the last one may be
+    /// shorter, and empty data has a single empty chunk).
+    /// Panics if `chunk_size` is 0
+    pub fn new(data: &[u8], chunk_size: usize, hash_function: HashFunction) -> Self {
+        assert!(chunk_size > 0, "the chunks must not be empty");
+        let mut leaves: Vec<[u8; 32]> = data.chunks(chunk_size).map(|chunk| leaf_hash(chunk, hash_function)).collect();
+        let num_chunks = leaves.len().max(1);
+        leaves.resize(num_chunks.next_power_of_two(), leaf_hash(&[], hash_function));
+
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let nodes = levels.last().unwrap().chunks(2).map(|pair| node_hash(&pair[0], &pair[1], hash_function)).collect();
+            levels.push(nodes);
+        }
+        MerkleTree { hash_function, chunk_size, num_chunks, levels }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Returns the root as a hex string, the commitment written in the hash file
+    pub fn hex_root(&self) -> String {
+        hex::encode(self.root())
+    }
+
+    pub fn hash_function(&self) -> HashFunction {
+        self.hash_function
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    pub fn num_chunks(&self) -> usize {
+        self.num_chunks
+    }
+
+    /// Returns the number of levels above the leaves, the length of every proof
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// Returns the proof of the chunk `index`
+    pub fn proof(&self, index: usize) -> Result<MerkleProof, FdeError> {
+        if index >= self.num_chunks {
+            return Err(FdeError::UnknownChunk { index, chunks: self.num_chunks });
+        }
+        let siblings = self.levels[..self.depth()]
+            .iter()
+            .enumerate()
+            .map(|(level, nodes)| nodes[(index >> level) ^ 1])
+            .collect();
+        Ok(MerkleProof { index, siblings })
+    }
+
+    /// Returns the chunk `index` of `data`, the data the tree was built over
+    pub fn chunk<'a>(&self, data: &'a [u8], index: usize) -> Result<&'a [u8], FdeError> {
+        if index >= self.num_chunks {
+            return Err(FdeError::UnknownChunk { index, chunks: self.num_chunks });
+        }
+        let start = (index * self.chunk_size).min(data.len());
+        Ok(&data[start..(start + self.chunk_size).min(data.len())])
+    }
+}
+
+impl MerkleProof {
+    /// Returns the root of the tree whose leaf `index` is `leaf`
+    pub fn root_from_leaf(&self, leaf: [u8; 32], hash_function: HashFunction) -> [u8; 32] {
+        self.siblings.iter().enumerate().fold(leaf, |node, (level, sibling)| {
+            if (self.index >> level) & 1 == 0 {
+                node_hash(&node, sibling, hash_function)
+            } else {
+                node_hash(sibling, &node, hash_function)
+            }
+        })
+    }
+
+    /// Checks that `chunk` is the leaf `index` of the tree whose hex root is `root`
+    pub fn verify(&self, chunk: &[u8], root: &str, hash_function: HashFunction) -> Result<(), FdeError> {
+        let computed = self.root_from_leaf(leaf_hash(chunk, hash_function), hash_function);
+        if hex::encode(computed) == root { Ok(()) } else { Err(FdeError::HashMismatch) }
+    }
+}
+
+/// Returns H(0x00 || chunk)
+pub fn leaf_hash(chunk: &[u8], hash_function: HashFunction) -> [u8; 32] {
+    digest(&[&[LEAF_PREFIX], chunk].concat(), hash_function)
+}
+
+/// Returns H(0x01 || left || right)
+pub fn node_hash(left: &[u8; 32], right: &[u8; 32], hash_function: HashFunction) -> [u8; 32] {
+    digest(&[&[NODE_PREFIX], &left[..], &right[..]].concat(), hash_function)
+}
+
+fn digest(data: &[u8], hash_function: HashFunction) -> [u8; 32] {
+    hex::decode(hash_function.hex_digest(data)).unwrap().try_into().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merkle_tree() {
+        let data: Vec<u8> = (0..=200).collect();
+        let tree = MerkleTree::new(&data, 32, HashFunction::Sha3_256);
+        assert_eq!((tree.num_chunks(), tree.depth()), (7, 3));
+        for index in 0..7 {
+            let (chunk, proof) = (tree.chunk(&data, index).unwrap(), tree.proof(index).unwrap());
+            assert_eq!(proof.siblings.len(), 3);
+            proof.verify(chunk, &tree.hex_root(), HashFunction::Sha3_256).unwrap();
+        }
+        assert_eq!(tree.chunk(&data, 6).unwrap().len(), 9);
+
+        // another chunk, another index or another hash function does not match the root
+        let proof = tree.proof(2).unwrap();
+        assert!(matches!(proof.verify(tree.chunk(&data, 3).unwrap(), &tree.hex_root(), HashFunction::Sha3_256), Err(FdeError::HashMismatch)));
+        let moved = MerkleProof { index: 3, ..proof.clone() };
+        assert!(moved.verify(tree.chunk(&data, 2).unwrap(), &tree.hex_root(), HashFunction::Sha3_256).is_err());
+        assert!(proof.verify(tree.chunk(&data, 2).unwrap(), &tree.hex_root(), HashFunction::Keccak256).is_err());
+        assert!(matches!(tree.proof(7), Err(FdeError::UnknownChunk { index: 7, chunks: 7 })));
+
+        // a single chunk is its own tree, the root being the hash of the leaf
+        let single = MerkleTree::new(b"tiny", 32, HashFunction::Sha3_256);
+        assert_eq!((single.num_chunks(), single.depth()), (1, 0));
+        assert_eq!(single.root(), leaf_hash(b"tiny", HashFunction::Sha3_256));
+        assert_eq!(MerkleTree::new(&[], 32, HashFunction::Sha3_256).num_chunks(), 1);
+    }
+}
@@ -1,8 +1,12 @@
 use tfhe::boolean::ciphertext::Ciphertext;
 use tfhe::boolean::client_key::ClientKey;
 use crate::commitment::*;
-use crate::homomorphic_functions::{decrypt_bools, bools_to_hex, sha3_hash_from_vec_bool};
-use std::io::{self, Read};
+use crate::error::{check_len, FdeError};
+use crate::homomorphic_functions::{decrypt_bools, bools_to_hex, lwe_decrypt_bools, ChallengeField, HashFunction};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha3::{Digest, Sha3_256};
+use std::io::Read;
 use std::net::{TcpStream};
 
 pub const SUCCESS: u8 = 1;
@@ -14,31 +18,73 @@ pub const SERVER_PORT: u16 = 9001;
 pub const DATA_FILE : &str = "data.txt";
 pub const HASH_FILE : &str = "hash.txt";
 
+/// Magic bytes starting every frame
+pub const FRAME_MAGIC: [u8; 4] = *b"FDE\x00";
+/// Version of the wire format, frames of another version are rejected
+pub const WIRE_VERSION: u8 = 1;
+pub const PROTOCOL_I: u8 = 1;
+pub const PROTOCOL_II: u8 = 2;
+/// Size of the frame header : magic (4), protocol (1), version (1), type (1), session (8) and
+/// checksum (4)
+pub const FRAME_HEADER_LEN: usize = 19;
+
+/// Largest message accepted by `read_one_message` (the public key of the server is ~130MB)
+pub const MAX_MESSAGE_LEN: usize = 1 << 30;
+
 /// Verify function for smart contract and server for protocol I
 /// Check the commitment, and the decryption of hash_ct == hash
-pub fn verify(hash_ct : Vec<Ciphertext>, hash : String, com : String, op : &Opening) -> bool {
-    if !verify_open(com, op) { return false }
-    let secret_key : ClientKey = bincode::deserialize(op.data.as_slice()).unwrap();
+pub fn verify(hash_ct : Vec<Ciphertext>, hash : String, com : String, op : &Opening) -> Result<(), FdeError> {
+    check_len("Hct", 256, hash_ct.len())?;
+    if !verify_open(com, op) { return Err(FdeError::CommitmentMismatch) }
+    let secret_key : ClientKey = bincode::deserialize(op.data.as_slice())?;
     let hash_comp = decrypt_bools(&hash_ct, &secret_key);
-    bools_to_hex(&hash_comp) == hash
+    if bools_to_hex(&hash_comp) == hash { Ok(()) } else { Err(FdeError::HashMismatch) }
+}
+
+/// Verify function for smart contract and server for protocol I, when the opening only holds
+/// the packed LWE secret key instead of the whole `ClientKey`.
+/// Check the commitment, and the decryption of hash_ct == hash with integer arithmetic
+pub fn verify_lwe(hash_ct : Vec<Ciphertext>, hash : String, com : String, op : &Opening) -> Result<(), FdeError> {
+    check_len("Hct", 256, hash_ct.len())?;
+    if !verify_open(com, op) { return Err(FdeError::CommitmentMismatch) }
+    let hash_comp = lwe_decrypt_bools(&hash_ct, &op.data)?;
+    if bools_to_hex(&hash_comp) == hash { Ok(()) } else { Err(FdeError::HashMismatch) }
 }
 
 /// VerifyKA function for smart contract and server for protocol II
 /// Check that the hash of a and k are the expected ones
-pub fn verify_ka(hash_a : String, hash_k : String, a : Vec<bool>, k : Vec<bool>) -> bool {
-    let hash_a_comp = sha3_hash_from_vec_bool(a);
-    let hash_k_comp = sha3_hash_from_vec_bool(k);
-    hash_a_comp == hash_a && hash_k_comp == hash_k
+pub fn verify_ka(hash_a : String, hash_k : String, a : Vec<bool>, k : Vec<bool>) -> Result<(), FdeError> {
+    verify_ka_with(HashFunction::Sha3_256, hash_a, hash_k, a, k)
+}
+
+/// VerifyKA function for smart contract and server for protocol II, where Ha and Hk were computed
+/// with `hash_function`
+pub fn verify_ka_with(hash_function : HashFunction, hash_a : String, hash_k : String, a : Vec<bool>, k : Vec<bool>) -> Result<(), FdeError> {
+    let hash_a_comp = hash_function.hash_from_vec_bool(a);
+    let hash_k_comp = hash_function.hash_from_vec_bool(k);
+    if hash_a_comp == hash_a && hash_k_comp == hash_k { Ok(()) } else { Err(FdeError::HashMismatch) }
+}
+
+/// VerifyKA function for smart contract and server for protocol II, where the challenge was
+/// computed over `field` : â must also be a canonical element of the field
+pub fn verify_ka_in(hash_function : HashFunction, field : ChallengeField, hash_a : String, hash_k : String, a : Vec<bool>, k : Vec<bool>) -> Result<(), FdeError> {
+    if !field.contains(&a) {
+        return Err(FdeError::NotInField(field.to_string()));
+    }
+    verify_ka_with(hash_function, hash_a, hash_k, a, k)
 }
 
 
 /// Reads one message, does not wait for connection to be closed
 /// The message first uses 4 bytes for its size, and then the actual data
-pub fn read_one_message(mut stream: &TcpStream) -> io::Result<Vec<u8>> {
+pub fn read_one_message(mut stream: &TcpStream) -> Result<Vec<u8>, FdeError> {
     // Read  4 bytes for the big-endian length prefix
     let mut len_buf = [0u8; 4];
     stream.read_exact(&mut len_buf)?;
     let msg_len = u32::from_be_bytes(len_buf) as usize;
+    if msg_len > MAX_MESSAGE_LEN {
+        return Err(FdeError::BadLength { what: "message", expected: MAX_MESSAGE_LEN, got: msg_len });
+    }
 
     // Read the message
     let mut buf = vec![0u8; msg_len];
@@ -58,4 +104,151 @@ pub fn prepare_message(msg: &[u8]) -> Vec<u8> {
     buf.extend_from_slice(msg);
 
     buf
-}
\ No newline at end of file
+}
+
+// -------------------------------------- CONTRACT EVENTS ------------------------------------------
+
+/// Why a smart contract aborted an exchange
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AbortReason {
+    /// The server sent an ABORT status
+    ServerAborted,
+    /// The values revealed by the server did not pass the verification
+    VerificationFailed,
+    /// The server did not reveal before the deadline
+    Deadline,
+}
+
+/// The events emitted by the smart contracts, as a contract on a blockchain would log them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContractEvent {
+    /// The exchange succeeded, the payment goes to the server
+    Settled,
+    /// The exchange aborted, the payment goes back to the client
+    Refund(AbortReason),
+}
+
+// ------------------------------------------ FRAMES -----------------------------------------------
+
+/// A message that can be sent in a frame : each message type of a protocol has its own tag
+pub trait WireMessage: Serialize + DeserializeOwned {
+    /// Protocol the message belongs to (`PROTOCOL_I` or `PROTOCOL_II`)
+    const PROTOCOL: u8;
+    /// Type of the message, unique within its protocol
+    const TAG: u8;
+    /// Name of the message, used in error messages
+    const NAME: &'static str;
+}
+
+/// The header prefixed to the serialized message in a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub protocol: u8,
+    pub version: u8,
+    pub msg_type: u8,
+    pub session_id: u64,
+}
+
+impl FrameHeader {
+    /// Returns the header of a frame carrying `M` in the exchange `session_id`
+    pub fn for_message<M: WireMessage>(session_id: u64) -> Self {
+        FrameHeader { protocol: M::PROTOCOL, version: WIRE_VERSION, msg_type: M::TAG, session_id }
+    }
+
+    /// Checks that the frame carries a message of type `M`
+    pub fn expect<M: WireMessage>(&self) -> Result<(), FdeError> {
+        if self.protocol == M::PROTOCOL && self.msg_type == M::TAG {
+            return Ok(());
+        }
+        Err(FdeError::UnexpectedMessage(format!(
+            "expected {} of protocol {}, got message type {} of protocol {}",
+            M::NAME, M::PROTOCOL, self.msg_type, self.protocol
+        )))
+    }
+}
+
+/// Returns a random id for a new exchange
+pub fn new_session_id() -> u64 {
+    rand::random()
+}
+
+// Checksum of a frame : the first 4 bytes of the SHA3-256 of the header fields and the payload
+fn frame_checksum(header: &[u8], payload: &[u8]) -> [u8; 4] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(header);
+    hasher.update(payload);
+    let digest = hasher.finalize();
+    [digest[0], digest[1], digest[2], digest[3]]
+}
+
+/// Builds a frame : the header, its checksum and then the payload. The frame is then sent as one
+/// message (see `prepare_message`)
+pub fn encode_frame(header: &FrameHeader, payload: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    buf.extend_from_slice(&FRAME_MAGIC);
+    buf.push(header.protocol);
+    buf.push(header.version);
+    buf.push(header.msg_type);
+    buf.extend_from_slice(&header.session_id.to_be_bytes());
+    let checksum = frame_checksum(&buf, payload);
+    buf.extend_from_slice(&checksum);
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// Splits a frame in its header and its payload, checking the magic bytes, the version and the
+/// checksum
+pub fn decode_frame(frame: &[u8]) -> Result<(FrameHeader, &[u8]), FdeError> {
+    if frame.len() < FRAME_HEADER_LEN {
+        return Err(FdeError::BadLength { what: "frame", expected: FRAME_HEADER_LEN, got: frame.len() });
+    }
+    let (head, payload) = frame.split_at(FRAME_HEADER_LEN);
+    if head[..4] != FRAME_MAGIC {
+        return Err(FdeError::BadFrame(format!("bad magic bytes {:02x?}", &head[..4])));
+    }
+    if head[5] != WIRE_VERSION {
+        return Err(FdeError::BadFrame(format!("unsupported version {} (expected {})", head[5], WIRE_VERSION)));
+    }
+    if head[15..] != frame_checksum(&head[..15], payload) {
+        return Err(FdeError::BadFrame(String::from("checksum mismatch")));
+    }
+    let header = FrameHeader {
+        protocol: head[4],
+        version: head[5],
+        msg_type: head[6],
+        session_id: u64::from_be_bytes(head[7..15].try_into().unwrap()),
+    };
+    Ok((header, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_roundtrip() {
+        let header = FrameHeader { protocol: PROTOCOL_II, version: WIRE_VERSION, msg_type: 3, session_id: 42 };
+        let frame = encode_frame(&header, b"payload");
+        assert_eq!(frame.len(), FRAME_HEADER_LEN + 7);
+        let (decoded, payload) = decode_frame(&frame).unwrap();
+        assert_eq!(decoded, header);
+        assert_eq!(payload, b"payload");
+    }
+
+    #[test]
+    fn test_frame_invalid() {
+        let header = FrameHeader { protocol: PROTOCOL_I, version: WIRE_VERSION, msg_type: 1, session_id: 7 };
+        let frame = encode_frame(&header, &[1, 2, 3]);
+
+        assert!(matches!(decode_frame(&frame[..10]), Err(FdeError::BadLength { .. })));
+        let mut bad = frame.clone();
+        bad[0] = b'X';
+        assert!(matches!(decode_frame(&bad), Err(FdeError::BadFrame(_))));
+        let mut bad = frame.clone();
+        bad[5] = WIRE_VERSION + 1;
+        assert!(matches!(decode_frame(&bad), Err(FdeError::BadFrame(_))));
+        let mut bad = frame.clone();
+        *bad.last_mut().unwrap() ^= 1;
+        assert!(matches!(decode_frame(&bad), Err(FdeError::BadFrame(_))));
+    }
+}
@@ -0,0 +1,734 @@
+//! This module contains the three roles of Protocol I (server, client and smart contract) as
+//! explicit state machines. Each role is driven by step methods that consume the message received
+//! from a counterparty and produce the message to send next, so that the exchange can be embedded
+//! in any service. The `fde` binary is a thin wrapper around these types.
+
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::commitment::{commit, Opening};
+use crate::error::{check_len, check_state, FdeError};
+use crate::ledger::{EscrowId, Ledger, Terms};
+use crate::homomorphic_functions::{decrypt_bools, encrypt_bools, lwe_decrypt_bools, lwe_key_bytes, HashFunction, Sha3FheHasher};
+use crate::prot_utils::{verify, verify_lwe, AbortReason, ContractEvent, WireMessage, ABORT, PROTOCOL_I, SUCCESS};
+
+// ---------------------------------------- KEY OPENING --------------------------------------------
+
+/// What the server commits to and reveals on-chain, so that the smart contract can check the
+/// decryption of Hct
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyOpening {
+    /// The whole bincode `ClientKey`, the contract decrypts Hct with TFHE
+    #[default]
+    ClientKey,
+    /// Only the bits of the LWE secret key, the contract decrypts Hct with integer arithmetic
+    LweKey,
+}
+
+impl KeyOpening {
+    /// Returns the bytes of `ck` the server commits to
+    pub fn key_bytes(self, ck: &ClientKey) -> Result<Vec<u8>, FdeError> {
+        match self {
+            KeyOpening::ClientKey => Ok(bincode::serialize(ck)?),
+            KeyOpening::LweKey => Ok(lwe_key_bytes(ck)),
+        }
+    }
+
+    /// Runs the verify function matching the opening
+    pub fn verify(self, hash_ct: Vec<Ciphertext>, hash: String, com: String, op: &Opening) -> Result<(), FdeError> {
+        match self {
+            KeyOpening::ClientKey => verify(hash_ct, hash, com, op),
+            KeyOpening::LweKey => verify_lwe(hash_ct, hash, com, op),
+        }
+    }
+
+    /// Decrypts `ct` with the revealed key bytes
+    pub fn decrypt(self, ct: &Vec<Ciphertext>, key: &[u8]) -> Result<Vec<bool>, FdeError> {
+        match self {
+            KeyOpening::ClientKey => {
+                let secret_key: ClientKey = bincode::deserialize(key)?;
+                Ok(decrypt_bools(ct, &secret_key))
+            }
+            KeyOpening::LweKey => lwe_decrypt_bools(ct, key),
+        }
+    }
+}
+
+impl FromStr for KeyOpening {
+    type Err = FdeError;
+
+    fn from_str(s: &str) -> Result<Self, FdeError> {
+        match s {
+            "client_key" => Ok(KeyOpening::ClientKey),
+            "lwe_key" => Ok(KeyOpening::LweKey),
+            _ => Err(FdeError::Deserialize(format!("unknown key opening {} (expected client_key or lwe_key)", s))),
+        }
+    }
+}
+
+// ----------------------------------------- MESSAGES ----------------------------------------------
+
+/// Sent off-chain by the server to the client: the encrypted padded data, the public (evaluation)
+/// key, the commitment to the secret key, what the commitment opens to and the hash function of
+/// the data
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CiphertextBundle {
+    pub ct: Vec<Ciphertext>,
+    pub pk: ServerKey,
+    pub com: String,
+    pub key_opening: KeyOpening,
+    pub hash_function: HashFunction,
+}
+
+/// Sent off-chain by the server to the client instead of the bundle, when the encrypted data is
+/// streamed: everything but the data, and the number of blocks that follow
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CiphertextHeader {
+    pub pk: ServerKey,
+    pub com: String,
+    pub key_opening: KeyOpening,
+    pub hash_function: HashFunction,
+    pub blocks: u64,
+}
+
+/// Sent off-chain by the server to the client after the header: one block of the encrypted padded
+/// data, as long as the rate of the hash function
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CiphertextBlock {
+    pub ct: Vec<Ciphertext>,
+}
+
+/// Sent on-chain by the client to the smart contract (and relayed to the server): the homomorphic
+/// hash Hct, the expected hash H and the commitment com, along with the payment locked in escrow
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Submission {
+    pub hash_ct: Vec<Ciphertext>,
+    pub hash: String,
+    pub com: String,
+    pub payer: String,
+    pub amount: u64,
+}
+
+/// Sent on-chain by the server to the smart contract: the server's status and the opening of the
+/// commitment (an empty opening if the server aborts)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Reveal {
+    pub status: u8,
+    pub opening: Opening,
+}
+
+/// Sent by the smart contract to the client and the server once the exchange is over: the final
+/// status, and the secret key (empty if the exchange aborted)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Settlement {
+    pub status: u8,
+    pub secret_key: Vec<u8>,
+}
+
+impl CiphertextBundle {
+    /// Splits the bundle into a header followed by one message per block of the data, so that the
+    /// client can hash each block as soon as it arrives
+    pub fn into_stream(self) -> (CiphertextHeader, Vec<CiphertextBlock>) {
+        let blocks: Vec<CiphertextBlock> = self.ct
+            .chunks(self.hash_function.sponge().rate())
+            .map(|ct| CiphertextBlock { ct: ct.to_vec() })
+            .collect();
+        let header = CiphertextHeader {
+            pk: self.pk,
+            com: self.com,
+            key_opening: self.key_opening,
+            hash_function: self.hash_function,
+            blocks: blocks.len() as u64,
+        };
+        (header, blocks)
+    }
+}
+
+impl WireMessage for CiphertextBundle {
+    const PROTOCOL: u8 = PROTOCOL_I;
+    const TAG: u8 = 1;
+    const NAME: &'static str = "CiphertextBundle";
+}
+
+impl WireMessage for Submission {
+    const PROTOCOL: u8 = PROTOCOL_I;
+    const TAG: u8 = 2;
+    const NAME: &'static str = "Submission";
+}
+
+impl WireMessage for Reveal {
+    const PROTOCOL: u8 = PROTOCOL_I;
+    const TAG: u8 = 3;
+    const NAME: &'static str = "Reveal";
+}
+
+impl WireMessage for Settlement {
+    const PROTOCOL: u8 = PROTOCOL_I;
+    const TAG: u8 = 4;
+    const NAME: &'static str = "Settlement";
+}
+
+impl WireMessage for CiphertextHeader {
+    const PROTOCOL: u8 = PROTOCOL_I;
+    const TAG: u8 = 5;
+    const NAME: &'static str = "CiphertextHeader";
+}
+
+impl WireMessage for CiphertextBlock {
+    const PROTOCOL: u8 = PROTOCOL_I;
+    const TAG: u8 = 6;
+    const NAME: &'static str = "CiphertextBlock";
+}
+
+// ------------------------------------------ SERVER -----------------------------------------------
+
+/// The states of the server in Protocol I
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Server1State {
+    AwaitingSubmission,
+    Revealed,
+    Settled,
+    Aborted,
+}
+
+/// The server of Protocol I: it owns the data and the homomorphic secret key
+pub struct Server1 {
+    state: Server1State,
+    opening: Opening,
+    key_opening: KeyOpening,
+}
+
+impl Server1 {
+    /// Pads and encrypts `data` homomorphically and commits to the whole secret key.
+    /// Returns the server along with the bundle (ct, pk, com) to send off-chain to the client
+    pub fn new(data: &[u8]) -> Result<(Self, CiphertextBundle), FdeError> {
+        Self::with_options(data, KeyOpening::ClientKey, HashFunction::Sha3_256)
+    }
+
+    /// Pads `data` for `hash_function`, encrypts it homomorphically and commits to the part of the
+    /// secret key given by `key_opening`. Returns the server along with the bundle (ct, pk, com) to
+    /// send off-chain to the client
+    pub fn with_options(
+        data: &[u8],
+        key_opening: KeyOpening,
+        hash_function: HashFunction,
+    ) -> Result<(Self, CiphertextBundle), FdeError> {
+        let padded_input = hash_function.sponge().pad_bytes(data);
+        let (ck, sk) = gen_keys();
+        let ct = encrypt_bools(padded_input, &ck);
+
+        let secret_key_serialize = key_opening.key_bytes(&ck)?;
+        let (com, opening) = commit(secret_key_serialize.as_slice());
+
+        let server = Server1 { state: Server1State::AwaitingSubmission, opening, key_opening };
+        Ok((server, CiphertextBundle { ct, pk: sk, com, key_opening, hash_function }))
+    }
+
+    pub fn state(&self) -> Server1State {
+        self.state
+    }
+
+    /// Runs the verify function on the client's submission, and reveals the opening if it
+    /// succeeded. Otherwise (including for a malformed submission), the server aborts and sends an
+    /// empty opening.
+    pub fn on_submission(&mut self, msg: Submission) -> Result<Reveal, FdeError> {
+        check_state(self.state, Server1State::AwaitingSubmission, "Server1 received a submission")?;
+        if self.key_opening.verify(msg.hash_ct, msg.hash, msg.com, &self.opening).is_ok() {
+            self.state = Server1State::Revealed;
+            Ok(Reveal { status: SUCCESS, opening: self.opening.clone() })
+        } else {
+            self.state = Server1State::Aborted;
+            Ok(Reveal { status: ABORT, opening: Opening { nonce: [0u8; 32], data: vec![] } })
+        }
+    }
+
+    /// Records the final status sent by the smart contract
+    pub fn on_settlement(&mut self, msg: &Settlement) -> Result<(), FdeError> {
+        if self.state != Server1State::Aborted {
+            check_state(self.state, Server1State::Revealed, "Server1 received a settlement")?;
+        }
+        self.state = if msg.status == SUCCESS { Server1State::Settled } else { Server1State::Aborted };
+        Ok(())
+    }
+}
+
+// ------------------------------------------ CLIENT -----------------------------------------------
+
+/// The states of the client in Protocol I
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Client1State {
+    AwaitingCiphertext,
+    ReceivingCiphertext,
+    HashComputed,
+    Settled,
+    Aborted,
+}
+
+/// Largest number of blocks of encrypted data a client accepts by default (about 8.9MB of data),
+/// as it keeps every encrypted block until the secret key is revealed
+pub const DEFAULT_MAX_BLOCKS: u64 = 1 << 16;
+
+/// The client of Protocol I: it knows the hash of the data it wants to buy
+pub struct Client1 {
+    state: Client1State,
+    hash: String,
+    ct: Vec<Ciphertext>,
+    key_opening: KeyOpening,
+    hash_function: HashFunction,
+    payer: String,
+    amount: u64,
+    max_blocks: u64,
+    // while the data is streamed: the evaluation key, the commitment, the hash absorbed so far and
+    // the number of blocks announced by the header
+    pk: Option<ServerKey>,
+    com: String,
+    hasher: Option<Sha3FheHasher>,
+    blocks: usize,
+}
+
+impl Client1 {
+    /// Creates a client buying the data whose SHA3-256 hex digest is `hash`
+    pub fn new(hash: String) -> Self {
+        Client1 {
+            state: Client1State::AwaitingCiphertext,
+            hash,
+            ct: vec![],
+            key_opening: KeyOpening::default(),
+            hash_function: HashFunction::default(),
+            payer: String::from("client"),
+            amount: 0,
+            max_blocks: DEFAULT_MAX_BLOCKS,
+            pk: None,
+            com: String::new(),
+            hasher: None,
+            blocks: 0,
+        }
+    }
+
+    /// Pays `amount` from the account `payer` when submitting to the smart contract
+    pub fn with_payment(self, payer: String, amount: u64) -> Self {
+        Client1 { payer, amount, ..self }
+    }
+
+    /// Rejects the encrypted data if the server announces more than `max_blocks` blocks
+    pub fn with_max_blocks(self, max_blocks: u64) -> Self {
+        Client1 { max_blocks, ..self }
+    }
+
+    pub fn state(&self) -> Client1State {
+        self.state
+    }
+
+    /// Computes the hash of the encrypted data homomorphically.
+    /// Returns (Hct, H, com) to send on-chain to the smart contract
+    pub fn on_ciphertext(&mut self, msg: CiphertextBundle) -> Result<Submission, FdeError> {
+        check_state(self.state, Client1State::AwaitingCiphertext, "Client1 received a ciphertext")?;
+        check_padded_len(msg.ct.len(), msg.hash_function.sponge().rate())?;
+        let (header, blocks) = msg.into_stream();
+        self.on_header(header)?;
+        for block in blocks {
+            self.on_block(block)?;
+        }
+        self.on_last_block()
+    }
+
+    /// Starts receiving the encrypted data as a stream of `msg.blocks` blocks, which must be
+    /// between 1 and the maximum number of blocks of the client
+    pub fn on_header(&mut self, msg: CiphertextHeader) -> Result<(), FdeError> {
+        check_state(self.state, Client1State::AwaitingCiphertext, "Client1 received a ciphertext header")?;
+        let rate = msg.hash_function.sponge().rate();
+        let blocks = usize::try_from(msg.blocks).unwrap_or(usize::MAX);
+        if msg.blocks == 0 || msg.blocks > self.max_blocks || blocks.checked_mul(rate).is_none() {
+            let expected = usize::try_from(self.max_blocks).unwrap_or(usize::MAX);
+            return Err(FdeError::BadLength { what: "ciphertext blocks (at most)", expected, got: blocks });
+        }
+        self.hasher = Some(msg.hash_function.sponge().hasher(&msg.pk));
+        self.ct = vec![];
+        self.pk = Some(msg.pk);
+        self.com = msg.com;
+        self.key_opening = msg.key_opening;
+        self.hash_function = msg.hash_function;
+        self.blocks = blocks;
+        self.state = Client1State::ReceivingCiphertext;
+        Ok(())
+    }
+
+    /// Absorbs one block of the encrypted data in the homomorphic hash as soon as it is received.
+    /// The hash only holds one Keccak state, but the block is kept to decrypt the data once the
+    /// secret key is revealed, so the client still holds the whole encrypted data
+    pub fn on_block(&mut self, msg: CiphertextBlock) -> Result<(), FdeError> {
+        check_state(self.state, Client1State::ReceivingCiphertext, "Client1 received a ciphertext block")?;
+        let (Some(pk), Some(hasher)) = (&self.pk, &mut self.hasher) else {
+            return Err(FdeError::UnexpectedMessage(String::from("Client1 received a block without a header")));
+        };
+        if hasher.absorbed_blocks() == self.blocks {
+            return Err(FdeError::UnexpectedMessage(format!("Client1 received more than {} blocks", self.blocks)));
+        }
+        check_len("ciphertext block (in bits)", self.hash_function.sponge().rate(), msg.ct.len())?;
+        hasher.update(&msg.ct, pk);
+        self.ct.extend(msg.ct);
+        Ok(())
+    }
+
+    /// Squeezes the homomorphic hash once all the blocks are absorbed.
+    /// Returns (Hct, H, com) to send on-chain to the smart contract
+    pub fn on_last_block(&mut self) -> Result<Submission, FdeError> {
+        check_state(self.state, Client1State::ReceivingCiphertext, "Client1 finished receiving the ciphertext")?;
+        let absorbed = self.hasher.as_ref().map_or(0, |hasher| hasher.absorbed_blocks());
+        check_len("ciphertext blocks", self.blocks, absorbed)?;
+        let (Some(pk), Some(hasher)) = (self.pk.take(), self.hasher.take()) else {
+            return Err(FdeError::UnexpectedMessage(String::from("Client1 finished receiving without a header")));
+        };
+        let hash_ct = hasher.finalize(&pk)?;
+        self.state = Client1State::HashComputed;
+        Ok(Submission {
+            hash_ct,
+            hash: self.hash.clone(),
+            com: std::mem::take(&mut self.com),
+            payer: self.payer.clone(),
+            amount: self.amount,
+        })
+    }
+
+    /// Reads the final status of the smart contract. On success, decrypts the data with the
+    /// revealed secret key and returns it, if it has the expected hash
+    pub fn on_settlement(&mut self, msg: &Settlement) -> Result<Vec<u8>, FdeError> {
+        check_state(self.state, Client1State::HashComputed, "Client1 received a settlement")?;
+        if msg.status != SUCCESS {
+            self.state = Client1State::Aborted;
+            return Err(FdeError::PeerAborted);
+        }
+        self.state = Client1State::Settled;
+
+        let data = self.key_opening.decrypt(&self.ct, &msg.secret_key)?;
+        let unpadded_data = self.hash_function.sponge().unpad_bytes(data.as_slice())?;
+        if self.hash_function.hex_digest(unpadded_data.as_slice()) == self.hash { Ok(unpadded_data) } else { Err(FdeError::HashMismatch) }
+    }
+}
+
+// --------------------------------------- SMART CONTRACT ------------------------------------------
+
+/// The states of the smart contract in Protocol I
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Contract1State {
+    AwaitingSubmission,
+    Committed,
+    Settled,
+    Aborted,
+}
+
+/// The smart contract of Protocol I: it stores the client's submission, holds its payment in
+/// escrow and verifies the opening revealed by the server
+pub struct Contract1 {
+    state: Contract1State,
+    submission: Option<Submission>,
+    reveal_deadline: Option<Duration>,
+    deadline: Option<Instant>,
+    events: Vec<ContractEvent>,
+    ledger: Ledger,
+    terms: Terms,
+    escrow: Option<EscrowId>,
+    key_opening: KeyOpening,
+}
+
+impl Default for Contract1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Contract1 {
+    /// Creates a contract for a free exchange, which waits for the server's reveal without a deadline
+    pub fn new() -> Self {
+        Contract1 {
+            state: Contract1State::AwaitingSubmission,
+            submission: None,
+            reveal_deadline: None,
+            deadline: None,
+            events: vec![],
+            ledger: Ledger::new(),
+            terms: Terms::default(),
+            escrow: None,
+            key_opening: KeyOpening::default(),
+        }
+    }
+
+    /// The server must reveal the opening within `reveal_deadline` of the client's submission,
+    /// otherwise the client is refunded
+    pub fn with_deadline(self, reveal_deadline: Duration) -> Self {
+        Contract1 { reveal_deadline: Some(reveal_deadline), ..self }
+    }
+
+    /// The client pays at least `terms.price` from its account on `ledger`, which is released to
+    /// `terms.seller` if the verification succeeds
+    pub fn with_ledger(self, ledger: Ledger, terms: Terms) -> Self {
+        Contract1 { ledger, terms, ..self }
+    }
+
+    /// The server reveals the part of the secret key given by `key_opening`, which must match
+    /// the commitment sent to the client
+    pub fn with_key_opening(self, key_opening: KeyOpening) -> Self {
+        Contract1 { key_opening, ..self }
+    }
+
+    pub fn key_opening(&self) -> KeyOpening {
+        self.key_opening
+    }
+
+    pub fn state(&self) -> Contract1State {
+        self.state
+    }
+
+    /// Returns the instant before which the server must reveal, once the client submitted
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the events emitted so far
+    pub fn events(&self) -> &[ContractEvent] {
+        &self.events
+    }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Stores (Hct, H, com) sent by the client and locks its payment in escrow. Rejects the
+    /// submission if the payment is below the price or the client cannot pay it
+    pub fn on_submission(&mut self, msg: Submission) -> Result<(), FdeError> {
+        check_state(self.state, Contract1State::AwaitingSubmission, "Contract1 received a submission")?;
+        if msg.amount < self.terms.price {
+            return Err(FdeError::InsufficientFunds { needed: self.terms.price, available: msg.amount });
+        }
+        self.escrow = Some(self.ledger.escrow(&msg.payer, &self.terms.seller, msg.amount)?);
+        self.submission = Some(msg);
+        self.deadline = self.reveal_deadline.map(|d| Instant::now() + d);
+        self.state = Contract1State::Committed;
+        Ok(())
+    }
+
+    /// Runs the verify function on the opening revealed by the server, if the server did not abort
+    /// and revealed before the deadline.
+    /// Returns the final status, which is sent to both the client and the server
+    pub fn on_reveal(&mut self, msg: Reveal) -> Result<Settlement, FdeError> {
+        check_state(self.state, Contract1State::Committed, "Contract1 received a reveal")?;
+        if self.deadline_passed() {
+            return self.abort(AbortReason::Deadline);
+        }
+        if msg.status != SUCCESS {
+            return self.abort(AbortReason::ServerAborted);
+        }
+        let submission = self.submission.take().unwrap();
+        if self.key_opening.verify(submission.hash_ct, submission.hash, submission.com, &msg.opening).is_err() {
+            return self.abort(AbortReason::VerificationFailed);
+        }
+        if let Some(escrow) = self.escrow.take() {
+            self.ledger.release(escrow)?;
+        }
+        self.state = Contract1State::Settled;
+        self.events.push(ContractEvent::Settled);
+        Ok(Settlement { status: SUCCESS, secret_key: msg.opening.data })
+    }
+
+    /// Aborts the exchange once the deadline passed without a reveal from the server, the client is
+    /// refunded. Returns the final status, which is sent to both the client and the server
+    pub fn on_deadline(&mut self) -> Result<Settlement, FdeError> {
+        check_state(self.state, Contract1State::Committed, "Contract1 reached a deadline")?;
+        if !self.deadline_passed() {
+            return Err(FdeError::UnexpectedMessage(String::from("the deadline of Contract1 has not passed")));
+        }
+        self.abort(AbortReason::Deadline)
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    // Aborts the exchange and refunds the client
+    fn abort(&mut self, reason: AbortReason) -> Result<Settlement, FdeError> {
+        if let Some(escrow) = self.escrow.take() {
+            self.ledger.refund(escrow)?;
+        }
+        self.submission = None;
+        self.state = Contract1State::Aborted;
+        self.events.push(ContractEvent::Refund(reason));
+        Ok(Settlement { status: ABORT, secret_key: vec![] })
+    }
+}
+
+// ------------------------------------------ HELPERS ----------------------------------------------
+
+/// Checks that the encrypted data is made of whole blocks of `rate` bits, the rate of its hash
+/// function
+pub fn check_padded_len(len: usize, rate: usize) -> Result<(), FdeError> {
+    if len == 0 || !len.is_multiple_of(rate) {
+        return Err(FdeError::BadLength { what: "padded data (in bits)", expected: len.div_ceil(rate).max(1) * rate, got: len });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic_functions::hex_sha3;
+
+    fn paying_submission(amount: u64) -> Submission {
+        Submission { hash_ct: vec![], hash: String::new(), com: String::new(), payer: String::from("client"), amount }
+    }
+
+    #[test]
+    fn test_check_padded_len() {
+        assert!(check_padded_len(1088, 1088).is_ok());
+        assert!(check_padded_len(3 * 1088, 1088).is_ok());
+        assert!(matches!(check_padded_len(0, 1088), Err(FdeError::BadLength { expected: 1088, got: 0, .. })));
+        assert!(matches!(check_padded_len(1100, 1088), Err(FdeError::BadLength { expected: 2176, got: 1100, .. })));
+        assert!(check_padded_len(1152, 1152).is_ok());
+        assert!(check_padded_len(1088, 1152).is_err());
+    }
+
+    #[test]
+    fn test_contract_rejects_out_of_order_messages() {
+        let mut contract = Contract1::new();
+        let reveal = Reveal { status: SUCCESS, opening: Opening { nonce: [0u8; 32], data: vec![] } };
+        assert!(matches!(contract.on_reveal(reveal.clone()), Err(FdeError::UnexpectedMessage(_))));
+
+        // a malformed submission (Hct of the wrong length) makes the contract abort instead of panicking
+        let submission = paying_submission(0);
+        contract.on_submission(submission.clone()).unwrap();
+        assert!(matches!(contract.on_submission(submission), Err(FdeError::UnexpectedMessage(_))));
+        let settlement = contract.on_reveal(reveal.clone()).unwrap();
+        assert_eq!(settlement.status, ABORT);
+        assert_eq!(contract.state(), Contract1State::Aborted);
+        assert_eq!(contract.events(), &[ContractEvent::Refund(AbortReason::VerificationFailed)]);
+
+        let mut contract = Contract1::new();
+        contract.on_submission(paying_submission(0)).unwrap();
+        contract.on_reveal(Reveal { status: ABORT, ..reveal }).unwrap();
+        assert_eq!(contract.events(), &[ContractEvent::Refund(AbortReason::ServerAborted)]);
+    }
+
+    #[test]
+    fn test_contract_deadline() {
+        let submission = paying_submission(0);
+
+        // without a deadline, the contract waits for the server forever
+        let mut contract = Contract1::new();
+        contract.on_submission(submission.clone()).unwrap();
+        assert!(contract.deadline().is_none());
+        assert!(matches!(contract.on_deadline(), Err(FdeError::UnexpectedMessage(_))));
+
+        let mut contract = Contract1::new().with_deadline(Duration::ZERO);
+        assert!(matches!(contract.on_deadline(), Err(FdeError::UnexpectedMessage(_))));
+        contract.on_submission(submission).unwrap();
+        let settlement = contract.on_deadline().unwrap();
+        assert_eq!(settlement.status, ABORT);
+        assert!(settlement.secret_key.is_empty());
+        assert_eq!(contract.state(), Contract1State::Aborted);
+        assert_eq!(contract.events(), &[ContractEvent::Refund(AbortReason::Deadline)]);
+    }
+
+    #[test]
+    fn test_contract_escrow() {
+        let mut ledger = Ledger::new();
+        ledger.deposit("client", 150);
+        let terms = Terms { seller: String::from("server"), price: 100 };
+
+        // the client must pay the price, and cannot pay more than its balance
+        let mut contract = Contract1::new().with_ledger(ledger, terms);
+        assert!(matches!(contract.on_submission(paying_submission(50)),
+            Err(FdeError::InsufficientFunds { needed: 100, available: 50 })));
+        assert!(matches!(contract.on_submission(paying_submission(200)),
+            Err(FdeError::InsufficientFunds { needed: 200, available: 150 })));
+        assert_eq!(contract.state(), Contract1State::AwaitingSubmission);
+
+        contract.on_submission(paying_submission(120)).unwrap();
+        assert_eq!(contract.ledger().balance("client"), 30);
+        assert_eq!(contract.ledger().total(), 150);
+
+        // the opening does not verify, the payment goes back to the client
+        let reveal = Reveal { status: SUCCESS, opening: Opening { nonce: [0u8; 32], data: vec![] } };
+        assert_eq!(contract.on_reveal(reveal).unwrap().status, ABORT);
+        assert_eq!(contract.ledger().balance("client"), 150);
+        assert_eq!(contract.ledger().balance("server"), 0);
+    }
+
+    #[test]
+    fn test_contract_lwe_key_opening() {
+        use tfhe::boolean::parameters::DEFAULT_PARAMETERS;
+
+        // Hct is forged by encrypting the expected hash, which is what the client computes
+        let ck = ClientKey::new(&DEFAULT_PARAMETERS);
+        let hash = hex_sha3(b"data");
+        let hash_bits: Vec<bool> = hex::decode(&hash).unwrap().iter()
+            .flat_map(|byte| (0..8).map(move |i| byte >> i & 1 == 1)).collect();
+        assert_eq!(crate::homomorphic_functions::bools_to_hex(&hash_bits), hash);
+        let hash_ct = encrypt_bools(hash_bits, &ck);
+        let key = KeyOpening::LweKey.key_bytes(&ck).unwrap();
+        let (com, opening) = commit(&key);
+        let submission = Submission { hash_ct, hash, com, ..paying_submission(0) };
+
+        // the compact opening is checked with integer arithmetic only
+        let mut contract = Contract1::new().with_key_opening(KeyOpening::LweKey);
+        contract.on_submission(submission.clone()).unwrap();
+        let settlement = contract.on_reveal(Reveal { status: SUCCESS, opening: opening.clone() }).unwrap();
+        assert_eq!(settlement.status, SUCCESS);
+        assert_eq!(settlement.secret_key, key);
+        assert!(key.len() < 128);
+
+        // a contract expecting the whole ClientKey rejects it
+        let mut contract = Contract1::new();
+        contract.on_submission(submission).unwrap();
+        assert_eq!(contract.on_reveal(Reveal { status: SUCCESS, opening }).unwrap().status, ABORT);
+        assert_eq!(contract.events(), &[ContractEvent::Refund(AbortReason::VerificationFailed)]);
+
+        assert_eq!("lwe_key".parse::<KeyOpening>().unwrap(), KeyOpening::LweKey);
+        assert!("lwe".parse::<KeyOpening>().is_err());
+    }
+
+    #[test]
+    fn test_client_stream_out_of_order() {
+        let mut client = Client1::new(hex_sha3(b"data"));
+        assert!(matches!(client.on_block(CiphertextBlock { ct: vec![Ciphertext::Trivial(true); 1088] }),
+            Err(FdeError::UnexpectedMessage(_))));
+        assert!(matches!(client.on_last_block(), Err(FdeError::UnexpectedMessage(_))));
+        assert_eq!(client.state(), Client1State::AwaitingCiphertext);
+    }
+
+    #[test]
+    fn test_client_rejects_header_block_count() {
+        let (_, sk) = gen_keys();
+        let header = |blocks| CiphertextHeader {
+            pk: sk.clone(),
+            com: String::new(),
+            key_opening: KeyOpening::default(),
+            hash_function: HashFunction::default(),
+            blocks,
+        };
+
+        // the server cannot make the client overflow or allocate the announced data
+        let mut client = Client1::new(hex_sha3(b"data")).with_max_blocks(2);
+        for blocks in [0, 3, u64::MAX] {
+            assert!(matches!(client.on_header(header(blocks)), Err(FdeError::BadLength { expected: 2, .. })));
+            assert_eq!(client.state(), Client1State::AwaitingCiphertext);
+        }
+        client.on_header(header(2)).unwrap();
+        assert_eq!(client.state(), Client1State::ReceivingCiphertext);
+    }
+
+    #[test]
+    fn test_client_rejects_malformed_messages() {
+        let mut client = Client1::new(hex_sha3(b"data"));
+        assert!(matches!(client.on_settlement(&Settlement { status: SUCCESS, secret_key: vec![] }),
+            Err(FdeError::UnexpectedMessage(_))));
+
+        // a secret key that does not deserialize does not crash the client
+        client.state = Client1State::HashComputed;
+        assert!(matches!(client.on_settlement(&Settlement { status: SUCCESS, secret_key: vec![1, 2, 3] }),
+            Err(FdeError::Deserialize(_))));
+        client.state = Client1State::HashComputed;
+        assert!(matches!(client.on_settlement(&Settlement { status: ABORT, secret_key: vec![] }),
+            Err(FdeError::PeerAborted)));
+    }
+}
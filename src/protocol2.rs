@@ -0,0 +1,1047 @@
+//! This module contains the three roles of Protocol II (server, client and smart contract) as
+//! explicit state machines, along with the messages they exchange. The client never decrypts the
+//! data before the exchange settles: it decrypts the Trivium (or Kreyvium) ciphertext homomorphically, and proves
+//! with a challenge that the hashes of the data and of the symmetric key are the expected ones.
+//! Several files of the same server can be bought in one exchange : they are encrypted under the
+//! same symmetric key, and all their hashes are folded in a single challenge. In the chunked
+//! variant, the data is committed to by the root of a Merkle tree over its chunks : the client
+//! requests a subset of the chunks, and proves that the root of each chunk's path is the expected one.
+//! The `fde` binary is a thin wrapper around these types.
+
+use rand::Rng;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tfhe::boolean::prelude::*;
+use crate::error::{check_len, check_state, FdeError};
+use crate::ledger::{EscrowId, Ledger, Terms};
+use crate::homomorphic_functions::{
+    bits_to_bytes_lsb, decrypt_bools, encrypt_bools, merkle_leaf_fhe, merkle_root_fhe, AesCtrStream, ChallengeField, HashFunction,
+    StreamCipher, SymmetricCipher,
+};
+use crate::merkle::{MerkleProof, MerkleTree};
+use crate::prot_utils::{verify_ka_in, AbortReason, ContractEvent, WireMessage, ABORT, PROTOCOL_II, SUCCESS};
+use crate::protocol1::check_padded_len;
+
+// ----------------------------------------- MESSAGES ----------------------------------------------
+
+/// Sent off-chain by the server to the client: the symmetrically encrypted padded files, the
+/// homomorphically encrypted symmetric key, the hash of the symmetric key, the public (evaluation)
+/// key, the hash function of the exchange and the cipher proposed by the server
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CiphertextBundle {
+    pub files: Vec<EncryptedFile>,
+    pub key_ct: Vec<Ciphertext>,
+    pub hash_key: String,
+    pub pk: ServerKey,
+    pub hash_function: HashFunction,
+    pub cipher: SymmetricCipher,
+}
+
+/// A file of a bundle: its symmetrically encrypted padded data, and the iv it was encrypted with.
+/// A chunk is encrypted without padding, along with its Merkle proof
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EncryptedFile {
+    pub sym_ct: Vec<bool>,
+    pub iv: Vec<bool>,
+    pub proof: Option<MerkleProof>,
+}
+
+/// Sent off-chain by the client to the server in the chunked variant, before the bundle: the
+/// indices of the chunks it buys
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRequest {
+    pub indices: Vec<usize>,
+}
+
+/// Sent off-chain by the client to the server: the encrypted challenge
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Challenge {
+    pub chal: Vec<Ciphertext>,
+}
+
+/// Sent on-chain by the client to the smart contract (and relayed to the server): the hash of the
+/// random value a, the expected hash of the symmetric key and the field of the challenge, along
+/// with the payment locked in escrow
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Submission {
+    pub hash_a: String,
+    pub hash_k: String,
+    pub field: ChallengeField,
+    pub payer: String,
+    pub amount: u64,
+}
+
+/// Sent on-chain by the server to the smart contract: the server's status, the symmetric key k
+/// and the decrypted challenge â (all-zero values if the server aborts)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Reveal {
+    pub status: u8,
+    pub key: Vec<bool>,
+    pub a: Vec<bool>,
+}
+
+/// Sent by the smart contract to the client and the server once the exchange is over: the final
+/// status, and the symmetric key (empty if the exchange aborted)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Settlement {
+    pub status: u8,
+    pub key: Vec<bool>,
+}
+
+impl WireMessage for CiphertextBundle {
+    const PROTOCOL: u8 = PROTOCOL_II;
+    const TAG: u8 = 1;
+    const NAME: &'static str = "CiphertextBundle";
+}
+
+impl WireMessage for Challenge {
+    const PROTOCOL: u8 = PROTOCOL_II;
+    const TAG: u8 = 2;
+    const NAME: &'static str = "Challenge";
+}
+
+impl WireMessage for Submission {
+    const PROTOCOL: u8 = PROTOCOL_II;
+    const TAG: u8 = 3;
+    const NAME: &'static str = "Submission";
+}
+
+impl WireMessage for Reveal {
+    const PROTOCOL: u8 = PROTOCOL_II;
+    const TAG: u8 = 4;
+    const NAME: &'static str = "Reveal";
+}
+
+impl WireMessage for Settlement {
+    const PROTOCOL: u8 = PROTOCOL_II;
+    const TAG: u8 = 5;
+    const NAME: &'static str = "Settlement";
+}
+
+impl WireMessage for ChunkRequest {
+    const PROTOCOL: u8 = PROTOCOL_II;
+    const TAG: u8 = 6;
+    const NAME: &'static str = "ChunkRequest";
+}
+
+/// All the messages of Protocol II, to be dispatched by an event loop to the step functions of the
+/// roles
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Message {
+    CiphertextBundle(CiphertextBundle),
+    Challenge(Challenge),
+    Submission(Submission),
+    Reveal(Reveal),
+    Status(Settlement),
+    ChunkRequest(ChunkRequest),
+}
+
+impl Message {
+    /// Returns the type tag of the message in a frame
+    pub fn tag(&self) -> u8 {
+        match self {
+            Message::CiphertextBundle(_) => CiphertextBundle::TAG,
+            Message::Challenge(_) => Challenge::TAG,
+            Message::Submission(_) => Submission::TAG,
+            Message::Reveal(_) => Reveal::TAG,
+            Message::Status(_) => Settlement::TAG,
+            Message::ChunkRequest(_) => ChunkRequest::TAG,
+        }
+    }
+}
+
+// ------------------------------------------ SERVER -----------------------------------------------
+
+/// The states of the server in Protocol II
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Server2State {
+    AwaitingSubmission,
+    AwaitingChallenge,
+    Revealed,
+    Settled,
+    Aborted,
+}
+
+/// The server of Protocol II: it owns the data, the symmetric key and the homomorphic secret key
+pub struct Server2 {
+    state: Server2State,
+    secret_key: ClientKey,
+    sym_key: Vec<bool>,
+    submission: Option<Submission>,
+    hash_function: HashFunction,
+}
+
+impl Server2 {
+    /// Pads and encrypts `data` with Trivium under a fresh symmetric key, encrypts the symmetric
+    /// key homomorphically and hashes it with SHA3-256.
+    /// Returns the server along with the bundle to send off-chain to the client
+    pub fn new(data: &[u8]) -> Result<(Self, CiphertextBundle), FdeError> {
+        Self::with_hash_function(data, HashFunction::Sha3_256)
+    }
+
+    /// Same as `new`, with the data padded for and the symmetric key hashed with `hash_function`
+    pub fn with_hash_function(data: &[u8], hash_function: HashFunction) -> Result<(Self, CiphertextBundle), FdeError> {
+        Self::with_cipher(data, hash_function, SymmetricCipher::default())
+    }
+
+    /// Same as `with_hash_function`, with the data encrypted by `cipher`, which is proposed to
+    /// the client in the bundle
+    pub fn with_cipher(data: &[u8], hash_function: HashFunction, cipher: SymmetricCipher) -> Result<(Self, CiphertextBundle), FdeError> {
+        Self::with_files(&[data], hash_function, cipher)
+    }
+
+    /// Same as `with_cipher` for a batch of files, bought together in one exchange: each file is
+    /// padded and encrypted under the same symmetric key, with its own random iv
+    pub fn with_files(files: &[&[u8]], hash_function: HashFunction, cipher: SymmetricCipher) -> Result<(Self, CiphertextBundle), FdeError> {
+        let (sym_key, _, buf_sym_key) = get_rand_key_iv_n(cipher.key_len() / 8, 0);
+        let files = files.iter()
+            .map(|data| {
+                let padded_input = hash_function.sponge().pad_bytes(data);
+                let (_, iv, _) = get_rand_key_iv_n(0, cipher.iv_len() / 8);
+                Ok(EncryptedFile { sym_ct: cipher.symmetric_enc(padded_input, &sym_key, &iv)?, iv, proof: None })
+            })
+            .collect::<Result<Vec<_>, FdeError>>()?;
+        Ok(Self::from_files(files, sym_key, &buf_sym_key, hash_function, cipher))
+    }
+
+    /// Same as `with_files` for the chunks of `chunk_size` bytes of `data` requested by the client,
+    /// the root of their Merkle tree being the commitment to the data. Each chunk is encrypted
+    /// without padding, with its own random iv, and sent along with its Merkle proof
+    pub fn with_chunks(data: &[u8], chunk_size: usize, request: &ChunkRequest, hash_function: HashFunction, cipher: SymmetricCipher) -> Result<(Self, CiphertextBundle), FdeError> {
+        let tree = MerkleTree::new(data, chunk_size, hash_function);
+        let (sym_key, _, buf_sym_key) = get_rand_key_iv_n(cipher.key_len() / 8, 0);
+        let files = request.indices.iter()
+            .map(|&index| {
+                let chunk = bytes_to_bits(tree.chunk(data, index)?);
+                let (_, iv, _) = get_rand_key_iv_n(0, cipher.iv_len() / 8);
+                Ok(EncryptedFile { sym_ct: cipher.symmetric_enc(chunk, &sym_key, &iv)?, iv, proof: Some(tree.proof(index)?) })
+            })
+            .collect::<Result<Vec<_>, FdeError>>()?;
+        Ok(Self::from_files(files, sym_key, &buf_sym_key, hash_function, cipher))
+    }
+
+    /// Same as `with_hash_function`, for data already encrypted with AES-128 in counter mode
+    /// under `key`, from the counter block `iv`. The archive is sent as it is, only its padding
+    /// being encrypted with the keystream which follows the one of the data, so the client
+    /// decrypts it like any data encrypted with `SymmetricCipher::Aes128Ctr`
+    pub fn with_aes_ctr_archive(archive: &[u8], key: [u8; 16], iv: [u8; 16], hash_function: HashFunction) -> Result<(Self, CiphertextBundle), FdeError> {
+        let (sym_key, iv) = (bytes_to_bits(&key), bytes_to_bits(&iv));
+        let padding = bytes_to_bits(&hash_function.sponge().padding(archive.len()));
+
+        // the keystream of the padding starts in the block of the last byte of the archive
+        let offset = 8 * (archive.len() % 16);
+        let mut stream = AesCtrStream::<bool>::new(to_array(&sym_key), to_array(&iv), &());
+        stream.skip_blocks((archive.len() / 16) as u128);
+        let keystream = stream.next_n(offset + padding.len());
+
+        let mut sym_ct = bytes_to_bits(archive);
+        sym_ct.extend(padding.iter().zip(&keystream[offset..]).map(|(&p, &k)| p ^ k));
+        Ok(Self::from_files(vec![EncryptedFile { sym_ct, iv, proof: None }], sym_key, &key, hash_function, SymmetricCipher::Aes128Ctr))
+    }
+
+    // Encrypts the symmetric key homomorphically and hashes its bytes, and returns the server
+    // with the bundle of the symmetrically encrypted padded files
+    fn from_files(files: Vec<EncryptedFile>, sym_key: Vec<bool>, key_bytes: &[u8], hash_function: HashFunction, cipher: SymmetricCipher) -> (Self, CiphertextBundle) {
+        let (ck, sk) = gen_keys();
+        let key_ct = encrypt_bools(sym_key.clone(), &ck);
+        let hash_key = hash_function.hex_digest(key_bytes);
+
+        let server = Server2 {
+            state: Server2State::AwaitingSubmission,
+            secret_key: ck,
+            sym_key,
+            submission: None,
+            hash_function,
+        };
+        (server, CiphertextBundle { files, key_ct, hash_key, pk: sk, hash_function, cipher })
+    }
+
+    pub fn state(&self) -> Server2State {
+        self.state
+    }
+
+    /// Stores (Ha, Hk) relayed by the smart contract
+    pub fn on_submission(&mut self, msg: Submission) -> Result<(), FdeError> {
+        check_state(self.state, Server2State::AwaitingSubmission, "Server2 received a submission")?;
+        self.submission = Some(msg);
+        self.state = Server2State::AwaitingChallenge;
+        Ok(())
+    }
+
+    /// Decrypts the challenge to â and runs VerifyKA. Reveals (k, â) if it succeeded, otherwise
+    /// the server aborts and sends all-zero values (also when the challenge is malformed)
+    pub fn on_challenge(&mut self, msg: Challenge) -> Result<Reveal, FdeError> {
+        check_state(self.state, Server2State::AwaitingChallenge, "Server2 received a challenge")?;
+        let submission = self.submission.take().unwrap();
+        let a = decrypt_bools(&msg.chal, &self.secret_key);
+
+        let verified = check_len("chal", 256, a.len()).is_ok()
+            && verify_ka_in(self.hash_function, submission.field, submission.hash_a, submission.hash_k, a.clone(), self.sym_key.to_vec()).is_ok();
+        if verified {
+            self.state = Server2State::Revealed;
+            Ok(Reveal { status: SUCCESS, key: self.sym_key.clone(), a })
+        } else {
+            self.state = Server2State::Aborted;
+            Ok(Reveal { status: ABORT, key: vec![false; self.sym_key.len()], a: vec![false; 256] })
+        }
+    }
+
+    /// Records the final status sent by the smart contract
+    pub fn on_settlement(&mut self, msg: &Settlement) -> Result<(), FdeError> {
+        if self.state != Server2State::Aborted {
+            check_state(self.state, Server2State::Revealed, "Server2 received a settlement")?;
+        }
+        self.state = if msg.status == SUCCESS { Server2State::Settled } else { Server2State::Aborted };
+        Ok(())
+    }
+}
+
+// ------------------------------------------ CLIENT -----------------------------------------------
+
+/// The states of the client in Protocol II
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Client2State {
+    AwaitingCiphertext,
+    ChallengeSent,
+    Settled,
+    Aborted,
+}
+
+// What the client of Protocol II buys
+enum Purchase {
+    // Whole files, with their expected hex digests
+    Files(Vec<String>),
+    // Chunks of data committed to by the hex root of its Merkle tree
+    Chunks { root: String, indices: Vec<usize> },
+}
+
+/// The client of Protocol II: it knows the hashes of the files it wants to buy, or the Merkle root
+/// of the data whose chunks it wants to buy
+pub struct Client2 {
+    state: Client2State,
+    purchase: Purchase,
+    files: Vec<EncryptedFile>,
+    hash_function: HashFunction,
+    cipher: SymmetricCipher,
+    accepted_ciphers: Vec<SymmetricCipher>,
+    field: ChallengeField,
+    payer: String,
+    amount: u64,
+}
+
+impl Client2 {
+    /// Creates a client buying the data whose SHA3-256 hex digest is `hash`
+    pub fn new(hash: String) -> Self {
+        Self::new_batch(vec![hash])
+    }
+
+    /// Creates a client buying in one exchange the files whose hex digests are `hashes`, in the
+    /// order of the files of the bundle
+    pub fn new_batch(hashes: Vec<String>) -> Self {
+        Self::from_purchase(Purchase::Files(hashes))
+    }
+
+    /// Creates a client buying the chunks `indices` of the data whose Merkle root is `root` (hex),
+    /// in the chunked variant : the client sends its `chunk_request` to the server first
+    pub fn new_chunks(root: String, indices: Vec<usize>) -> Self {
+        Self::from_purchase(Purchase::Chunks { root, indices })
+    }
+
+    fn from_purchase(purchase: Purchase) -> Self {
+        Client2 {
+            state: Client2State::AwaitingCiphertext,
+            purchase,
+            files: vec![],
+            hash_function: HashFunction::default(),
+            cipher: SymmetricCipher::default(),
+            accepted_ciphers: SymmetricCipher::ALL.to_vec(),
+            field: ChallengeField::default(),
+            payer: String::from("client"),
+            amount: 0,
+        }
+    }
+
+    /// Pays `amount` from the account `payer` when submitting to the smart contract
+    pub fn with_payment(self, payer: String, amount: u64) -> Self {
+        Client2 { payer, amount, ..self }
+    }
+
+    /// Only goes on with the exchange if the server proposes one of `accepted_ciphers` (all the
+    /// supported ciphers by default), e.g. only Kreyvium for 128-bit security
+    pub fn with_accepted_ciphers(self, accepted_ciphers: Vec<SymmetricCipher>) -> Self {
+        Client2 { accepted_ciphers, ..self }
+    }
+
+    /// Computes the challenge over `field` (modulo 2^256 by default), e.g. a prime field so that
+    /// no difference of hashes can be annihilated by the random coefficients
+    pub fn with_challenge_field(self, field: ChallengeField) -> Self {
+        Client2 { field, ..self }
+    }
+
+    pub fn state(&self) -> Client2State {
+        self.state
+    }
+
+    /// Returns the request to send to the server in the chunked variant (`None` for whole files)
+    pub fn chunk_request(&self) -> Option<ChunkRequest> {
+        match &self.purchase {
+            Purchase::Files(_) => None,
+            Purchase::Chunks { indices, .. } => Some(ChunkRequest { indices: indices.clone() }),
+        }
+    }
+
+    // Returns the expected hash of every file of the bundle : the root of the tree for a chunk
+    fn expected_hashes(&self) -> Vec<&str> {
+        match &self.purchase {
+            Purchase::Files(hashes) => hashes.iter().map(String::as_str).collect(),
+            Purchase::Chunks { root, indices } => vec![root.as_str(); indices.len()],
+        }
+    }
+
+    /// Runs CreateChal: decrypts the files homomorphically, hashes them and the symmetric key
+    /// homomorphically, and folds all the hashes in an encrypted challenge, each with its own
+    /// random coefficient. A chunk is hashed as a Merkle leaf, and its path to the root.
+    /// Returns the challenge to send off-chain to the server, and (Ha, Hk) to send on-chain to
+    /// the smart contract
+    pub fn on_ciphertext(&mut self, msg: CiphertextBundle) -> Result<(Challenge, Submission), FdeError> {
+        check_state(self.state, Client2State::AwaitingCiphertext, "Client2 received a ciphertext")?;
+        if !self.accepted_ciphers.contains(&msg.cipher) {
+            return Err(FdeError::UnsupportedCipher(msg.cipher.to_string()));
+        }
+        let expected_hashes = self.expected_hashes();
+        check_len("files", expected_hashes.len(), msg.files.len())?;
+        for (i, file) in msg.files.iter().enumerate() {
+            match &self.purchase {
+                Purchase::Files(_) => check_padded_len(file.sym_ct.len(), msg.hash_function.sponge().rate())?,
+                Purchase::Chunks { indices, .. } => check_chunk(file, indices[i])?,
+            }
+            check_len("iv", msg.cipher.iv_len(), file.iv.len())?;
+        }
+        check_len("k_ct", msg.cipher.key_len(), msg.key_ct.len())?;
+        let sym_key_hash_bits = hex_to_bits_256(&msg.hash_key)?;
+        let data_hash_bits = expected_hashes.iter().map(|hash| hex_to_bits_256(hash)).collect::<Result<Vec<_>, FdeError>>()?;
+        let pk = &msg.pk;
+        let sponge = msg.hash_function.sponge();
+
+        // 1 : decrypt the files homomorphically, absorbing each block in the hash of its file as
+        // soon as it is decrypted. A chunk is decrypted as a whole, and hashed up to the root of
+        // the Merkle tree with its proof
+        let mut data_hash_comp: Vec<[Ciphertext; 256]> = Vec::with_capacity(msg.files.len());
+        for file in &msg.files {
+            let hash = match &file.proof {
+                None => {
+                    let mut hasher = sponge.hasher(pk);
+                    msg.cipher.homomorphic_dec_blocks(&file.sym_ct, msg.key_ct.clone(), &file.iv, sponge.rate(), pk, |block| {
+                        hasher.update(&block, pk)
+                    })?;
+                    hasher.finalize(pk)?.try_into().unwrap()
+                }
+                Some(proof) => {
+                    let mut chunk = Vec::with_capacity(file.sym_ct.len());
+                    msg.cipher.homomorphic_dec_blocks(&file.sym_ct, msg.key_ct.clone(), &file.iv, sponge.rate(), pk, |block| {
+                        chunk.extend(block)
+                    })?;
+                    let leaf = merkle_leaf_fhe(&chunk, msg.hash_function, pk)?;
+                    merkle_root_fhe(leaf, proof, msg.hash_function, pk)?
+                }
+            };
+            data_hash_comp.push(hash);
+        }
+
+        // 2 : hash the symmetric key homomorphically
+        let padded_sym_key = sponge.pad_cipher(msg.key_ct, pk)?;
+        let key_hash_comp: [Ciphertext; 256] = sponge.hash(&padded_sym_key, pk)?.try_into().unwrap();
+
+        // 3 : compute the challenge with the expected plaintext hashes
+        let mut pairs = vec![(&key_hash_comp, &sym_key_hash_bits)];
+        pairs.extend(data_hash_comp.iter().zip(&data_hash_bits));
+        let (a, coefs) = self.field.rand_coefficients(pairs.len());
+        let chal = self.field.compute_challenge_n(&pairs, &a, &coefs, pk)?;
+
+        self.files = msg.files;
+        self.hash_function = msg.hash_function;
+        self.cipher = msg.cipher;
+        self.state = Client2State::ChallengeSent;
+        Ok((
+            Challenge { chal: chal.to_vec() },
+            Submission {
+                hash_a: msg.hash_function.hash_from_vec_bool(a.to_vec()),
+                hash_k: msg.hash_key,
+                field: self.field,
+                payer: self.payer.clone(),
+                amount: self.amount,
+            },
+        ))
+    }
+
+    /// Reads the final status of the smart contract. On success, decrypts the data with the
+    /// revealed symmetric key and returns it, if it has the expected hash (the files one after the
+    /// other for a batch, see `on_settlement_files`)
+    pub fn on_settlement(&mut self, msg: &Settlement) -> Result<Vec<u8>, FdeError> {
+        Ok(self.on_settlement_files(msg)?.concat())
+    }
+
+    /// Same as `on_settlement`, returning every file of the batch
+    pub fn on_settlement_files(&mut self, msg: &Settlement) -> Result<Vec<Vec<u8>>, FdeError> {
+        check_state(self.state, Client2State::ChallengeSent, "Client2 received a settlement")?;
+        if msg.status != SUCCESS {
+            self.state = Client2State::Aborted;
+            return Err(FdeError::PeerAborted);
+        }
+        self.state = Client2State::Settled;
+
+        check_len("k", self.cipher.key_len(), msg.key.len())?;
+        self.files.iter()
+            .zip(self.expected_hashes())
+            .map(|(file, hash)| {
+                let data_dec = self.cipher.symmetric_enc(file.sym_ct.clone(), &msg.key, &file.iv)?;
+                match &file.proof {
+                    None => {
+                        let unpadded_data = self.hash_function.sponge().unpad_bytes(data_dec.as_slice())?;
+                        if self.hash_function.hex_digest(unpadded_data.as_slice()) == hash { Ok(unpadded_data) } else { Err(FdeError::HashMismatch) }
+                    }
+                    Some(proof) => {
+                        let chunk = bits_to_bytes_lsb(&data_dec);
+                        proof.verify(&chunk, hash, self.hash_function)?;
+                        Ok(chunk)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+// --------------------------------------- SMART CONTRACT ------------------------------------------
+
+/// The states of the smart contract in Protocol II
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Contract2State {
+    AwaitingSubmission,
+    Committed,
+    Settled,
+    Aborted,
+}
+
+/// The smart contract of Protocol II: it stores (Ha, Hk), holds the client's payment in escrow
+/// and verifies (k, â) revealed by the server
+pub struct Contract2 {
+    state: Contract2State,
+    submission: Option<Submission>,
+    reveal_deadline: Option<Duration>,
+    deadline: Option<Instant>,
+    events: Vec<ContractEvent>,
+    ledger: Ledger,
+    terms: Terms,
+    escrow: Option<EscrowId>,
+    hash_function: HashFunction,
+}
+
+impl Default for Contract2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Contract2 {
+    /// Creates a contract for a free exchange, which waits for the server's reveal without a deadline
+    pub fn new() -> Self {
+        Contract2 {
+            state: Contract2State::AwaitingSubmission,
+            submission: None,
+            reveal_deadline: None,
+            deadline: None,
+            events: vec![],
+            ledger: Ledger::new(),
+            terms: Terms::default(),
+            escrow: None,
+            hash_function: HashFunction::default(),
+        }
+    }
+
+    /// The server must reveal (k, â) within `reveal_deadline` of the client's submission,
+    /// otherwise the client is refunded
+    pub fn with_deadline(self, reveal_deadline: Duration) -> Self {
+        Contract2 { reveal_deadline: Some(reveal_deadline), ..self }
+    }
+
+    /// The client pays at least `terms.price` from its account on `ledger`, which is released to
+    /// `terms.seller` if VerifyKA succeeds
+    pub fn with_ledger(self, ledger: Ledger, terms: Terms) -> Self {
+        Contract2 { ledger, terms, ..self }
+    }
+
+    /// VerifyKA checks (Ha, Hk) with `hash_function`, e.g. the `keccak256` of the chain
+    pub fn with_hash_function(self, hash_function: HashFunction) -> Self {
+        Contract2 { hash_function, ..self }
+    }
+
+    pub fn state(&self) -> Contract2State {
+        self.state
+    }
+
+    /// Returns the instant before which the server must reveal, once the client submitted
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the events emitted so far
+    pub fn events(&self) -> &[ContractEvent] {
+        &self.events
+    }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Stores (Ha, Hk) sent by the client and locks its payment in escrow. Rejects the submission
+    /// if the payment is below the price or the client cannot pay it
+    pub fn on_submission(&mut self, msg: Submission) -> Result<(), FdeError> {
+        check_state(self.state, Contract2State::AwaitingSubmission, "Contract2 received a submission")?;
+        if msg.amount < self.terms.price {
+            return Err(FdeError::InsufficientFunds { needed: self.terms.price, available: msg.amount });
+        }
+        self.escrow = Some(self.ledger.escrow(&msg.payer, &self.terms.seller, msg.amount)?);
+        self.submission = Some(msg);
+        self.deadline = self.reveal_deadline.map(|d| Instant::now() + d);
+        self.state = Contract2State::Committed;
+        Ok(())
+    }
+
+    /// Runs VerifyKA on (k, â) revealed by the server, if the server did not abort and revealed
+    /// before the deadline.
+    /// Returns the final status, which is sent to both the client and the server
+    pub fn on_reveal(&mut self, msg: Reveal) -> Result<Settlement, FdeError> {
+        check_state(self.state, Contract2State::Committed, "Contract2 received a reveal")?;
+        if self.deadline_passed() {
+            return self.abort(AbortReason::Deadline);
+        }
+        if msg.status != SUCCESS {
+            return self.abort(AbortReason::ServerAborted);
+        }
+        let submission = self.submission.take().unwrap();
+        if verify_ka_in(self.hash_function, submission.field, submission.hash_a, submission.hash_k, msg.a, msg.key.clone()).is_err() {
+            return self.abort(AbortReason::VerificationFailed);
+        }
+        if let Some(escrow) = self.escrow.take() {
+            self.ledger.release(escrow)?;
+        }
+        self.state = Contract2State::Settled;
+        self.events.push(ContractEvent::Settled);
+        Ok(Settlement { status: SUCCESS, key: msg.key })
+    }
+
+    /// Aborts the exchange once the deadline passed without a reveal from the server, the client is
+    /// refunded. Returns the final status, which is sent to both the client and the server
+    pub fn on_deadline(&mut self) -> Result<Settlement, FdeError> {
+        check_state(self.state, Contract2State::Committed, "Contract2 reached a deadline")?;
+        if !self.deadline_passed() {
+            return Err(FdeError::UnexpectedMessage(String::from("the deadline of Contract2 has not passed")));
+        }
+        self.abort(AbortReason::Deadline)
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    // Aborts the exchange and refunds the client
+    fn abort(&mut self, reason: AbortReason) -> Result<Settlement, FdeError> {
+        if let Some(escrow) = self.escrow.take() {
+            self.ledger.refund(escrow)?;
+        }
+        self.submission = None;
+        self.state = Contract2State::Aborted;
+        self.events.push(ContractEvent::Refund(reason));
+        Ok(Settlement { status: ABORT, key: vec![] })
+    }
+}
+
+// ------------------------------------------ HELPERS ----------------------------------------------
+
+/// Returns a random key and iv, both 80-bit bit strings, and the bytes of the key
+pub fn get_rand_key_iv()->([bool; 80], [bool; 80], [u8; 10]){
+    let (key_bits, iv_bits, buf_key) = get_rand_key_iv_n(10, 10);
+    (key_bits.try_into().unwrap(), iv_bits.try_into().unwrap(), buf_key.try_into().unwrap())
+}
+
+/// Returns a random key of `key_bytes` bytes and a random iv of `iv_bytes` bytes as bit strings,
+/// and the bytes of the key
+pub fn get_rand_key_iv_n(key_bytes: usize, iv_bytes: usize)->(Vec<bool>, Vec<bool>, Vec<u8>){
+    let mut buf_key = vec![0u8; key_bytes];
+    rand::thread_rng().fill(&mut buf_key[..]);
+    let mut buf_iv = vec![0u8; iv_bytes];
+    rand::thread_rng().fill(&mut buf_iv[..]);
+    (bytes_to_bits(&buf_key), bytes_to_bits(&buf_iv), buf_key)
+}
+
+// Returns the bits of `bytes`, lsb first in each byte
+fn bytes_to_bits(bytes: &[u8]) -> Vec<bool> {
+    bytes.iter().flat_map(|byte| (0..8).map(move |bit_in_byte| (byte >> bit_in_byte) & 1 == 1)).collect()
+}
+
+// Checks that an encrypted chunk holds whole bytes, and the Merkle proof of the requested index
+fn check_chunk(file: &EncryptedFile, index: usize) -> Result<(), FdeError> {
+    check_len("chunk (in bits)", file.sym_ct.len().div_ceil(8) * 8, file.sym_ct.len())?;
+    match &file.proof {
+        Some(proof) if proof.index == index => Ok(()),
+        Some(proof) => Err(FdeError::UnexpectedMessage(format!("the proof of chunk {} instead of chunk {}", proof.index, index))),
+        None => Err(FdeError::UnexpectedMessage(format!("no Merkle proof for chunk {}", index))),
+    }
+}
+
+// Converts 128 bits to the array expected by AES
+fn to_array(bits: &[bool]) -> [bool; 128] {
+    bits.try_into().unwrap()
+}
+
+/// Returns a triple of random bit strings
+pub fn get_rand_abc()->([bool; 256], [bool; 256], [bool; 256]){
+    let mut buf_a = [0u8; 32];
+    rand::thread_rng().fill(&mut buf_a[..]);
+    let mut buf_b = [0u8; 32];
+    rand::thread_rng().fill(&mut buf_b[..]);
+    let mut buf_c = [0u8; 32];
+    rand::thread_rng().fill(&mut buf_c[..]);
+
+    let mut a: [bool;256] = [false; 256];
+    let mut b: [bool;256] = [false; 256];
+    let mut c: [bool;256] = [false; 256];
+
+    for (byte_idx, ((byte_a, byte_b), byte_c)) in buf_a.iter().zip(buf_b).zip(buf_c).enumerate() {
+        for bit_in_byte in 0..8 {
+            let mask = 1 << (bit_in_byte);
+            let bool_a = (byte_a & mask) != 0;
+            let bool_b = (byte_b & mask) != 0;
+            let bool_c = (byte_c & mask) != 0;
+            a[byte_idx * 8 + bit_in_byte] = bool_a;
+            b[byte_idx * 8 + bit_in_byte] = bool_b;
+            c[byte_idx * 8 + bit_in_byte] = bool_c;
+        }
+    }
+    (a, b, c)
+}
+
+// Gets the bits (lsb first in each byte) of a 256-bit hex digest
+fn hex_to_bits_256(hash: &str) -> Result<[bool; 256], FdeError> {
+    let bytes = hex::decode(hash)?;
+    check_len("hash (in bytes)", 32, bytes.len())?;
+    let bits: Vec<bool> = bytes.iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1u8 == 1u8)).collect();
+    Ok(bits.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homomorphic_functions::{hex_sha3, sha3_hash_from_vec_bool, symmetric_enc};
+
+    fn paying_submission(a: &[bool; 256], buf_key: &[u8], amount: u64) -> Submission {
+        Submission {
+            hash_a: sha3_hash_from_vec_bool(a.to_vec()),
+            hash_k: hex_sha3(buf_key),
+            field: ChallengeField::Mod2_256,
+            payer: String::from("client"),
+            amount,
+        }
+    }
+
+    #[test]
+    fn test_rand_key_hash() {
+        // the hash of the key bytes sent to the client must match the hash checked by verify_ka
+        let (key, _, buf_key) = get_rand_key_iv();
+        assert_eq!(sha3_hash_from_vec_bool(key.to_vec()), hex_sha3(&buf_key));
+    }
+
+    #[test]
+    fn test_contract_settlement() {
+        let (key, _, buf_key) = get_rand_key_iv();
+        let (a, _, _) = get_rand_abc();
+        let submission = paying_submission(&a, &buf_key, 0);
+
+        // an honest reveal releases the key
+        let mut contract = Contract2::new();
+        contract.on_submission(submission.clone()).unwrap();
+        let settlement = contract.on_reveal(Reveal { status: SUCCESS, key: key.to_vec(), a: a.to_vec() }).unwrap();
+        assert_eq!(settlement.status, SUCCESS);
+        assert_eq!(settlement.key, key.to_vec());
+        assert_eq!(contract.state(), Contract2State::Settled);
+        assert_eq!(contract.events(), &[ContractEvent::Settled]);
+
+        // a wrong â makes the contract abort
+        let mut contract = Contract2::new();
+        contract.on_submission(submission.clone()).unwrap();
+        let settlement = contract.on_reveal(Reveal { status: SUCCESS, key: key.to_vec(), a: vec![false; 256] }).unwrap();
+        assert_eq!(settlement.status, ABORT);
+        assert!(settlement.key.is_empty());
+        assert_eq!(contract.state(), Contract2State::Aborted);
+        assert_eq!(contract.events(), &[ContractEvent::Refund(AbortReason::VerificationFailed)]);
+
+        // a message that comes out of order is rejected without crashing the contract
+        assert!(matches!(contract.on_submission(submission), Err(FdeError::UnexpectedMessage(_))));
+        let mut contract = Contract2::new();
+        let reveal = Reveal { status: SUCCESS, key: key.to_vec(), a: a.to_vec() };
+        assert!(matches!(contract.on_reveal(reveal), Err(FdeError::UnexpectedMessage(_))));
+        assert_eq!(contract.state(), Contract2State::AwaitingSubmission);
+    }
+
+    #[test]
+    fn test_contract_deadline() {
+        let (key, _, buf_key) = get_rand_key_iv();
+        let (a, _, _) = get_rand_abc();
+        let submission = paying_submission(&a, &buf_key, 0);
+        let reveal = Reveal { status: SUCCESS, key: key.to_vec(), a: a.to_vec() };
+
+        // the deadline cannot be enforced before it passes
+        let mut contract = Contract2::new().with_deadline(Duration::from_secs(3600));
+        assert!(contract.deadline().is_none());
+        contract.on_submission(submission.clone()).unwrap();
+        assert!(contract.deadline().is_some());
+        assert!(matches!(contract.on_deadline(), Err(FdeError::UnexpectedMessage(_))));
+        assert_eq!(contract.on_reveal(reveal.clone()).unwrap().status, SUCCESS);
+
+        // the server never reveals, the client is refunded
+        let mut contract = Contract2::new().with_deadline(Duration::ZERO);
+        contract.on_submission(submission.clone()).unwrap();
+        let settlement = contract.on_deadline().unwrap();
+        assert_eq!(settlement.status, ABORT);
+        assert!(settlement.key.is_empty());
+        assert_eq!(contract.events(), &[ContractEvent::Refund(AbortReason::Deadline)]);
+        assert!(matches!(contract.on_reveal(reveal.clone()), Err(FdeError::UnexpectedMessage(_))));
+
+        // a reveal after the deadline is not accepted
+        let mut contract = Contract2::new().with_deadline(Duration::ZERO);
+        contract.on_submission(submission).unwrap();
+        assert_eq!(contract.on_reveal(reveal).unwrap().status, ABORT);
+        assert_eq!(contract.events(), &[ContractEvent::Refund(AbortReason::Deadline)]);
+    }
+
+    #[test]
+    fn test_contract_keccak256() {
+        let (key, _, buf_key) = get_rand_key_iv();
+        let (a, _, _) = get_rand_abc();
+        let keccak = HashFunction::Keccak256;
+        let submission = Submission {
+            hash_a: keccak.hash_from_vec_bool(a.to_vec()),
+            hash_k: keccak.hex_digest(&buf_key),
+            ..paying_submission(&a, &buf_key, 0)
+        };
+        let reveal = Reveal { status: SUCCESS, key: key.to_vec(), a: a.to_vec() };
+
+        // (Ha, Hk) computed with keccak256 only verify with keccak256
+        let mut contract = Contract2::new().with_hash_function(keccak);
+        contract.on_submission(submission.clone()).unwrap();
+        assert_eq!(contract.on_reveal(reveal.clone()).unwrap().status, SUCCESS);
+        let mut contract = Contract2::new();
+        contract.on_submission(submission).unwrap();
+        assert_eq!(contract.on_reveal(reveal).unwrap().status, ABORT);
+    }
+
+    #[test]
+    fn test_contract_challenge_field() {
+        let (key, _, buf_key) = get_rand_key_iv();
+        let field = ChallengeField::Secp256k1;
+        for (a, status) in [(field.rand_abc().0, SUCCESS), ([true; 256], ABORT)] {
+            // â = 2^256 - 1 has the expected hash, but it is not an element of the field
+            let submission = Submission { field, ..paying_submission(&a, &buf_key, 0) };
+            let reveal = Reveal { status: SUCCESS, key: key.to_vec(), a: a.to_vec() };
+            let mut contract = Contract2::new();
+            contract.on_submission(submission).unwrap();
+            assert_eq!(contract.on_reveal(reveal).unwrap().status, status);
+        }
+        assert!(matches!(verify_ka_in(HashFunction::Sha3_256, field, String::new(), String::new(), vec![true; 256], vec![]),
+            Err(FdeError::NotInField(_))));
+    }
+
+    #[test]
+    fn test_contract_escrow() {
+        let (key, _, buf_key) = get_rand_key_iv();
+        let (a, _, _) = get_rand_abc();
+        let mut ledger = Ledger::new();
+        ledger.deposit("client", 100);
+        let terms = Terms { seller: String::from("server"), price: 100 };
+
+        // an honest reveal pays the server
+        let mut contract = Contract2::new().with_ledger(ledger.clone(), terms.clone());
+        assert!(matches!(contract.on_submission(paying_submission(&a, &buf_key, 99)),
+            Err(FdeError::InsufficientFunds { needed: 100, available: 99 })));
+        contract.on_submission(paying_submission(&a, &buf_key, 100)).unwrap();
+        assert_eq!(contract.ledger().balance("client"), 0);
+        assert_eq!(contract.ledger().balance("server"), 0);
+        contract.on_reveal(Reveal { status: SUCCESS, key: key.to_vec(), a: a.to_vec() }).unwrap();
+        assert_eq!(contract.ledger().balance("server"), 100);
+        assert_eq!(contract.ledger().total(), 100);
+
+        // the server never reveals, the client gets its payment back
+        let mut contract = Contract2::new().with_deadline(Duration::ZERO).with_ledger(ledger, terms);
+        contract.on_submission(paying_submission(&a, &buf_key, 100)).unwrap();
+        contract.on_deadline().unwrap();
+        assert_eq!(contract.ledger().balance("client"), 100);
+        assert_eq!(contract.ledger().balance("server"), 0);
+    }
+
+    #[test]
+    fn test_client_rejects_malformed_messages() {
+        let mut client = Client2::new(hex_sha3(b"data"));
+        assert!(matches!(client.on_settlement(&Settlement { status: ABORT, key: vec![] }),
+            Err(FdeError::UnexpectedMessage(_))));
+
+        // a settlement with a truncated key or an abort status does not crash the client
+        client.state = Client2State::ChallengeSent;
+        client.files = vec![EncryptedFile { sym_ct: vec![false; 1088], iv: vec![false; 80], proof: None }];
+        assert!(matches!(client.on_settlement(&Settlement { status: SUCCESS, key: vec![true; 3] }),
+            Err(FdeError::BadLength { what: "k", expected: 80, got: 3 })));
+        client.state = Client2State::ChallengeSent;
+        assert!(matches!(client.on_settlement(&Settlement { status: ABORT, key: vec![] }),
+            Err(FdeError::PeerAborted)));
+
+        assert!(matches!(hex_to_bits_256("not hex"), Err(FdeError::Deserialize(_))));
+        assert!(matches!(hex_to_bits_256("00ff"), Err(FdeError::BadLength { .. })));
+    }
+
+    #[test]
+    fn test_cipher_negotiation() {
+        let data = b"data bought with a 128-bit key";
+        let (server, bundle) = Server2::with_cipher(data, HashFunction::Sha3_256, SymmetricCipher::Kreyvium).unwrap();
+        assert_eq!(bundle.cipher, SymmetricCipher::Kreyvium);
+        assert_eq!((bundle.key_ct.len(), bundle.files[0].iv.len()), (128, 128));
+
+        // a client which only accepts Trivium stops before any homomorphic computation
+        let mut client = Client2::new(hex_sha3(data)).with_accepted_ciphers(vec![SymmetricCipher::Trivium]);
+        assert!(matches!(client.on_ciphertext(bundle.clone()), Err(FdeError::UnsupportedCipher(_))));
+        assert_eq!(client.state(), Client2State::AwaitingCiphertext);
+
+        // the revealed 128-bit key decrypts the data with Kreyvium
+        let mut client = Client2::new(hex_sha3(data));
+        client.state = Client2State::ChallengeSent;
+        client.files = bundle.files;
+        client.cipher = bundle.cipher;
+        assert!(matches!(client.on_settlement(&Settlement { status: SUCCESS, key: vec![true; 80] }),
+            Err(FdeError::BadLength { what: "k", expected: 128, got: 80 })));
+        client.state = Client2State::ChallengeSent;
+        let settlement = Settlement { status: SUCCESS, key: server.sym_key.clone() };
+        assert_eq!(client.on_settlement(&settlement).unwrap(), data.to_vec());
+    }
+
+    #[test]
+    fn test_batch_files() {
+        let files: [&[u8]; 3] = [b"first file", b"second file", b"the third file of the batch"];
+        let hashes: Vec<String> = files.iter().map(|data| hex_sha3(data)).collect();
+        let (server, bundle) = Server2::with_files(&files, HashFunction::Sha3_256, SymmetricCipher::Trivium).unwrap();
+        assert_eq!(bundle.files.len(), 3);
+        assert!(bundle.files[0].iv != bundle.files[1].iv && bundle.files[1].iv != bundle.files[2].iv);
+
+        // a client expecting another number of files stops before any homomorphic computation
+        let mut client = Client2::new_batch(hashes[..2].to_vec());
+        assert!(matches!(client.on_ciphertext(bundle.clone()), Err(FdeError::BadLength { what: "files", expected: 2, got: 3 })));
+
+        // the revealed key decrypts every file, which must have the expected hash
+        let settlement = Settlement { status: SUCCESS, key: server.sym_key.clone() };
+        let mut client = Client2::new_batch(hashes.clone());
+        client.state = Client2State::ChallengeSent;
+        client.files = bundle.files.clone();
+        let expected: Vec<Vec<u8>> = files.iter().map(|data| data.to_vec()).collect();
+        assert_eq!(client.on_settlement_files(&settlement).unwrap(), expected);
+
+        let mut client = Client2::new_batch(vec![hashes[1].clone(), hashes[0].clone(), hashes[2].clone()]);
+        client.state = Client2State::ChallengeSent;
+        client.files = bundle.files;
+        assert!(matches!(client.on_settlement_files(&settlement), Err(FdeError::HashMismatch)));
+    }
+
+    #[test]
+    fn test_chunked_exchange() {
+        let data: Vec<u8> = (0..100).collect();
+        let tree = MerkleTree::new(&data, 16, HashFunction::Sha3_256);
+        let request = ChunkRequest { indices: vec![6, 2] };
+        let (server, bundle) = Server2::with_chunks(&data, 16, &request, HashFunction::Sha3_256, SymmetricCipher::Trivium).unwrap();
+        assert_eq!(bundle.files[0].sym_ct.len(), 4 * 8);
+        assert_eq!(bundle.files[1].proof, Some(tree.proof(2).unwrap()));
+        assert!(matches!(Server2::with_chunks(&data, 16, &ChunkRequest { indices: vec![7] }, HashFunction::Sha3_256, SymmetricCipher::Trivium),
+            Err(FdeError::UnknownChunk { index: 7, chunks: 7 })));
+
+        // the client checks that the proofs are those of the chunks it requested before decrypting
+        let mut client = Client2::new_chunks(tree.hex_root(), vec![2, 6]);
+        assert_eq!(client.chunk_request(), Some(ChunkRequest { indices: vec![2, 6] }));
+        assert!(matches!(client.on_ciphertext(bundle.clone()), Err(FdeError::UnexpectedMessage(_))));
+        assert_eq!(Client2::new(tree.hex_root()).chunk_request(), None);
+
+        // the revealed key decrypts every chunk, which must be a leaf of the tree
+        let settlement = Settlement { status: SUCCESS, key: server.sym_key.clone() };
+        let mut client = Client2::new_chunks(tree.hex_root(), request.indices.clone());
+        client.state = Client2State::ChallengeSent;
+        client.files = bundle.files.clone();
+        assert_eq!(client.on_settlement_files(&settlement).unwrap(), vec![data[96..].to_vec(), data[32..48].to_vec()]);
+
+        let mut client = Client2::new_chunks(tree.hex_root(), request.indices);
+        client.state = Client2State::ChallengeSent;
+        client.files = bundle.files;
+        client.files[1].proof = Some(MerkleProof { index: 2, ..tree.proof(3).unwrap() });
+        assert!(matches!(client.on_settlement_files(&settlement), Err(FdeError::HashMismatch)));
+    }
+
+    #[test]
+    fn test_aes_ctr_archive() {
+        // an archive encrypted with AES-CTR beforehand, whose length is not a multiple of a block
+        let data = b"an archive encrypted with AES-128 in counter mode";
+        let (key, iv) = ([7u8; 16], [0xf0u8; 16]);
+        let mut stream = AesCtrStream::<bool>::new(to_array(&bytes_to_bits(&key)), to_array(&bytes_to_bits(&iv)), &());
+        let archive_bits = symmetric_enc(bytes_to_bits(data), &mut stream);
+        let archive: Vec<u8> = archive_bits.chunks(8).map(|byte| byte.iter().rev().fold(0u8, |acc, &b| acc << 1 | b as u8)).collect();
+
+        // the archive is sent as it is, and the padding is encrypted as if it had been with the data
+        let (server, bundle) = Server2::with_aes_ctr_archive(&archive, key, iv, HashFunction::Sha3_256).unwrap();
+        assert_eq!(bundle.cipher, SymmetricCipher::Aes128Ctr);
+        let file = &bundle.files[0];
+        assert_eq!(file.sym_ct[..archive_bits.len()], archive_bits);
+        let padded = HashFunction::Sha3_256.sponge().pad_bytes(data);
+        assert_eq!(SymmetricCipher::Aes128Ctr.symmetric_enc(padded, &bytes_to_bits(&key), &file.iv).unwrap(), file.sym_ct);
+        assert_eq!(bundle.hash_key, HashFunction::Sha3_256.hex_digest(&key));
+
+        let mut client = Client2::new(hex_sha3(data));
+        client.state = Client2State::ChallengeSent;
+        client.files = bundle.files;
+        client.cipher = bundle.cipher;
+        let settlement = Settlement { status: SUCCESS, key: server.sym_key.clone() };
+        assert_eq!(client.on_settlement(&settlement).unwrap(), data.to_vec());
+    }
+
+    #[test]
+    fn test_contract_over_channels() {
+        use crate::prot_utils::new_session_id;
+        use crate::transport::{recv_message, send_message, ChannelTransport, FramedTransport};
+
+        // the contract runs in its own thread, the test plays the client and the server
+        let session_id = new_session_id();
+        let (client_end, sc_client_end) = ChannelTransport::pair();
+        let (server_end, sc_server_end) = ChannelTransport::pair();
+        let mut client_end = FramedTransport::new(client_end, session_id);
+        let mut server_end = FramedTransport::new(server_end, session_id);
+        let handle = std::thread::spawn(move || {
+            let mut contract = Contract2::new();
+            let mut sc_client_end = FramedTransport::joining(sc_client_end);
+            let (submission, _): (Submission, usize) = recv_message(&mut sc_client_end).unwrap();
+            let mut sc_server_end = FramedTransport::new(sc_server_end, sc_client_end.session_id().unwrap());
+            send_message(&mut sc_server_end, &submission).unwrap();
+            contract.on_submission(submission).unwrap();
+            let (reveal, _): (Reveal, usize) = recv_message(&mut sc_server_end).unwrap();
+            let settlement = contract.on_reveal(reveal).unwrap();
+            send_message(&mut sc_client_end, &settlement).unwrap();
+            send_message(&mut sc_server_end, &settlement).unwrap();
+        });
+
+        let (key, _, buf_key) = get_rand_key_iv();
+        let (a, _, _) = get_rand_abc();
+        let submission = paying_submission(&a, &buf_key, 0);
+        send_message(&mut client_end, &submission).unwrap();
+        let (relayed, _): (Submission, usize) = recv_message(&mut server_end).unwrap();
+        assert_eq!(relayed.hash_a, submission.hash_a);
+        send_message(&mut server_end, &Reveal { status: SUCCESS, key: key.to_vec(), a: a.to_vec() }).unwrap();
+
+        let (settlement, _): (Settlement, usize) = recv_message(&mut client_end).unwrap();
+        assert_eq!(settlement.status, SUCCESS);
+        assert_eq!(settlement.key, key.to_vec());
+        let (settlement, _): (Settlement, usize) = recv_message(&mut server_end).unwrap();
+        assert_eq!(settlement.status, SUCCESS);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,344 @@
+//! This module contains the `Transport` trait used by the binaries to exchange messages,
+//! with a TCP implementation and an in-process implementation based on `std::sync::mpsc`
+//! channels. The latter allows a whole exchange to run inside one process (e.g. with one thread
+//! per role), without binding any port or starting the roles in a specific order. The messages
+//! of the protocols go through a `FramedTransport`, which tags each of them with its type and the
+//! session id of the exchange.
+
+use std::cmp::min;
+use std::io::{self, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::error::FdeError;
+use crate::prot_utils::{decode_frame, encode_frame, prepare_message, read_one_message, FrameHeader, WireMessage};
+
+/// A bidirectional link to a counterparty, which sends and receives whole messages
+pub trait Transport {
+    /// Sends one message
+    fn send(&mut self, msg: &[u8]) -> Result<(), FdeError>;
+
+    /// Waits for the next message
+    fn recv(&mut self) -> Result<Vec<u8>, FdeError>;
+}
+
+/// A transport carrying the messages of one exchange, each in a frame (see `encode_frame`) tagged
+/// with the type of the message and the session id of the exchange
+pub struct FramedTransport<T: Transport> {
+    inner: T,
+    session_id: Option<u64>,
+}
+
+impl<T: Transport> FramedTransport<T> {
+    /// Wraps a transport for the exchange `session_id`, frames from other exchanges are rejected
+    pub fn new(inner: T, session_id: u64) -> Self {
+        FramedTransport { inner, session_id: Some(session_id) }
+    }
+
+    /// Wraps a transport joining the exchange of the first frame received
+    pub fn joining(inner: T) -> Self {
+        FramedTransport { inner, session_id: None }
+    }
+
+    /// Returns the session id, which is unknown before the first frame when joining an exchange
+    pub fn session_id(&self) -> Option<u64> {
+        self.session_id
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+}
+
+/// Serializes `msg` with bincode and sends it in one frame. Returns the number of bytes of the
+/// serialized message
+pub fn send_message<T: Transport, M: WireMessage>(transport: &mut FramedTransport<T>, msg: &M) -> Result<usize, FdeError> {
+    let session_id = transport.session_id.ok_or_else(|| {
+        FdeError::UnexpectedMessage(format!("cannot send {} before joining an exchange", M::NAME))
+    })?;
+    let bytes = bincode::serialize(msg)?;
+    transport.inner.send(&encode_frame(&FrameHeader::for_message::<M>(session_id), &bytes))?;
+    Ok(bytes.len())
+}
+
+/// Waits for the next frame, checks that it carries a message of type `M` of the current exchange
+/// and deserializes it with bincode. Returns the message along with its number of bytes
+pub fn recv_message<T: Transport, M: WireMessage>(transport: &mut FramedTransport<T>) -> Result<(M, usize), FdeError> {
+    let frame = transport.inner.recv()?;
+    let (header, bytes) = decode_frame(&frame)?;
+    header.expect::<M>()?;
+    match transport.session_id {
+        Some(id) if id != header.session_id => {
+            return Err(FdeError::UnexpectedMessage(format!(
+                "{} belongs to session {:016x}, expected session {:016x}", M::NAME, header.session_id, id
+            )));
+        }
+        _ => transport.session_id = Some(header.session_id),
+    }
+    let msg = bincode::deserialize(bytes)?;
+    Ok((msg, bytes.len()))
+}
+
+/// Waits until `deadline` for the next frame (see `recv_message`). Returns `FdeError::Timeout` if it
+/// did not arrive in time
+pub fn recv_message_before<M: WireMessage>(transport: &mut FramedTransport<TcpTransport>, deadline: Option<Instant>) -> Result<(M, usize), FdeError> {
+    let Some(deadline) = deadline else { return recv_message(transport) };
+    let time_left = deadline.saturating_duration_since(Instant::now());
+    if time_left.is_zero() {
+        return Err(FdeError::Timeout);
+    }
+    let stream = &transport.inner.stream;
+    let read_timeout = stream.read_timeout()?;
+    stream.set_read_timeout(Some(read_timeout.map_or(time_left, |t| min(t, time_left))))?;
+    let received = recv_message(transport);
+    transport.inner.stream.set_read_timeout(read_timeout)?;
+    received
+}
+
+// -------------------------------------------- TCP ------------------------------------------------
+
+/// Options of the TCP connections
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// How long to keep retrying to connect to a counterparty which is not listening yet
+    pub connect_timeout: Duration,
+    /// How long a read may wait for the counterparty (forever if `None`)
+    pub read_timeout: Option<Duration>,
+    /// How long a write may wait for the counterparty (forever if `None`)
+    pub write_timeout: Option<Duration>,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        TcpOptions { connect_timeout: Duration::from_secs(120), read_timeout: None, write_timeout: None }
+    }
+}
+
+// First and largest delays between two connection attempts
+const FIRST_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Transport over a TCP stream, each message is prefixed by its length (see `prepare_message`)
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    /// Connects to a counterparty listening on `addr`. The connection is retried with an
+    /// exponential backoff until `options.connect_timeout`, so that the counterparty may be started
+    /// after us
+    pub fn connect<A: ToSocketAddrs>(addr: A, options: &TcpOptions) -> Result<Self, FdeError> {
+        let deadline = Instant::now() + options.connect_timeout;
+        let mut backoff = FIRST_BACKOFF;
+        loop {
+            match TcpStream::connect(&addr) {
+                Ok(stream) => return TcpTransport::with_options(stream, options),
+                Err(e) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(match e.kind() {
+                            io::ErrorKind::ConnectionRefused => FdeError::Timeout,
+                            _ => e.into(),
+                        });
+                    }
+                    thread::sleep(min(backoff, deadline - now));
+                    backoff = min(backoff * 2, MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    /// Waits for one counterparty to connect to `listener`. The listener should be bound when the
+    /// role starts, so that the counterparty can connect in any order
+    pub fn accept(listener: &TcpListener, options: &TcpOptions) -> Result<Self, FdeError> {
+        let (stream, _) = listener.accept()?;
+        TcpTransport::with_options(stream, options)
+    }
+
+    fn with_options(stream: TcpStream, options: &TcpOptions) -> Result<Self, FdeError> {
+        stream.set_read_timeout(options.read_timeout)?;
+        stream.set_write_timeout(options.write_timeout)?;
+        Ok(TcpTransport { stream })
+    }
+
+    /// Wraps an already connected stream
+    pub fn from_stream(stream: TcpStream) -> Self {
+        TcpTransport { stream }
+    }
+
+    /// Closes the connection in both directions
+    pub fn shutdown(&self) -> Result<(), FdeError> {
+        Ok(self.stream.shutdown(Shutdown::Both)?)
+    }
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, msg: &[u8]) -> Result<(), FdeError> {
+        Ok(self.stream.write_all(prepare_message(msg).as_slice())?)
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, FdeError> {
+        read_one_message(&self.stream)
+    }
+}
+
+// ------------------------------------------ IN-MEMORY --------------------------------------------
+
+/// Transport between two ends living in the same process
+pub struct ChannelTransport {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+}
+
+impl ChannelTransport {
+    /// Returns the two connected ends of an in-memory link
+    pub fn pair() -> (ChannelTransport, ChannelTransport) {
+        let (tx_a, rx_a) = channel();
+        let (tx_b, rx_b) = channel();
+        (ChannelTransport { tx: tx_a, rx: rx_b }, ChannelTransport { tx: tx_b, rx: rx_a })
+    }
+}
+
+impl Transport for ChannelTransport {
+    fn send(&mut self, msg: &[u8]) -> Result<(), FdeError> {
+        self.tx.send(msg.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "counterparty hung up").into())
+    }
+
+    fn recv(&mut self) -> Result<Vec<u8>, FdeError> {
+        self.rx.recv()
+            .map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "counterparty hung up").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Ping(String);
+
+    impl WireMessage for Ping {
+        const PROTOCOL: u8 = 0;
+        const TAG: u8 = 1;
+        const NAME: &'static str = "Ping";
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Pong(u8);
+
+    impl WireMessage for Pong {
+        const PROTOCOL: u8 = 0;
+        const TAG: u8 = 2;
+        const NAME: &'static str = "Pong";
+    }
+
+    #[test]
+    fn test_channel_transport() {
+        let (mut a, mut b) = ChannelTransport::pair();
+        a.send(&[1, 2, 3]).unwrap();
+        assert_eq!(b.recv().unwrap(), vec![1, 2, 3]);
+
+        b.send(&[]).unwrap();
+        assert_eq!(a.recv().unwrap(), Vec::<u8>::new());
+
+        drop(b);
+        assert!(matches!(a.recv(), Err(FdeError::Io(_))));
+        assert!(matches!(a.send(&[0]), Err(FdeError::Io(_))));
+    }
+
+    #[test]
+    fn test_framed_transport() {
+        let (a, b) = ChannelTransport::pair();
+        let mut a = FramedTransport::new(a, 42);
+        let mut b = FramedTransport::joining(b);
+        assert!(matches!(send_message(&mut b, &Pong(0)), Err(FdeError::UnexpectedMessage(_))));
+
+        send_message(&mut a, &Ping(String::from("Ha"))).unwrap();
+        let (msg, _): (Ping, usize) = recv_message(&mut b).unwrap();
+        assert_eq!(msg, Ping(String::from("Ha")));
+        assert_eq!(b.session_id(), Some(42));
+
+        // a message of another type is rejected
+        send_message(&mut b, &Pong(1)).unwrap();
+        assert!(matches!(recv_message::<_, Ping>(&mut a), Err(FdeError::UnexpectedMessage(_))));
+
+        // so are a message from another exchange and a raw message without header
+        let mut other = FramedTransport::new(b.inner, 43);
+        send_message(&mut other, &Pong(2)).unwrap();
+        assert!(matches!(recv_message::<_, Pong>(&mut a), Err(FdeError::UnexpectedMessage(_))));
+        other.inner.send(&[0, 1]).unwrap();
+        assert!(matches!(recv_message::<_, Pong>(&mut a), Err(FdeError::BadLength { .. })));
+    }
+
+    #[test]
+    fn test_tcp_transport() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let mut t = TcpTransport::from_stream(listener.accept().unwrap().0);
+            let msg = t.recv().unwrap();
+            t.send(&msg).unwrap();
+        });
+
+        let mut t = TcpTransport::connect(addr, &TcpOptions::default()).unwrap();
+        t.send(&[4, 5, 6]).unwrap();
+        assert_eq!(t.recv().unwrap(), vec![4, 5, 6]);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_tcp_connect_retry() {
+        // get a free port, and only start listening on it after the counterparty tried to connect
+        let addr = TcpListener::bind(("127.0.0.1", 0)).unwrap().local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            let listener = TcpListener::bind(addr).unwrap();
+            let mut t = TcpTransport::accept(&listener, &TcpOptions::default()).unwrap();
+            t.send(&[7]).unwrap();
+        });
+        let mut t = TcpTransport::connect(addr, &TcpOptions::default()).unwrap();
+        assert_eq!(t.recv().unwrap(), vec![7]);
+        handle.join().unwrap();
+
+        // nobody ever listens
+        let options = TcpOptions { connect_timeout: Duration::from_millis(200), ..TcpOptions::default() };
+        assert!(matches!(TcpTransport::connect(addr, &options), Err(FdeError::Timeout)));
+    }
+
+    #[test]
+    fn test_recv_message_before() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut a = FramedTransport::new(TcpTransport::connect(addr, &TcpOptions::default()).unwrap(), 1);
+        let mut b = FramedTransport::new(TcpTransport::accept(&listener, &TcpOptions::default()).unwrap(), 1);
+
+        send_message(&mut a, &Pong(3)).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(60);
+        assert_eq!(recv_message_before::<Pong>(&mut b, Some(deadline)).unwrap().0, Pong(3));
+
+        // the deadline passes while waiting, or has already passed
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(200);
+        assert!(matches!(recv_message_before::<Pong>(&mut b, Some(deadline)), Err(FdeError::Timeout)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(matches!(recv_message_before::<Pong>(&mut b, Some(start)), Err(FdeError::Timeout)));
+        assert_eq!(b.get_ref().stream.read_timeout().unwrap(), None);
+    }
+
+    #[test]
+    fn test_tcp_read_timeout() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let options = TcpOptions { read_timeout: Some(Duration::from_millis(200)), ..TcpOptions::default() };
+        let mut t = TcpTransport::connect(addr, &options).unwrap();
+
+        // the counterparty accepts the connection but stalls
+        let _stalled = TcpTransport::accept(&listener, &TcpOptions::default()).unwrap();
+        let start = Instant::now();
+        assert!(matches!(t.recv(), Err(FdeError::Timeout)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}